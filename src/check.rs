@@ -0,0 +1,227 @@
+use macroquad::prelude::*;
+
+use crate::defaults::*;
+use crate::diagnostic::*;
+use crate::document::*;
+use crate::settings::*;
+use crate::slide::*;
+use crate::style::*;
+use crate::utils::*;
+use crate::wrap::*;
+
+/// loads a font for measuring only, without a window
+fn load_headless_font(path: Option<&str>, fallback: &[u8]) -> Result<fontdue::Font, String> {
+    let data = match path {
+        Some(path) => std::fs::read(path).map_err(|e| format!("failed to read font `{}`: {}", path, e))?,
+        None => fallback.to_vec(),
+    };
+
+    fontdue::Font::from_bytes(data, fontdue::FontSettings::default()).map_err(|e| match path {
+        Some(path) => format!("failed to load font `{}`: {}", path, e),
+        None => format!("failed to load embedded font: {}", e),
+    })
+}
+
+/// validates a presentation without opening a window, returns the process exit code
+pub fn run(input_path: &str, settings: &Settings) -> i32 {
+    let content = match std::fs::read_to_string(input_path) {
+        Ok(content) => content,
+        Err(e) => {
+            report(&[Diagnostic::file_error(format!("failed to read `{}`: {}", input_path, e))], input_path);
+            return 1;
+        }
+    };
+
+    let (slide_count, diagnostics) = diagnose(&content, settings);
+    report(&diagnostics, input_path);
+
+    let errors = diagnostics.iter().filter(|d| d.is_error()).count();
+    let warnings = diagnostics.len() - errors;
+    println!(
+        "checked `{}`: {}, {}, {}",
+        input_path,
+        pluralize(slide_count, "slide"),
+        pluralize(errors, "error"),
+        pluralize(warnings, "warning")
+    );
+
+    exit_code(&diagnostics, settings.deny_warnings.value)
+}
+
+/// 1 when there are errors, or warnings and they are denied
+fn exit_code(diagnostics: &[Diagnostic], deny_warnings: bool) -> i32 {
+    let errors = diagnostics.iter().any(|d| d.is_error());
+    if errors || (deny_warnings && !diagnostics.is_empty()) { 1 } else { 0 }
+}
+
+/// everything check finds in a presentation: parse errors, images and fonts that do not load
+/// and text fitted below the readable size. Returns the number of slides and the diagnostics
+/// in source order, problems with whole files first
+fn diagnose(content: &str, settings: &Settings) -> (usize, Vec<Diagnostic>) {
+    let virtual_screen_size = settings.virtual_resolution.value;
+    let min_font_size = settings
+        .min_font_size
+        .value
+        .unwrap_or((virtual_screen_size.y / 30f32) as u16);
+
+    let (deck, mut diagnostics) = parse_deck(content);
+
+    let font = load_headless_font(settings.font_path.value.as_deref(), DEFAULT_FONT);
+    let mono_font = load_headless_font(settings.mono_font_path.value.as_deref(), MONO_FONT);
+    let style_font = |path: &Option<String>| path.as_deref().map(|path| load_headless_font(Some(path), &[]));
+    let bold_font = style_font(&settings.bold_font_path.value);
+    let italic_font = style_font(&settings.italic_font_path.value);
+
+    for font in [Some(&font), Some(&mono_font), bold_font.as_ref(), italic_font.as_ref()].into_iter().flatten() {
+        if let Err(e) = font {
+            diagnostics.push(Diagnostic::file_error(e.clone()));
+        }
+    }
+
+    // measured once for the whole deck, like when presenting
+    let measure = match (&font, &mono_font) {
+        (Ok(font), Ok(mono_font)) => Some((
+            CachedFaces::new(&FontFaces {
+                regular: font,
+                bold: bold_font.as_ref().and_then(|f| f.as_ref().ok()),
+                italic: italic_font.as_ref().and_then(|f| f.as_ref().ok()),
+            }),
+            MeasureCache::new(mono_font),
+        )),
+        _ => None,
+    };
+
+    if let Some(path) = &settings.bg_image_path.value
+        && let Err(e) = decode_image(path)
+    {
+        diagnostics.push(Diagnostic::file_error(format!("failed to load background image `{}`: {}", path, e)));
+    }
+
+    for spec in &deck.slides {
+        if let Some(img_path) = &spec.image {
+            if img_path.is_empty() {
+                continue; // already reported by the parser
+            }
+            if let Err(e) = decode_image(img_path) {
+                diagnostics.push(image_diagnostic(&deck, spec, img_path, &e));
+            }
+        }
+
+        if let Some(BackgroundOverride::Image { path, line, .. }) = &spec.directives.background
+            && let Err(e) = decode_image(path)
+        {
+            diagnostics.push(image_diagnostic_at(&deck, *line, spec.num, path, &e));
+        }
+
+        let (Some((faces, mono_font)), Some(_)) = (&measure, spec.text()) else {
+            continue;
+        };
+        let faces = faces.faces();
+
+        let wrap = spec.directives.wrap.unwrap_or(settings.wrap.value);
+        let (lines, _) = slide_lines(spec, wrap.then_some(&faces), &virtual_screen_size);
+        if let Some(size) = fit_font_size(spec.slide_type, &lines, &faces, mono_font, &virtual_screen_size)
+            && size < min_font_size
+        {
+            let line_no = first_text_line(&deck, spec);
+            let line = deck.source_line(line_no);
+            diagnostics.push(
+                Diagnostic::warning(
+                    format!("text is fitted to {}px, below the readable {}px", size, min_font_size),
+                    line_no,
+                    1,
+                    line.chars().count(),
+                    line,
+                )
+                .in_slide(spec.num)
+                .with_help(if wrap || spec.slide_type == SlideType::Code {
+                    "shorten the longest line or split the slide"
+                } else {
+                    "shorten the longest line, split the slide or turn on wrap"
+                }),
+            );
+        }
+    }
+
+    diagnostics.sort_by_key(|d| d.line);
+    (deck.slides.len(), diagnostics)
+}
+
+/// line number of the first line that is rendered as text, falls back to the slide start
+fn first_text_line(deck: &Deck, spec: &SlideSpec) -> usize {
+    let first = match spec.slide_type {
+        SlideType::Code | SlideType::TextImage => spec.span.start_line + 1,
+        _ => spec.span.start_line,
+    };
+
+    (first..=spec.span.end_line)
+        .find(|&n| {
+            let line = deck.source_line(n).trim_start();
+            !line.is_empty() && !line.starts_with('|')
+        })
+        .unwrap_or(spec.span.start_line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_text_is_a_warning() {
+        let mut settings = Settings::default();
+        settings.min_font_size.value = Some(300);
+        let content = "Big\n\nA line far too long to be shown at three hundred pixels\n";
+
+        let (slides, diagnostics) = diagnose(content, &settings);
+        assert_eq!(slides, 2);
+        assert_eq!(diagnostics.len(), 1);
+        let warning = &diagnostics[0];
+        assert!(!warning.is_error());
+        assert_eq!((warning.line, warning.slide), (3, Some(2)));
+        assert!(warning.message.starts_with("text is fitted to"), "{}", warning.message);
+        assert!(warning.message.ends_with("below the readable 300px"), "{}", warning.message);
+
+        // the same deck is fine with the default minimum
+        assert!(diagnose(content, &Settings::default()).1.is_empty());
+    }
+
+    #[test]
+    fn missing_images_are_errors() {
+        let content = "@missing.png\nCaption\n\n% background: nowhere.png fit\nText\n\n% background: none\nPlain\n";
+
+        let (_, diagnostics) = diagnose(content, &Settings::default());
+        let lines: Vec<usize> = diagnostics.iter().map(|d| d.line).collect();
+        assert_eq!(lines, [1, 4]);
+        assert!(diagnostics[0].message.starts_with("failed to load image `missing.png`"));
+        assert!(diagnostics[1].message.starts_with("failed to load image `nowhere.png`"));
+        assert!(diagnostics.iter().all(Diagnostic::is_error));
+        assert_eq!(diagnostics[1].slide, Some(2));
+        assert_eq!(diagnostics[1].column, 15);
+    }
+
+    #[test]
+    fn diagnostics_are_in_source_order() {
+        let mut settings = Settings::default();
+        settings.bg_image_path.value = Some("no-background.png".to_string());
+        // the parser finds the directive first, the image is only looked at afterwards
+        let content = "@missing.png\n\n% oops: 1\nText\n";
+
+        let (_, diagnostics) = diagnose(content, &settings);
+        let lines: Vec<usize> = diagnostics.iter().map(|d| d.line).collect();
+        assert_eq!(lines, [0, 1, 3]);
+        assert!(diagnostics[0].message.starts_with("failed to load background image `no-background.png`"));
+    }
+
+    #[test]
+    fn denied_warnings_fail_the_check() {
+        let warning = Diagnostic::warning("small", 1, 1, 1, "x");
+        let error = Diagnostic::error("broken", 1, 1, 1, "x");
+
+        assert_eq!(exit_code(&[], false), 0);
+        assert_eq!(exit_code(&[], true), 0);
+        assert_eq!(exit_code(std::slice::from_ref(&warning), false), 0);
+        assert_eq!(exit_code(std::slice::from_ref(&warning), true), 1);
+        assert_eq!(exit_code(&[warning, error.clone()], false), 1);
+        assert_eq!(exit_code(&[error], false), 1);
+    }
+}
@@ -0,0 +1,512 @@
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command {
+    Present,
+    Check,
+    Export,
+    Notes,
+    Stats,
+    Config,
+}
+
+const COMMANDS: &[(Command, &str, &str)] = &[
+    (Command::Present, "present", "show the presentation in a window (default)"),
+    (Command::Check, "check", "validate the presentation without opening a window"),
+    (Command::Export, "export", "render every slide to png files, a pdf or a html page"),
+    (Command::Notes, "notes", "print the speaker notes of every slide"),
+    (Command::Stats, "stats", "print slide, word and image counts"),
+    (Command::Config, "config", "print the effective settings, for a presentation if a path is given"),
+];
+
+const LAYOUT: &[Command] = &[Command::Present, Command::Check, Command::Export];
+
+/// command line option, the same table drives parsing and --help
+pub struct Opt {
+    pub short: Option<&'static str>,
+    pub long: &'static str,
+    /// names of the required values, empty for flags
+    pub values: &'static [&'static str],
+    /// optional values that follow the required ones, each one is taken only if it is one of the keywords
+    pub optional: &'static [&'static [&'static str]],
+    pub help: &'static str,
+    pub commands: &'static [Command],
+}
+
+pub const OPTIONS: &[Opt] = &[
+    Opt {
+        short: Some("-t"),
+        long: "--theme",
+        values: &["dark|light|<bg_hex>x<font_hex>"],
+        optional: &[],
+        help: "Set theme",
+        commands: LAYOUT,
+    },
+    Opt {
+        short: Some("-l"),
+        long: "--linear",
+        values: &[],
+        optional: &[],
+        help: "set texture filtering for images to linear, default is nearest",
+        commands: LAYOUT,
+    },
+    Opt {
+        short: Some("-f"),
+        long: "--font",
+        values: &["<path/to/font>"],
+        optional: &[],
+        help: "Use a custom font",
+        commands: LAYOUT,
+    },
+    Opt {
+        short: Some("-m"),
+        long: "--mono-font",
+        values: &["<path/to/font>"],
+        optional: &[],
+        help: "Use a custom font for code slides",
+        commands: LAYOUT,
+    },
+    Opt {
+        short: None,
+        long: "--bold-font",
+        values: &["<path/to/font>"],
+        optional: &[],
+        help: "font for *bold* text, faked from the regular font by default",
+        commands: LAYOUT,
+    },
+    Opt {
+        short: None,
+        long: "--italic-font",
+        values: &["<path/to/font>"],
+        optional: &[],
+        help: "font for _italic_ text, faked from the regular font by default",
+        commands: LAYOUT,
+    },
+    Opt {
+        short: Some("-r"),
+        long: "--resolution",
+        values: &["<width>x<height>"],
+        optional: &[],
+        help: "Set virtual resolution (default 1600x1200) (max 3840x3840)",
+        commands: LAYOUT,
+    },
+    Opt {
+        short: Some("-n"),
+        long: "--numbering",
+        values: &[],
+        optional: &[],
+        help: "turn on the slide numbering",
+        commands: LAYOUT,
+    },
+    Opt {
+        short: Some("-a"),
+        long: "--numbering-anchor",
+        values: &["bl|bc|br|tl|tc|tr"],
+        optional: &[],
+        help: "position of the slide number (default bl, bottom left)",
+        commands: LAYOUT,
+    },
+    Opt {
+        short: Some("-b"),
+        long: "--background",
+        values: &["<path/to/image.png>"],
+        optional: &[&["linear", "l", "nearest", "n"], &["fit", "fill"]],
+        help: "background image, filtering defaults to linear and mode to fill",
+        commands: LAYOUT,
+    },
+    Opt {
+        short: None,
+        long: "--align",
+        values: &["block|left|center|right"],
+        optional: &[],
+        help: "alignment of text lines, block centers the block with left aligned lines (default)",
+        commands: LAYOUT,
+    },
+    Opt {
+        short: None,
+        long: "--valign",
+        values: &["top|middle|bottom"],
+        optional: &[],
+        help: "vertical position of text (default middle)",
+        commands: LAYOUT,
+    },
+    Opt {
+        short: Some("-w"),
+        long: "--wrap",
+        values: &[],
+        optional: &[],
+        help: "reflow long text lines so the text can be larger, code is never wrapped",
+        commands: LAYOUT,
+    },
+    Opt {
+        short: None,
+        long: "--transition",
+        values: &["none|fade|slide-left|slide-right|fade-through-bg"],
+        optional: &[],
+        help: "how slides replace each other while presenting (default none), exports never use it",
+        commands: &[Command::Present],
+    },
+    Opt {
+        short: None,
+        long: "--transition-duration",
+        values: &["<seconds>"],
+        optional: &[],
+        help: "length of a transition (default 0.4)",
+        commands: &[Command::Present],
+    },
+    Opt {
+        short: None,
+        long: "--duration",
+        values: &["<time>"],
+        optional: &[],
+        help: "length of the talk like 20 (minutes), 45m or 1h30m, shows the time left and whether you are on pace",
+        commands: &[Command::Present],
+    },
+    Opt {
+        short: None,
+        long: "--pace-warning",
+        values: &["<time>"],
+        optional: &[],
+        help: "how far behind the plan the pace turns yellow (default 30s)",
+        commands: &[Command::Present],
+    },
+    Opt {
+        short: None,
+        long: "--pace-alarm",
+        values: &["<time>"],
+        optional: &[],
+        help: "how far behind the plan the pace turns red (default 2m)",
+        commands: &[Command::Present],
+    },
+    Opt {
+        short: None,
+        long: "--progress-bar",
+        values: &[],
+        optional: &[],
+        help: "thin bar at the bottom of the slides with the part of the talk that is over",
+        commands: &[Command::Present],
+    },
+    Opt {
+        short: None,
+        long: "--timing-report",
+        values: &["<path>"],
+        optional: &[],
+        help: "write how long every slide took to this file on exit instead of printing it",
+        commands: &[Command::Present],
+    },
+    Opt {
+        short: None,
+        long: "--rehearse",
+        values: &[],
+        optional: &[],
+        help: "save how long every slide took to <presentation>.rehearsals.json on exit, later runs are compared with it",
+        commands: &[Command::Present],
+    },
+    Opt {
+        short: Some("-p"),
+        long: "--preview",
+        values: &[],
+        optional: &[],
+        help: "shows next slide in your terminal if there is such",
+        commands: &[Command::Present],
+    },
+    Opt {
+        short: None,
+        long: "--presenter",
+        values: &["off|split|window"],
+        optional: &[],
+        help: "presenter view with notes, next slide and clock: in the window or in a second one (default off)",
+        commands: &[Command::Present],
+    },
+    Opt {
+        short: None,
+        long: "--min-font-size",
+        values: &["<px>"],
+        optional: &[],
+        help: "warn when slide text is fitted below this size (default 1/30 of the height)",
+        commands: &[Command::Check],
+    },
+    Opt {
+        short: Some("-D"),
+        long: "--deny-warnings",
+        values: &[],
+        optional: &[],
+        help: "exit with an error when check reports warnings",
+        commands: &[Command::Check],
+    },
+    Opt {
+        short: None,
+        long: "--format",
+        values: &["png|pdf|html"],
+        optional: &[],
+        help: "output format (default png)",
+        commands: &[Command::Export],
+    },
+    Opt {
+        short: Some("-o"),
+        long: "--out",
+        values: &["<path>"],
+        optional: &[],
+        help: "png: directory for slide-001.png etc. (default .), pdf/html: file (default <presentation>.pdf/.html)",
+        commands: &[Command::Export],
+    },
+    Opt {
+        short: None,
+        long: "--notes",
+        values: &["pages|annotations"],
+        optional: &[],
+        help: "pdf: add speaker notes as extra pages or as annotations",
+        commands: &[Command::Export],
+    },
+    Opt {
+        short: None,
+        long: "--explain",
+        values: &[],
+        optional: &[],
+        help: "also print which layer (default, config, front matter, environment, command line) set each value",
+        commands: &[Command::Config],
+    },
+];
+
+/// option with its values split off
+pub type ParsedOption = (&'static Opt, Vec<String>);
+
+/// what the command line asks for
+pub enum Invocation {
+    Help,
+    Version,
+    Run {
+        command: Command,
+        /// always there except for config, where it is optional
+        input_path: Option<String>,
+        options: Vec<ParsedOption>,
+    },
+}
+
+fn command_name(command: Command) -> &'static str {
+    COMMANDS.iter().find(|(c, _, _)| *c == command).map(|(_, name, _)| *name).unwrap_or("")
+}
+
+pub fn find_option(name: &str) -> Option<&'static Opt> {
+    OPTIONS.iter().find(|opt| opt.long == name || opt.short == Some(name))
+}
+
+/// options with their values and the arguments that are not options, in the order given.
+/// With no command every option is accepted, that is how config files are read
+pub fn split_args(args: &[String], command: Option<Command>) -> Result<(Vec<String>, Vec<ParsedOption>), String> {
+    let mut positional = Vec::new();
+    let mut options = Vec::new();
+    let mut only_positional = false;
+    let mut i = 0;
+
+    while i < args.len() {
+        let arg = &args[i];
+        i += 1;
+
+        if only_positional || !arg.starts_with('-') || arg == "-" {
+            positional.push(arg.clone());
+            continue;
+        }
+
+        if arg == "--" {
+            only_positional = true;
+            continue;
+        }
+
+        // --option=value
+        let (name, inline_value) = match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => (name, Some(value.to_string())),
+            _ => (arg.as_str(), None),
+        };
+
+        let Some(opt) = find_option(name) else {
+            return Err(format!("unknown option '{}'", name));
+        };
+        if let Some(command) = command
+            && command != Command::Config
+            && !opt.commands.contains(&command)
+        {
+            return Err(format!("option '{}' is not supported by '{}'", opt.long, command_name(command)));
+        }
+
+        let mut values = Vec::new();
+        if let Some(value) = inline_value {
+            if opt.values.is_empty() {
+                return Err(format!("option '{}' does not take a value", opt.long));
+            }
+            values.push(value);
+        }
+
+        while values.len() < opt.values.len() {
+            let Some(value) = args.get(i) else {
+                return Err(format!("option '{}' expects {}", opt.long, opt.values[values.len()]));
+            };
+            values.push(value.clone());
+            i += 1;
+        }
+
+        for keywords in opt.optional {
+            match args.get(i) {
+                Some(value) if keywords.contains(&value.as_str()) => {
+                    values.push(value.clone());
+                    i += 1;
+                }
+                _ => break,
+            }
+        }
+
+        options.push((opt, values));
+    }
+
+    Ok((positional, options))
+}
+
+/// one of the flags is given where an option can be, so not as the value of another option or after `--`
+fn has_flag(args: &[String], flags: &[&str]) -> bool {
+    let mut i = 0;
+    while let Some(arg) = args.get(i) {
+        if arg == "--" {
+            return false;
+        }
+        if flags.contains(&arg.as_str()) {
+            return true;
+        }
+        i += 1;
+        if let Some(opt) = find_option(arg) {
+            i += opt.values.len();
+        }
+    }
+    false
+}
+
+/// splits arguments into subcommand, presentation path and options.
+/// args do not include the program name
+pub fn parse_args(args: &[String]) -> Result<Invocation, String> {
+    if has_flag(args, &["-h", "--help"]) {
+        return Ok(Invocation::Help);
+    }
+    if has_flag(args, &["-V", "--version"]) {
+        return Ok(Invocation::Version);
+    }
+
+    let (command, rest) = match args.first().and_then(|a| COMMANDS.iter().find(|(_, name, _)| name == a)) {
+        Some((command, _, _)) => (*command, &args[1..]),
+        None => (Command::Present, args),
+    };
+
+    let (mut positional, options) = split_args(rest, Some(command))?;
+
+    if positional.len() > 1 {
+        return Err(format!(
+            "unexpected argument '{}', the presentation is already '{}'",
+            positional[1], positional[0]
+        ));
+    }
+    let input_path = positional.pop();
+    if input_path.is_none() && command != Command::Config {
+        return Err(format!("'{}' needs a path to the presentation", command_name(command)));
+    }
+
+    Ok(Invocation::Run { command, input_path, options })
+}
+
+fn usage_line(opt: &Opt) -> String {
+    let mut line = match opt.short {
+        Some(short) => format!("{}, {}", short, opt.long),
+        None => opt.long.to_string(),
+    };
+    for value in opt.values {
+        line.push(' ');
+        line.push_str(value);
+    }
+    for keywords in opt.optional {
+        line.push_str(&format!(" [{}]", keywords.join("|")));
+    }
+    format!("{} - {}", line, opt.help)
+}
+
+/// text of --help, the Options block of the README is a copy of it
+pub fn help() -> String {
+    let mut out = String::from(
+        "Usage: reiha [present] <path/to/presentation> <options>\n\
+         Usage: reiha check|export|notes|stats <path/to/presentation> <options>\n\
+         Usage: reiha config [path/to/presentation] [--explain] <options>\n\n\
+         Commands:\n",
+    );
+    for (_, name, help) in COMMANDS {
+        out.push_str(&format!("{} - {}\n", name, help));
+    }
+
+    let sections: &[(&str, &[Command])] = &[
+        ("Options", LAYOUT),
+        ("Present options", &[Command::Present]),
+        ("Check options", &[Command::Check]),
+        ("Export options", &[Command::Export]),
+        ("Config options (every option above is accepted too)", &[Command::Config]),
+    ];
+    for (title, commands) in sections {
+        out.push_str(&format!("\n{}:\n", title));
+        for opt in OPTIONS.iter().filter(|opt| opt.commands == *commands) {
+            out.push_str(&usage_line(opt));
+            out.push('\n');
+        }
+    }
+    out.push_str(
+        "\nGeneral:\n\
+         -h, --help - print this help\n\
+         -V, --version - print the version\n",
+    );
+    out.push_str(&format!(
+        "\nSettings are layered, later ones win: defaults, /home/$USER/.config/reiha/config and config.toml,\n\
+         reiha.config and reiha.toml next to the presentation, its `---` front matter,\n\
+         REIHA_<OPTION> environment variables (e.g. REIHA_NUMBERING_ANCHOR=br) and the command line.\n\
+         Relative paths in deck config files and front matter start at the presentation's directory.\n\
+         ____________________________________________________________\n\
+         Reiha | ver{} | bk\n",
+        env!("CARGO_PKG_VERSION")
+    ));
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn help_is_only_an_option() {
+        assert!(matches!(parse_args(&args("export talk.md -h")), Ok(Invocation::Help)));
+        assert!(matches!(parse_args(&args("--help")), Ok(Invocation::Help)));
+        assert!(matches!(parse_args(&args("-V")), Ok(Invocation::Version)));
+
+        let Ok(Invocation::Run { options, .. }) = parse_args(&args("export talk.md --out -h")) else {
+            panic!("expected a run");
+        };
+        assert_eq!(options[0].0.long, "--out");
+        assert_eq!(options[0].1, ["-h"]);
+
+        let Ok(Invocation::Run { input_path, .. }) = parse_args(&args("check -- --help")) else {
+            panic!("expected a run");
+        };
+        assert_eq!(input_path.as_deref(), Some("--help"));
+    }
+
+    #[test]
+    fn readme_lists_the_options_of_help() {
+        let readme = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/README.md")).unwrap();
+        // from the Options: title to the --version line, in both
+        let options_block = |text: &str| -> Vec<String> {
+            text.lines()
+                .skip_while(|line| *line != "Options:")
+                .take_while(|line| !line.starts_with("-V, --version"))
+                .map(str::to_string)
+                .collect()
+        };
+
+        let expected = options_block(&help());
+        assert!(expected.len() > OPTIONS.len());
+        assert_eq!(options_block(&readme), expected, "the README Options block is out of date with --help");
+    }
+}
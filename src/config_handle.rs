@@ -1,140 +1,253 @@
-use macroquad::prelude::*;
-use std::path::PathBuf;
-
-use crate::slide::NumberingAnchor;
-use crate::theming::*;
-use crate::utils::*;
-
-pub struct Config {
-    pub theme: Option<Theme>,
-    pub filtering: Option<FilterMode>,
-    pub font_path: Option<String>,
-    pub mono_font_path: Option<String>,
-    pub virtual_resolution: Option<Vec2>,
-    pub numbering: Option<bool>,
-    pub numbering_anchor: Option<NumberingAnchor>,
-    pub bg_image_path: Option<String>,
-    pub bg_filter: Option<bool>,
-    pub bg_mode: Option<BackgroundMode>,
-    pub preview: Option<bool>,
+use std::path::{Path, PathBuf};
+
+use crate::cli::*;
+use crate::keys::*;
+
+/// config files looked up next to the presentation, later ones win
+pub const DECK_CONFIG_NAMES: &[&str] = &["reiha.config", "reiha.toml"];
+
+/// keys that map one to one onto a command line option, as (section, key, option)
+const TOML_OPTIONS: &[(&str, &str, &str)] = &[
+    ("display", "resolution", "--resolution"),
+    ("fonts", "regular", "--font"),
+    ("fonts", "mono", "--mono-font"),
+    ("fonts", "bold", "--bold-font"),
+    ("fonts", "italic", "--italic-font"),
+    ("numbering", "enabled", "--numbering"),
+    ("numbering", "anchor", "--numbering-anchor"),
+    ("presenter", "preview", "--preview"),
+    ("presenter", "view", "--presenter"),
+    ("text", "align", "--align"),
+    ("text", "valign", "--valign"),
+    ("text", "wrap", "--wrap"),
+    ("transition", "effect", "--transition"),
+    ("transition", "duration", "--transition-duration"),
+    ("timer", "duration", "--duration"),
+    ("timer", "warning", "--pace-warning"),
+    ("timer", "alarm", "--pace-alarm"),
+    ("timer", "progress_bar", "--progress-bar"),
+    ("timer", "report", "--timing-report"),
+    ("timer", "rehearse", "--rehearse"),
+];
+
+/// keys of every section, used for unknown key errors
+const TOML_SECTIONS: &[(&str, &[&str])] = &[
+    ("theme", &["name", "background", "font"]),
+    ("fonts", &["regular", "mono", "bold", "italic"]),
+    ("display", &["resolution", "filtering"]),
+    ("background", &["image", "filter", "mode"]),
+    ("numbering", &["enabled", "anchor"]),
+    ("presenter", &["preview", "view"]),
+    ("text", &["align", "valign", "wrap"]),
+    ("transition", &["effect", "duration"]),
+    ("timer", &["duration", "warning", "alarm", "progress_bar", "report", "rehearse"]),
+    ("keys", &["next", "previous", "fullscreen", "swap_theme", "preview", "numbering", "presenter", "pause", "reset_timer", "quit"]),
+];
+
+/// what a config file sets, in the same shape as command line options
+#[derive(Default)]
+pub struct ConfigFile {
+    pub options: Vec<ParsedOption>,
+    pub keys: Vec<(Action, Vec<Trigger>)>,
+}
+
+/// legacy config first, config.toml after it so it wins
+pub fn global_config_paths() -> Vec<PathBuf> {
+    match dirs::home_dir() {
+        Some(home) => vec![home.join(".config/reiha/config"), home.join(".config/reiha/config.toml")],
+        None => Vec::new(),
+    }
 }
 
-impl Config {
-    pub fn from_file() -> Self {
-        let mut config = Config {
-            theme: None,
-            filtering: None,
-            font_path: None,
-            mono_font_path: None,
-            virtual_resolution: None,
-            numbering: None,
-            numbering_anchor: None,
-            bg_image_path: None,
-            bg_filter: None,
-            bg_mode: None,
-            preview: None,
+/// reads a config file, None if there is no such file.
+/// .toml files have sections, anything else is command line options line by line
+pub fn read_config_file(path: &Path) -> Result<Option<ConfigFile>, String> {
+    let Ok(content) = std::fs::read_to_string(path) else {
+        return Ok(None);
+    };
+
+    let config = if path.extension().is_some_and(|ext| ext == "toml") {
+        parse_toml_config(&content)
+    } else {
+        parse_option_config(&content)
+    };
+
+    config.map(Some).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn parse_option_config(content: &str) -> Result<ConfigFile, String> {
+    let args = content
+        .lines()
+        .flat_map(|line| line.split_whitespace())
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+
+    let (positional, options) = split_args(&args, None)?;
+    if let Some(arg) = positional.first() {
+        return Err(format!("unexpected '{}', expected an option", arg));
+    }
+
+    Ok(ConfigFile { options, keys: Vec::new() })
+}
+
+fn toml_value(value: &toml::Value, key: &str) -> Result<String, String> {
+    match value {
+        toml::Value::String(s) => Ok(s.clone()),
+        toml::Value::Boolean(b) => Ok(b.to_string()),
+        toml::Value::Integer(i) => Ok(i.to_string()),
+        toml::Value::Float(f) => Ok(f.to_string()),
+        _ => Err(format!("`{}` must be a string, number or boolean", key)),
+    }
+}
+
+fn option(long: &str, values: Vec<String>) -> ParsedOption {
+    (find_option(long).expect("config keys map onto known options"), values)
+}
+
+fn parse_toml_config(content: &str) -> Result<ConfigFile, String> {
+    let table: toml::Table = content.parse().map_err(|e: toml::de::Error| e.message().to_string())?;
+    let mut config = ConfigFile::default();
+
+    for (section, entries) in &table {
+        let Some((_, known)) = TOML_SECTIONS.iter().find(|(name, _)| name == section) else {
+            let sections: Vec<&str> = TOML_SECTIONS.iter().map(|(name, _)| *name).collect();
+            return Err(format!("unknown section `[{}]`, expected one of: {}", section, sections.join(", ")));
+        };
+        let Some(entries) = entries.as_table() else {
+            return Err(format!("`{}` must be a section", section));
         };
+        if let Some(key) = entries.keys().find(|key| !known.contains(&key.as_str())) {
+            return Err(format!(
+                "unknown key `{}.{}`, expected one of: {}",
+                section,
+                key,
+                known.join(", ")
+            ));
+        }
 
-        let home_dir = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
-        let config_path = home_dir.join(".config/reiha/config");
-
-        if let Ok(lines) = std::fs::read_to_string(config_path) {
-            let args = lines
-                .lines()
-                .flat_map(|line| line.split_whitespace())
-                .map(str::to_string)
-                .collect::<Vec<_>>();
-
-            for i in 0..args.len() {
-                match args[i].as_str() {
-                    "-t" | "--theme" => {
-                        if let Some(value) = args.get(i + 1) {
-                            if value == "dark" {
-                                config.theme = Some(DARK_THEME);
-                            } else if value == "light" {
-                                config.theme = Some(LIGHT_THEME);
-                            } else if value.contains('x') {
-                                let parts: Vec<&str> = value.split('x').collect();
-                                if parts.len() == 2 {
-                                    if let (Ok(bg), Ok(font)) =
-                                        (parse_hex_color(parts[0]), parse_hex_color(parts[1]))
-                                    {
-                                        config.theme = Some(Theme {
-                                            background_color: bg,
-                                            font_color: font,
-                                        });
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    "-l" | "--linear" => {
-                        config.filtering = Some(FilterMode::Linear);
-                    }
-                    "-f" | "--font" => {
-                        if let Some(path) = args.get(i + 1) {
-                            config.font_path = Some(path.clone());
-                        }
-                    }
-                    "-m" | "--mono-font" => {
-                        if let Some(path) = args.get(i + 1) {
-                            config.mono_font_path = Some(path.clone());
-                        }
-                    }
-                    "-r" | "--resolution" => {
-                        if let Some(value) = args.get(i + 1) {
-                            if let Some((w, h)) = value.split_once('x') {
-                                if let (Ok(w), Ok(h)) = (w.parse::<f32>(), h.parse::<f32>()) {
-                                    if w <= 3840.0 && h <= 3840.0 {
-                                        config.virtual_resolution = Some(vec2(w, h));
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    "-n" | "--numbering" => {
-                        config.numbering = Some(true);
-                    }
-                    "-a" | "--numbering-anchor" => {
-                        if let Some(val) = args.get(i + 1) {
-                            match val.as_str() {
-                                "bl" => config.numbering_anchor = Some(NumberingAnchor::BottomLeft),
-                                "bc" => config.numbering_anchor = Some(NumberingAnchor::BottomCenter),
-                                "br" => config.numbering_anchor = Some(NumberingAnchor::BottomRight),
-                                "tl" => config.numbering_anchor = Some(NumberingAnchor::TopLeft),
-                                "tc" => config.numbering_anchor = Some(NumberingAnchor::TopCenter),
-                                "tr" => config.numbering_anchor = Some(NumberingAnchor::TopRight),
-                                _    => config.numbering_anchor = Some(NumberingAnchor::BottomLeft),
-                            }
-                        }
-                    }
-                    "-b" | "--background" => {
-                        if let Some(path) = args.get(i + 1) {
-                            config.bg_image_path = Some(path.clone());
-                        }
-                        if let Some(filter) = args.get(i + 2) {
-                            match filter.as_str() {
-                                "l" | "linear" =>  config.bg_filter = Some(true),
-                                "n" | "nearest" => config.bg_filter = Some(false),
-                                _ => panic!("Incorrect usage of background image! It takes 3 arguments. Second is filtering[nearest[n] and linear[l]]")
-                            }
-                        }
-                        if let Some(mode) = args.get(i + 3) {
-                            match mode.as_str() {
-                                "fit"  => config.bg_mode = Some(BackgroundMode::Fit),
-                                "fill" => config.bg_mode = Some(BackgroundMode::Fill),
-                                _ => panic!("Incorrect usage of background image! It takes 3 arguments. Third is mode[fit and fill]")
-                            }
-                        }
-                    }
+        let get = |key: &str| -> Result<Option<String>, String> {
+            entries.get(key).map(|v| toml_value(v, &format!("{}.{}", section, key))).transpose()
+        };
 
-                    "-p" | "--preview" => {
-                        config.preview = Some(true);
+        match section.as_str() {
+            "theme" => {
+                if let Some(name) = get("name")? {
+                    config.options.push(option("--theme", vec![name]));
+                }
+                match (get("background")?, get("font")?) {
+                    (Some(bg), Some(font)) => config.options.push(option("--theme", vec![format!("{}x{}", bg, font)])),
+                    (None, None) => {}
+                    _ => return Err("`theme.background` and `theme.font` have to be set together".to_string()),
+                }
+            }
+            "display" => {
+                if let Some(filtering) = get("filtering")? {
+                    let linear = match filtering.as_str() {
+                        "linear" => "true",
+                        "nearest" => "false",
+                        _ => return Err(format!("`display.filtering` must be linear or nearest, got '{}'", filtering)),
+                    };
+                    config.options.push(option("--linear", vec![linear.to_string()]));
+                }
+            }
+            "background" => {
+                let extra: Vec<String> = [get("filter")?, get("mode")?].into_iter().flatten().collect();
+                match get("image")? {
+                    Some(image) => config.options.push(option("--background", [vec![image], extra].concat())),
+                    None if !extra.is_empty() => {
+                        return Err("`background.filter` and `background.mode` need `background.image`".to_string());
                     }
-                    _ => {}
+                    None => {}
                 }
             }
+            "keys" => {
+                for (name, value) in entries {
+                    let action = parse_action(name).expect("checked against the known keys");
+                    let names: Vec<&str> = match value {
+                        toml::Value::String(key) => vec![key.as_str()],
+                        toml::Value::Array(keys) => keys.iter().filter_map(|k| k.as_str()).collect(),
+                        _ => Vec::new(),
+                    };
+                    if names.is_empty() || matches!(value, toml::Value::Array(keys) if keys.len() != names.len()) {
+                        return Err(format!("`keys.{}` must be a key name or a list of key names", name));
+                    }
+                    let triggers = names
+                        .into_iter()
+                        .map(parse_trigger)
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(|e| format!("keys.{}: {}", name, e))?;
+                    config.keys.push((action, triggers));
+                }
+            }
+            _ => {}
         }
+
+        for (_, key, long) in TOML_OPTIONS.iter().filter(|(s, _, _)| s == section) {
+            if let Some(value) = get(key)? {
+                config.options.push(option(long, vec![value]));
+            }
+        }
+    }
+
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use macroquad::prelude::{KeyCode, MouseButton};
+
+    use super::*;
+
+    fn options(config: &ConfigFile) -> Vec<(&str, Vec<&str>)> {
         config
+            .options
+            .iter()
+            .map(|(opt, values)| (opt.long, values.iter().map(String::as_str).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn toml_sections_become_options() {
+        let config = parse_toml_config(
+            "[theme]\nbackground = \"202040\"\nfont = \"ffaa00\"\n\n\
+             [display]\nfiltering = \"nearest\"\n\n\
+             [background]\nimage = \"bg.png\"\nmode = \"fit\"\n\n\
+             [timer]\nduration = \"20m\"\nwarning = 30\n\n\
+             [keys]\nnext = [\"J\", \"MouseLeft\"]\n",
+        )
+        .unwrap();
+
+        // sections come in name order
+        assert_eq!(
+            options(&config),
+            [
+                ("--background", vec!["bg.png", "fit"]),
+                ("--linear", vec!["false"]),
+                ("--theme", vec!["202040xffaa00"]),
+                ("--duration", vec!["20m"]),
+                ("--pace-warning", vec!["30"]),
+            ]
+        );
+        assert_eq!(
+            config.keys,
+            [(Action::Next, vec![Trigger::Key(KeyCode::J), Trigger::Mouse(MouseButton::Left)])]
+        );
+    }
+
+    #[test]
+    fn toml_mistakes_are_errors() {
+        let error = |content: &str| parse_toml_config(content).err().unwrap_or_default();
+        assert!(error("[colors]\nred = 1\n").starts_with("unknown section `[colors]`"));
+        assert!(error("[text]\nalign = \"left\"\nsize = 3\n").starts_with("unknown key `text.size`"));
+        assert_eq!(error("[theme]\nfont = \"ffffff\"\n"), "`theme.background` and `theme.font` have to be set together");
+        assert_eq!(error("[text]\nwrap = [true]\n"), "`text.wrap` must be a string, number or boolean");
+        assert_eq!(error("[keys]\nquit = 3\n"), "`keys.quit` must be a key name or a list of key names");
+    }
+
+    #[test]
+    fn option_files_are_command_line_options() {
+        let config = parse_option_config("--numbering\n--align center\n").unwrap();
+        assert_eq!(options(&config), [("--numbering", vec![]), ("--align", vec!["center"])]);
+        assert!(parse_option_config("talk.rh\n").is_err());
     }
 }
@@ -0,0 +1,418 @@
+use std::io::{IsTerminal, Write};
+
+use crate::diagnostic::*;
+use crate::document::clean_notes;
+use crate::pacing::*;
+use crate::rehearsal::*;
+use crate::slide::*;
+
+/// alternate screen with a hidden cursor, LEAVE brings the terminal back as it was
+const ENTER: &str = "\x1b[?1049h\x1b[?25l";
+const LEAVE: &str = "\x1b[?25h\x1b[?1049l";
+
+/// narrower terminals get the next slide under the notes instead of next to them
+const SIDE_BY_SIDE_WIDTH: usize = 100;
+
+/// what the terminal shows while presenting
+pub struct DashboardState<'a> {
+    pub slides: &'a [Slide],
+    pub slide: usize,
+    pub step: usize,
+    /// time, pace and what is left of the talk
+    pub clock: Clock,
+    /// times of the current slide in the saved rehearsals
+    pub comparison: Rehearsed,
+    /// shows the next slide too
+    pub preview: bool,
+    /// problems of the last reload
+    pub diagnostics: &'a [Diagnostic],
+}
+
+/// terminal side of presenting. On a terminal it redraws panes in place on the alternate screen,
+/// when stdout is piped it prints a plain block whenever the slide changes
+pub struct Dashboard {
+    input_path: String,
+    tty: bool,
+    /// last plain block, it is not printed again while nothing changes
+    printed: Option<String>,
+}
+
+impl Dashboard {
+    pub fn new(input_path: &str) -> Self {
+        let tty = std::io::stdout().is_terminal();
+        if tty {
+            print!("{}", ENTER);
+            let _ = std::io::stdout().flush();
+
+            // a panic must not leave the terminal on the alternate screen
+            let hook = std::panic::take_hook();
+            std::panic::set_hook(Box::new(move |info| {
+                print!("{}", LEAVE);
+                let _ = std::io::stdout().flush();
+                hook(info);
+            }));
+        }
+
+        Self { input_path: input_path.to_string(), tty, printed: None }
+    }
+
+    pub fn draw(&mut self, state: &DashboardState) {
+        if state.slides.get(state.slide).is_none() {
+            return;
+        }
+        if self.tty {
+            self.draw_panes(state);
+        } else {
+            self.print_plain(state);
+        }
+    }
+
+    fn draw_panes(&self, state: &DashboardState) {
+        let (width, height) = terminal_size();
+        let slide = &state.slides[state.slide];
+        let mut screen = Vec::new();
+
+        let counter = slide.counter(state.slides.len(), state.step);
+        let mut clock = format!("time {}", state.clock.time_text());
+        let plan = state.clock.plan_text();
+        if !plan.is_empty() {
+            clock = format!("\x1b[{}m{}\x1b[0m   {}", urgency_ansi(state.clock.urgency), plan, clock);
+        }
+        if let Some(rehearsed) = state.comparison.text(state.clock.elapsed, state.clock.on_slide) {
+            let color = if state.comparison.is_long(state.clock.on_slide) { "33" } else { "2" };
+            clock = format!("\x1b[{}m{}\x1b[0m   {}", color, rehearsed, clock);
+        }
+        screen.push(spread(&format!("reiha  {}", self.input_path), &format!("{}   {}", counter, clock), width));
+        screen.push(progress_bar((state.slide + 1) as f32 / state.slides.len() as f32, width));
+
+        let problems: Vec<String> = state
+            .diagnostics
+            .iter()
+            .flat_map(|d| d.render(&self.input_path).lines().map(str::to_string).collect::<Vec<_>>())
+            .collect();
+        let footer_height = problems.len().min(height / 3);
+        let body_height = height.saturating_sub(screen.len() + footer_height);
+
+        let current = Pane::new("Current", slide.terminal_lines(state.step, true));
+        let notes = Pane::new("Notes", slide.comments.as_deref().map(clean_notes).unwrap_or_default());
+        let next = state.preview.then(|| match state.slides.get(state.slide + 1) {
+            Some(next) => Pane::new(&format!("Next, {}", next.counter(state.slides.len(), 0)), next.terminal_lines(0, true)),
+            None => Pane::new("Next", vec!["[ end of the presentation ]".to_string()]),
+        });
+
+        let body = match next {
+            Some(next) if width >= SIDE_BY_SIDE_WIDTH => {
+                let left_width = width / 2;
+                let left = stack(&[current, notes], left_width, body_height);
+                let right = stack(&[next], width - left_width - 1, body_height);
+                left.into_iter().zip(right).map(|(left, right)| format!("{} {}", left, right)).collect()
+            }
+            Some(next) => stack(&[current, notes, next], width, body_height),
+            None => stack(&[current, notes], width, body_height),
+        };
+        screen.extend(body);
+        screen.extend(problems.iter().take(footer_height).map(|line| fit(line, width)));
+
+        // every line is cleared to its end and everything below the last one, so nothing flickers or stays behind
+        let mut out = String::from("\x1b[H");
+        for (i, line) in screen.iter().take(height).enumerate() {
+            if i > 0 {
+                out.push_str("\r\n");
+            }
+            out.push_str(line);
+            out.push_str("\x1b[K");
+        }
+        out.push_str("\x1b[J");
+
+        let mut stdout = std::io::stdout();
+        let _ = stdout.write_all(out.as_bytes()).and_then(|_| stdout.flush());
+    }
+
+    fn print_plain(&mut self, state: &DashboardState) {
+        let slide = &state.slides[state.slide];
+
+        let mut block = format!("[{}]\n", slide.counter(state.slides.len(), state.step));
+        block.push_str("===[ Content ]===============================================\n");
+        for line in slide.terminal_lines(state.step, false) {
+            block.push_str(&format!("{}\n", line));
+        }
+        if let Some(comments) = &slide.comments {
+            block.push_str("\n= =[ Notes ]= = = = = = = = = = = = = = = = = = = = = = = = =\n");
+            for line in clean_notes(comments) {
+                block.push_str(&format!("{}\n", line));
+            }
+        }
+        if state.preview && let Some(next) = state.slides.get(state.slide + 1) {
+            block.push_str("\n___[ Next Slide ]____________________________________________\n");
+            block.push_str(&format!("[{}]\n", next.counter(state.slides.len(), 0)));
+            for line in next.terminal_lines(0, false) {
+                block.push_str(&format!("{}\n", line));
+            }
+        }
+        block.push_str("=============================================================\n");
+
+        // problems are part of what changed, they go to stderr like every other report
+        let problems: String = state.diagnostics.iter().map(|d| d.render(&self.input_path)).collect();
+        let changed = format!("{}{}", block, problems);
+        if self.printed.as_ref() == Some(&changed) {
+            return;
+        }
+
+        let mut time = state.clock.time_text();
+        let plan = state.clock.plan_text();
+        if !plan.is_empty() {
+            time = format!("{}   {}", time, plan);
+        }
+        if let Some(rehearsed) = state.comparison.text(state.clock.elapsed, state.clock.on_slide) {
+            time = format!("{}   {}", time, rehearsed);
+        }
+        println!("[time {}]", time);
+        print!("{}", block);
+        let _ = std::io::stdout().flush();
+        if !state.diagnostics.is_empty() {
+            report(state.diagnostics, &self.input_path);
+        }
+        self.printed = Some(changed);
+    }
+}
+
+impl Drop for Dashboard {
+    fn drop(&mut self) {
+        if self.tty {
+            print!("{}", LEAVE);
+            let _ = std::io::stdout().flush();
+        }
+    }
+}
+
+/// a titled block of lines
+struct Pane {
+    title: String,
+    lines: Vec<String>,
+}
+
+impl Pane {
+    fn new(title: &str, lines: Vec<String>) -> Self {
+        Self { title: title.to_string(), lines }
+    }
+}
+
+/// panes one under another in exactly `height` lines of `width` columns.
+/// Lines are handed out in rounds, so short panes stay whole and tall ones share what is left
+fn stack(panes: &[Pane], width: usize, height: usize) -> Vec<String> {
+    let wanted: Vec<usize> = panes.iter().map(|pane| pane.lines.len() + 1).collect();
+    let mut sizes = vec![0; panes.len()];
+    let mut left = height;
+    while left > 0 && sizes.iter().zip(&wanted).any(|(size, want)| size < want) {
+        for (size, want) in sizes.iter_mut().zip(&wanted) {
+            if left > 0 && *size < *want {
+                *size += 1;
+                left -= 1;
+            }
+        }
+    }
+
+    let mut out = Vec::new();
+    for (pane, size) in panes.iter().zip(sizes) {
+        if size == 0 {
+            continue;
+        }
+        let title = format!("===[ {} ]", pane.title);
+        let fill = width.saturating_sub(text_width(&title));
+        out.push(fit(&format!("{}{}", title, "=".repeat(fill)), width));
+
+        let room = size - 1;
+        if room < pane.lines.len() && room > 0 {
+            out.extend(pane.lines[..room - 1].iter().map(|line| fit(line, width)));
+            let more = format!("\x1b[2m... {} more lines\x1b[0m", pane.lines.len() - room + 1);
+            out.push(fit(&more, width));
+        } else {
+            out.extend(pane.lines[..room].iter().map(|line| fit(line, width)));
+        }
+    }
+
+    out.resize(height, " ".repeat(width));
+    out
+}
+
+/// left and right text on one line of `width` columns
+fn spread(left: &str, right: &str, width: usize) -> String {
+    let gap = width.saturating_sub(text_width(left) + text_width(right)).max(1);
+    fit(&format!("{}{}{}", left, " ".repeat(gap), right), width)
+}
+
+fn progress_bar(fraction: f32, width: usize) -> String {
+    let filled = ((fraction.clamp(0f32, 1f32) * width as f32).round() as usize).min(width);
+    format!("{}\x1b[2m{}\x1b[0m", "#".repeat(filled), "-".repeat(width - filled))
+}
+
+/// columns a character takes in a terminal, CJK and fullwidth forms take two
+fn char_width(c: char) -> usize {
+    match c as u32 {
+        0x1100..=0x115F
+        | 0x2E80..=0xA4CF
+        | 0xAC00..=0xD7A3
+        | 0xF900..=0xFAFF
+        | 0xFE30..=0xFE4F
+        | 0xFF00..=0xFF60
+        | 0xFFE0..=0xFFE6
+        | 0x1F300..=0x1F64F
+        | 0x1F900..=0x1F9FF
+        | 0x20000..=0x3FFFD => 2,
+        _ => 1,
+    }
+}
+
+/// columns a line takes, ANSI escape sequences take none
+fn text_width(line: &str) -> usize {
+    let mut width = 0;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // up to the letter that ends the sequence
+            chars.by_ref().find(|c| c.is_ascii_alphabetic());
+        } else if !c.is_control() {
+            width += char_width(c);
+        }
+    }
+    width
+}
+
+/// cuts a line to `width` columns and pads it with spaces, escape sequences are kept.
+/// Tabs become four spaces, other control characters are dropped
+fn fit(line: &str, width: usize) -> String {
+    let line = line.replace('\t', "    ");
+    let mut out = String::new();
+    let mut used = 0;
+    let mut styled = false;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            out.push(c);
+            for c in chars.by_ref() {
+                out.push(c);
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+            styled = true;
+        } else if !c.is_control() {
+            if used + char_width(c) > width {
+                break;
+            }
+            out.push(c);
+            used += char_width(c);
+        }
+    }
+
+    if styled {
+        out.push_str("\x1b[0m");
+    }
+    out.push_str(&" ".repeat(width - used));
+    out
+}
+
+/// columns and rows of the terminal, COLUMNS and LINES or 80x24 when it can not be asked
+fn terminal_size() -> (usize, usize) {
+    let from_env = |name: &str, default: usize| {
+        std::env::var(name).ok().and_then(|v| v.parse().ok()).filter(|v| *v > 0).unwrap_or(default)
+    };
+    ask_terminal_size().unwrap_or_else(|| (from_env("COLUMNS", 80), from_env("LINES", 24)))
+}
+
+#[cfg(unix)]
+fn ask_terminal_size() -> Option<(usize, usize)> {
+    let mut size = libc::winsize { ws_row: 0, ws_col: 0, ws_xpixel: 0, ws_ypixel: 0 };
+    // SAFETY: TIOCGWINSZ only writes into the winsize it is given
+    let result = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+    (result == 0 && size.ws_col > 0 && size.ws_row > 0).then_some((size.ws_col as usize, size.ws_row as usize))
+}
+
+#[cfg(not(unix))]
+fn ask_terminal_size() -> Option<(usize, usize)> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(texts: &[&str]) -> Vec<String> {
+        texts.iter().map(|text| text.to_string()).collect()
+    }
+
+    #[test]
+    fn wide_characters_take_two_columns() {
+        for c in ['a', 'é', '-'] {
+            assert_eq!(char_width(c), 1, "{}", c);
+        }
+        for c in ['日', '本', 'ア', '한', 'Ａ', '🙂'] {
+            assert_eq!(char_width(c), 2, "{}", c);
+        }
+        assert_eq!(text_width("日本語 ok"), 9);
+        assert_eq!(text_width("\x1b[2m日本\x1b[0m"), 4);
+    }
+
+    #[test]
+    fn fit_cuts_and_pads_to_the_width() {
+        assert_eq!(fit("hello", 3), "hel");
+        assert_eq!(fit("hi", 4), "hi  ");
+        assert_eq!(fit("", 0), "");
+        // a double width character that does not fit whole leaves a space
+        assert_eq!(fit("日本語", 5), "日本 ");
+        assert_eq!(fit("a日本", 2), "a ");
+        // styles are closed when the line is cut inside them
+        assert_eq!(fit("\x1b[2mabc\x1b[0m", 2), "\x1b[2mab\x1b[0m");
+        assert_eq!(fit("\ta\x07b", 7), "    ab ");
+    }
+
+    #[test]
+    fn spread_keeps_a_gap() {
+        assert_eq!(spread("a", "b", 5), "a   b");
+        assert_eq!(spread("日本", "1/3", 8), "日本 1/3");
+        // on a narrow terminal the right side is cut
+        assert_eq!(spread("left", "right", 7), "left ri");
+        assert_eq!(spread("left", "right", 3), "lef");
+    }
+
+    #[test]
+    fn stack_fills_the_height_exactly() {
+        let panes = [Pane::new("Notes", lines(&["one", "two"])), Pane::new("Next", lines(&["slide 2"]))];
+
+        let tall = stack(&panes, 12, 8);
+        assert_eq!(tall.len(), 8);
+        assert_eq!(tall[0], "===[ Notes ]");
+        assert_eq!(tall[1..3], ["one         ", "two         "]);
+        assert_eq!(tall[3], "===[ Next ]=");
+        assert_eq!(tall[4], "slide 2     ");
+        assert!(tall[5..].iter().all(|line| *line == " ".repeat(12)));
+
+        // narrow terminals cut titles and lines alike
+        assert!(stack(&panes, 6, 8).iter().all(|line| text_width(line) == 6));
+    }
+
+    #[test]
+    fn short_terminals_cut_the_tall_panes() {
+        let panes = [
+            Pane::new("Notes", lines(&["1", "2", "3", "4", "5"])),
+            Pane::new("Next", lines(&["slide 2"])),
+        ];
+
+        // the short pane stays whole, the tall one says what it leaves out
+        let short = stack(&panes, 20, 6);
+        assert_eq!(short.len(), 6);
+        assert_eq!(short[1..3], [fit("1", 20), fit("2", 20)]);
+        assert_eq!(short[3], fit("\x1b[2m... 3 more lines\x1b[0m", 20));
+        assert_eq!(short[4], "===[ Next ]=========");
+        assert_eq!(short[5], fit("slide 2", 20));
+
+        // with a line each there is only room for the titles
+        let titles = stack(&panes, 20, 2);
+        assert!(titles[0].starts_with("===[ Notes ]"));
+        assert!(titles[1].starts_with("===[ Next ]"));
+
+        assert_eq!(stack(&panes, 20, 1).len(), 1);
+        assert!(stack(&panes, 20, 0).is_empty());
+    }
+}
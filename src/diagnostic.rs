@@ -0,0 +1,183 @@
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// a problem found in the presentation source, reported rustc-style
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// 1-based, 0 when the problem is not tied to a line
+    pub line: usize,
+    /// 1-based
+    pub column: usize,
+    /// how many characters to underline starting at column
+    pub len: usize,
+    pub slide: Option<u32>,
+    pub snippet: String,
+    pub help: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn error(message: impl Into<String>, line: usize, column: usize, len: usize, snippet: &str) -> Self {
+        Self {
+            severity: Severity::Error,
+            message: message.into(),
+            line,
+            column,
+            len,
+            slide: None,
+            snippet: snippet.to_string(),
+            help: None,
+        }
+    }
+
+    pub fn warning(message: impl Into<String>, line: usize, column: usize, len: usize, snippet: &str) -> Self {
+        Self {
+            severity: Severity::Warning,
+            ..Self::error(message, line, column, len, snippet)
+        }
+    }
+
+    /// problem with the file as a whole, e.g. it can not be read
+    pub fn file_error(message: impl Into<String>) -> Self {
+        Self::error(message, 0, 0, 0, "")
+    }
+
+    pub fn in_slide(mut self, slide: u32) -> Self {
+        self.slide = Some(slide);
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    /// formats the diagnostic the way rustc does, path is the presentation file
+    pub fn render(&self, path: &str) -> String {
+        let label = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+
+        let mut out = format!("{}: {}\n", label, self.message);
+
+        if self.line == 0 {
+            out.push_str(&format!(" --> {}\n", path));
+            return out;
+        }
+
+        let gutter = " ".repeat(self.line.to_string().len());
+        let slide = match self.slide {
+            Some(num) => format!(" (slide {})", num),
+            None => String::new(),
+        };
+
+        out.push_str(&format!("{}--> {}:{}:{}{}\n", gutter, path, self.line, self.column, slide));
+        out.push_str(&format!("{} |\n", gutter));
+        out.push_str(&format!("{} | {}\n", self.line, self.snippet));
+
+        // columns count characters, so pad with the same amount of characters
+        let pad: String = self
+            .snippet
+            .chars()
+            .take(self.column.saturating_sub(1))
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        out.push_str(&format!("{} | {}{}\n", gutter, pad, "^".repeat(self.len.max(1))));
+
+        if let Some(help) = &self.help {
+            out.push_str(&format!("{} |\n", gutter));
+            out.push_str(&format!("{} = help: {}\n", gutter, help));
+        }
+
+        out
+    }
+}
+
+/// "1 error", "2 errors"
+pub fn pluralize(n: usize, word: &str) -> String {
+    if n == 1 {
+        format!("1 {}", word)
+    } else {
+        format!("{} {}s", n, word)
+    }
+}
+
+pub fn has_errors(diagnostics: &[Diagnostic]) -> bool {
+    diagnostics.iter().any(Diagnostic::is_error)
+}
+
+/// prints every diagnostic to stderr followed by a summary line
+pub fn report(diagnostics: &[Diagnostic], path: &str) {
+    for diagnostic in diagnostics {
+        eprintln!("{}", diagnostic.render(path));
+    }
+
+    let errors = diagnostics.iter().filter(|d| d.is_error()).count();
+    let warnings = diagnostics.len() - errors;
+
+    if errors > 0 {
+        if warnings > 0 {
+            eprintln!(
+                "error: could not load `{}` due to {}; {} emitted",
+                path,
+                pluralize(errors, "previous error"),
+                pluralize(warnings, "warning")
+            );
+        } else {
+            eprintln!("error: could not load `{}` due to {}", path, pluralize(errors, "previous error"));
+        }
+    } else if warnings > 0 {
+        eprintln!("warning: `{}` generated {}", path, pluralize(warnings, "warning"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_like_rustc() {
+        let diagnostic = Diagnostic::error("unknown directive `\\oops`", 12, 3, 5, "  \\oops here")
+            .in_slide(4)
+            .with_help("known directives are: bg-color");
+        assert_eq!(
+            diagnostic.render("talk.rh"),
+            concat!(
+                "error: unknown directive `\\oops`\n",
+                "  --> talk.rh:12:3 (slide 4)\n",
+                "   |\n",
+                "12 |   \\oops here\n",
+                "   |   ^^^^^\n",
+                "   |\n",
+                "   = help: known directives are: bg-color\n",
+            )
+        );
+    }
+
+    #[test]
+    fn underline_counts_characters_and_keeps_tabs() {
+        let diagnostic = Diagnostic::warning("text after `~` is ignored", 1, 4, 0, "\t日本~x");
+        assert_eq!(
+            diagnostic.render("t.rh"),
+            "warning: text after `~` is ignored\n --> t.rh:1:4\n  |\n1 | \t日本~x\n  | \t  ^\n"
+        );
+    }
+
+    #[test]
+    fn file_errors_have_no_snippet() {
+        let diagnostic = Diagnostic::file_error("failed to read `t.rh`");
+        assert_eq!(diagnostic.render("t.rh"), "error: failed to read `t.rh`\n --> t.rh\n");
+        assert!(has_errors(&[diagnostic]));
+        assert_eq!(pluralize(1, "error"), "1 error");
+        assert_eq!(pluralize(2, "warning"), "2 warnings");
+    }
+}
@@ -0,0 +1,1174 @@
+use crate::diagnostic::*;
+use crate::highlight::{Language, parse_language};
+use crate::markup::{COLOR_NAMES, parse_line};
+
+/// kind of content a slide holds
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SlideType {
+    Empty,
+    Text,
+    Image,
+    TextImage,
+    Code,
+}
+
+/// lines of the source file a slide was parsed from, 1-based and inclusive
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Span {
+    pub start_line: usize,
+    pub end_line: usize,
+}
+
+/// a slide as written in the source, before any font or texture is loaded
+#[derive(Clone, Debug, PartialEq)]
+pub struct SlideSpec {
+    pub num: u32,
+    pub slide_type: SlideType,
+    pub lines: Vec<String>,
+    pub image: Option<String>,
+    pub notes: Option<String>,
+    pub directives: Directives,
+    /// language named after the opening ``` of a code block, None when it is unknown or missing
+    pub language: Option<Language>,
+    /// `{3-5,8}` or `{1-2|3-5|all}` after the language of a code block
+    pub focus: Focus,
+    /// step each text line shows up at, 0 for the ones that are there from the start
+    pub reveal: Vec<usize>,
+    pub span: Span,
+}
+
+impl SlideSpec {
+    /// text lines joined the way they are rendered, None if the slide type has no text
+    pub fn text(&self) -> Option<String> {
+        match self.slide_type {
+            SlideType::Empty | SlideType::Image => None,
+            _ => Some(self.lines.join("\n")),
+        }
+    }
+
+    /// how many times next stays on the slide, plus one
+    pub fn steps(&self) -> usize {
+        step_count(&self.focus, &self.reveal)
+    }
+}
+
+/// steps of a slide: its code focus groups or its `+` lines, whichever are more
+pub fn step_count(focus: &Focus, reveal: &[usize]) -> usize {
+    let builds = reveal.iter().max().map_or(1, |last| last + 1);
+    focus.step_count().max(builds)
+}
+
+/// lines of a code block shown in full, one entry per step, the others are dimmed.
+/// Empty when the block has no `{}` annotation
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Focus {
+    /// 1-based inclusive line ranges, None focuses every line
+    pub steps: Vec<Option<Vec<(usize, usize)>>>,
+}
+
+impl Focus {
+    pub fn step_count(&self) -> usize {
+        self.steps.len().max(1)
+    }
+
+    /// whether the 1-based line is shown in full at the step
+    pub fn is_focused(&self, step: usize, line: usize) -> bool {
+        match self.steps.get(step) {
+            None | Some(None) => true,
+            Some(Some(ranges)) => ranges.iter().any(|(from, to)| (*from..=*to).contains(&line)),
+        }
+    }
+}
+
+/// horizontal placement of slide text lines
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Align {
+    /// the block is centered, its lines are left aligned inside it
+    #[default]
+    Block,
+    Left,
+    /// every line is centered on its own
+    Center,
+    Right,
+}
+
+/// vertical placement of the text block inside its area
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum VAlign {
+    Top,
+    #[default]
+    Middle,
+    Bottom,
+}
+
+/// how a slide replaces the one before it
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Transition {
+    #[default]
+    None,
+    Fade,
+    /// the new slide comes in from the right and pushes the old one out to the left
+    SlideLeft,
+    SlideRight,
+    /// the old slide fades out to the background color, then the new one fades in
+    FadeThroughBackground,
+}
+
+/// how a background image covers the slide
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BackgroundMode {
+    Fill,
+    Fit
+}
+
+/// background a slide shows instead of the one from the settings
+#[derive(Clone, Debug, PartialEq)]
+pub enum BackgroundOverride {
+    Hidden,
+    /// mode falls back to the one from the settings, line is where the directive is written
+    Image { path: String, mode: Option<BackgroundMode>, line: usize },
+}
+
+/// overrides written as `% key: value` lines at the top of a slide, None keeps the deck default
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Directives {
+    /// hex without the leading #
+    pub bg_color: Option<String>,
+    pub font_color: Option<String>,
+    pub background: Option<BackgroundOverride>,
+    pub numbering: Option<bool>,
+    pub align: Option<Align>,
+    pub valign: Option<VAlign>,
+    pub wrap: Option<bool>,
+    /// transition into the slide
+    pub transition: Option<Transition>,
+    /// seconds, falls back to the duration from the settings
+    pub transition_duration: Option<f32>,
+    /// seconds the slide may take, the rest of the talk duration is shared by slides without one
+    pub time: Option<u64>,
+}
+
+pub const DIRECTIVE_KEYS: &[&str] =
+    &["bg-color", "font-color", "background", "numbering", "align", "valign", "wrap", "transition", "time"];
+
+/// parsed presentation, knows nothing about macroquad
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Deck {
+    pub front_matter: Vec<FrontMatterEntry>,
+    pub slides: Vec<SlideSpec>,
+    pub source_lines: Vec<String>,
+}
+
+/// `key: value` line of the `---` block at the top of a presentation
+#[derive(Clone, Debug, PartialEq)]
+pub struct FrontMatterEntry {
+    pub key: String,
+    pub value: String,
+    pub line: usize,
+}
+
+/// settings the front matter may hold, named like the command line options
+pub const FRONT_MATTER_KEYS: &[&str] = &[
+    "theme",
+    "font",
+    "mono-font",
+    "bold-font",
+    "italic-font",
+    "resolution",
+    "numbering",
+    "numbering-anchor",
+    "background",
+    "filtering",
+    "preview",
+    "align",
+    "valign",
+    "wrap",
+    "transition",
+    "transition-duration",
+    "duration",
+];
+
+impl Deck {
+    /// source line by its 1-based number, empty if out of range
+    pub fn source_line(&self, line: usize) -> &str {
+        line.checked_sub(1)
+            .and_then(|i| self.source_lines.get(i))
+            .map(String::as_str)
+            .unwrap_or("")
+    }
+}
+
+/// a paragraph of the source: consecutive non blank lines with their line numbers
+struct Paragraph<'a> {
+    lines: Vec<(usize, &'a str)>,
+}
+
+impl Paragraph<'_> {
+    fn span(&self) -> Span {
+        Span {
+            start_line: self.lines.first().map(|(n, _)| *n).unwrap_or(0),
+            end_line: self.lines.last().map(|(n, _)| *n).unwrap_or(0),
+        }
+    }
+}
+
+/// reads the `---` block at the very top of the source.
+/// Returns its entries and how many lines it takes, 0 when there is none
+pub fn parse_front_matter(source: &str) -> (Vec<FrontMatterEntry>, usize, Vec<Diagnostic>) {
+    let mut entries = Vec::new();
+    let mut diagnostics = Vec::new();
+    let mut lines = source.lines();
+
+    if lines.next().map(str::trim_end) != Some("---") {
+        return (entries, 0, diagnostics);
+    }
+
+    for (i, line) in lines.enumerate() {
+        let line_no = i + 2;
+        let trimmed = line.trim();
+
+        if trimmed == "---" {
+            return (entries, line_no, diagnostics);
+        }
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        let Some((key, value)) = trimmed.split_once(':') else {
+            diagnostics.push(
+                Diagnostic::error("expected `key: value`", line_no, indent_column(line), trimmed.chars().count(), line)
+                    .with_help("front matter ends with a line of `---`"),
+            );
+            continue;
+        };
+        let key = key.trim();
+
+        if !FRONT_MATTER_KEYS.contains(&key) {
+            diagnostics.push(
+                Diagnostic::error(
+                    format!("unknown front matter key `{}`", key),
+                    line_no,
+                    indent_column(line),
+                    key.chars().count(),
+                    line,
+                )
+                .with_help(format!("known keys are: {}", FRONT_MATTER_KEYS.join(", "))),
+            );
+            continue;
+        }
+
+        entries.push(FrontMatterEntry {
+            key: key.to_string(),
+            value: value.trim().to_string(),
+            line: line_no,
+        });
+    }
+
+    // without the closing line everything below was read as front matter, those errors are noise
+    let unclosed = Diagnostic::error("unclosed front matter", 1, 1, 3, "---")
+        .with_help("end the front matter with a line of `---`");
+    (Vec::new(), 0, vec![unclosed])
+}
+
+/// splits source into paragraphs, empty lines inside code blocks do not split.
+/// The first `skip` lines are left out. Also returns the opening fence of a code block that is never closed
+fn split_paragraphs(source: &str, skip: usize) -> (Vec<Paragraph<'_>>, Option<(usize, &str)>) {
+    let mut paragraphs = Vec::new();
+    let mut current: Vec<(usize, &str)> = Vec::new();
+    let mut in_code = false;
+    let mut open_fence = None;
+
+    for (i, line) in source.lines().enumerate().skip(skip) {
+        let trimmed = line.trim_start();
+
+        if trimmed.starts_with("```") {
+            in_code = !in_code;
+            open_fence = if in_code { Some((i + 1, line)) } else { None };
+            current.push((i + 1, line));
+            continue;
+        }
+
+        if trimmed.is_empty() {
+            if in_code {
+                current.push((i + 1, ""));
+            } else if !current.is_empty() {
+                paragraphs.push(Paragraph { lines: std::mem::take(&mut current) });
+            }
+            continue;
+        }
+
+        current.push((i + 1, line));
+    }
+
+    if !current.is_empty() {
+        paragraphs.push(Paragraph { lines: current });
+    }
+
+    (paragraphs, open_fence)
+}
+
+/// 1-based column of the first non whitespace character
+fn indent_column(line: &str) -> usize {
+    line.chars().take_while(|c| c.is_whitespace()).count() + 1
+}
+
+/// 1-based column of `part` inside `line`, 1 when it is not there
+fn column_of(line: &str, part: &str) -> usize {
+    line.find(part).map(|i| line[..i].chars().count() + 1).unwrap_or(1)
+}
+
+pub fn parse_align(value: &str) -> Result<Align, String> {
+    match value {
+        "block" => Ok(Align::Block),
+        "left" => Ok(Align::Left),
+        "center" => Ok(Align::Center),
+        "right" => Ok(Align::Right),
+        _ => Err(format!("unknown alignment `{}`, expected block, left, center or right", value)),
+    }
+}
+
+pub fn parse_valign(value: &str) -> Result<VAlign, String> {
+    match value {
+        "top" => Ok(VAlign::Top),
+        "middle" => Ok(VAlign::Middle),
+        "bottom" => Ok(VAlign::Bottom),
+        _ => Err(format!("unknown vertical alignment `{}`, expected top, middle or bottom", value)),
+    }
+}
+
+pub fn align_name(align: Align) -> &'static str {
+    match align {
+        Align::Block => "block",
+        Align::Left => "left",
+        Align::Center => "center",
+        Align::Right => "right",
+    }
+}
+
+pub fn valign_name(valign: VAlign) -> &'static str {
+    match valign {
+        VAlign::Top => "top",
+        VAlign::Middle => "middle",
+        VAlign::Bottom => "bottom",
+    }
+}
+
+pub fn parse_transition(value: &str) -> Result<Transition, String> {
+    match value {
+        "none" => Ok(Transition::None),
+        "fade" => Ok(Transition::Fade),
+        "slide-left" => Ok(Transition::SlideLeft),
+        "slide-right" => Ok(Transition::SlideRight),
+        "fade-through-bg" => Ok(Transition::FadeThroughBackground),
+        _ => Err(format!(
+            "unknown transition `{}`, expected none, fade, slide-left, slide-right or fade-through-bg",
+            value
+        )),
+    }
+}
+
+pub fn transition_name(transition: Transition) -> &'static str {
+    match transition {
+        Transition::None => "none",
+        Transition::Fade => "fade",
+        Transition::SlideLeft => "slide-left",
+        Transition::SlideRight => "slide-right",
+        Transition::FadeThroughBackground => "fade-through-bg",
+    }
+}
+
+/// seconds like 0.4 or 0.4s
+pub fn parse_transition_duration(value: &str) -> Result<f32, String> {
+    match value.trim_end_matches('s').parse::<f32>() {
+        Ok(seconds) if (0f32..=10f32).contains(&seconds) => Ok(seconds),
+        _ => Err(format!("expected a duration in seconds between 0 and 10, got `{}`", value)),
+    }
+}
+
+/// length of time like `90s`, `2m`, `1h15m`, `1m30s`, `2:30` or `1:02:30`, in seconds.
+/// A bare number is counted in `bare` seconds, minutes for the talk duration and seconds for slides
+pub fn parse_time_span(value: &str, bare: u64) -> Result<u64, String> {
+    let error = || format!("expected a time like 90s, 2m, 1h15m or 2:30, got `{}`", value);
+
+    if value.contains(':') {
+        // m:ss or h:mm:ss
+        let parts: Vec<&str> = value.split(':').collect();
+        if parts.len() > 3 || parts[1..].iter().any(|part| part.len() != 2) {
+            return Err(error());
+        }
+        return parts.iter().try_fold(0u64, |total, part| match part.parse::<u64>() {
+            Ok(n) if total == 0 || n < 60 => total.checked_mul(60).and_then(|total| total.checked_add(n)).ok_or_else(error),
+            _ => Err(error()),
+        });
+    }
+
+    if let Ok(n) = value.parse::<u64>() {
+        return n.checked_mul(bare).ok_or_else(error);
+    }
+
+    let mut total = 0u64;
+    let mut number = String::new();
+    let mut last_unit = u64::MAX;
+    for c in value.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let unit = match c {
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => return Err(error()),
+        };
+        // units go from large to small, each one once
+        if number.is_empty() || unit >= last_unit {
+            return Err(error());
+        }
+        let seconds = number.parse::<u64>().ok().and_then(|n| n.checked_mul(unit));
+        total = seconds.and_then(|seconds| total.checked_add(seconds)).ok_or_else(error)?;
+        number.clear();
+        last_unit = unit;
+    }
+
+    if !number.is_empty() || last_unit == u64::MAX { Err(error()) } else { Ok(total) }
+}
+
+fn parse_switch(value: &str) -> Result<bool, String> {
+    match value {
+        "on" | "true" => Ok(true),
+        "off" | "false" => Ok(false),
+        _ => Err(format!("expected on or off, got `{}`", value)),
+    }
+}
+
+/// `fade` or `fade 0.6`
+fn parse_directive_transition(value: &str) -> Result<(Transition, Option<f32>), String> {
+    let mut parts = value.split_whitespace();
+    let transition = parse_transition(parts.next().unwrap_or(""))?;
+    let duration = parts.next().map(parse_transition_duration).transpose()?;
+    match parts.next() {
+        Some(extra) => Err(format!("unexpected `{}` after the duration", extra)),
+        None => Ok((transition, duration)),
+    }
+}
+
+fn parse_directive_color(value: &str) -> Result<String, String> {
+    let hex = value.trim_start_matches('#');
+    if hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        Ok(hex.to_ascii_lowercase())
+    } else {
+        Err(format!("invalid color `{}`, expected hex like ff8800", value))
+    }
+}
+
+/// `none` or an image path with an optional trailing fit or fill, the path may contain spaces
+fn parse_directive_background(value: &str, line_no: usize) -> Result<BackgroundOverride, String> {
+    if value == "none" {
+        return Ok(BackgroundOverride::Hidden);
+    }
+
+    let (path, mode) = match value.rsplit_once(' ') {
+        Some((path, "fit")) => (path.trim_end(), Some(BackgroundMode::Fit)),
+        Some((path, "fill")) => (path.trim_end(), Some(BackgroundMode::Fill)),
+        _ => (value, None),
+    };
+    if path.is_empty() {
+        return Err("expected an image path or `none`".to_string());
+    }
+
+    Ok(BackgroundOverride::Image { path: path.to_string(), mode, line: line_no })
+}
+
+/// applies one `% key: value` line to the directives of a slide
+fn parse_directive(line_no: usize, line: &str, slide_num: u32, directives: &mut Directives, diagnostics: &mut Vec<Diagnostic>) {
+    let body = line.trim_start()[1..].trim();
+    let Some((key, value)) = body.split_once(':') else {
+        diagnostics.push(
+            Diagnostic::error("expected `% key: value`", line_no, indent_column(line), line.trim().chars().count(), line)
+                .in_slide(slide_num)
+                .with_help(format!(
+                    "known directives are: {}. Write `\\%` for a slide that starts with a literal %",
+                    DIRECTIVE_KEYS.join(", ")
+                )),
+        );
+        return;
+    };
+    let (key, value) = (key.trim(), value.trim());
+
+    let result = match key {
+        "bg-color" => parse_directive_color(value).map(|color| directives.bg_color = Some(color)),
+        "font-color" => parse_directive_color(value).map(|color| directives.font_color = Some(color)),
+        "background" => parse_directive_background(value, line_no).map(|bg| directives.background = Some(bg)),
+        "numbering" => parse_switch(value).map(|numbering| directives.numbering = Some(numbering)),
+        "align" => parse_align(value).map(|align| directives.align = Some(align)),
+        "valign" => parse_valign(value).map(|valign| directives.valign = Some(valign)),
+        "wrap" => parse_switch(value).map(|wrap| directives.wrap = Some(wrap)),
+        "time" => parse_time_span(value, 1).map(|time| directives.time = Some(time)),
+        "transition" => parse_directive_transition(value).map(|(transition, duration)| {
+            directives.transition = Some(transition);
+            directives.transition_duration = duration;
+        }),
+        _ => {
+            diagnostics.push(
+                Diagnostic::error(
+                    format!("unknown slide directive `{}`", key),
+                    line_no,
+                    column_of(line, key),
+                    key.chars().count(),
+                    line,
+                )
+                .in_slide(slide_num)
+                .with_help(format!("known directives are: {}", DIRECTIVE_KEYS.join(", "))),
+            );
+            return;
+        }
+    };
+
+    if let Err(message) = result {
+        let (column, len) = if value.is_empty() {
+            (line.trim_end().chars().count(), 1)
+        } else {
+            (column_of(line, value), value.chars().count())
+        };
+        diagnostics.push(Diagnostic::error(message, line_no, column, len, line).in_slide(slide_num));
+    }
+}
+
+/// reads the `%` lines at the top of a paragraph, returns them and how many lines they take
+fn parse_directives(lines: &[(usize, &str)], slide_num: u32, diagnostics: &mut Vec<Diagnostic>) -> (Directives, usize) {
+    let mut directives = Directives::default();
+    let mut count = 0;
+
+    for (line_no, line) in lines.iter().take_while(|(_, line)| line.trim_start().starts_with('%')) {
+        parse_directive(*line_no, line, slide_num, &mut directives, diagnostics);
+        count += 1;
+    }
+
+    (directives, count)
+}
+
+/// directives are dropped when nothing below them becomes a slide
+fn warn_orphan_directives(first_line: (usize, &str), diagnostics: &mut Vec<Diagnostic>) {
+    let (line_no, line) = first_line;
+    diagnostics.push(
+        Diagnostic::warning("slide directives without a slide are ignored", line_no, indent_column(line), 1, line)
+            .with_help("put slide content right below them, or a `\\` line for an empty slide"),
+    );
+}
+
+/// reports inline markup that can not be drawn, e.g. unknown colors
+fn check_markup(line_no: usize, line: &str, slide_num: u32, diagnostics: &mut Vec<Diagnostic>) {
+    for error in parse_line(line).1 {
+        let names: Vec<&str> = COLOR_NAMES.iter().map(|(name, _)| *name).collect();
+        diagnostics.push(
+            Diagnostic::warning(error.message, line_no, error.column, error.len, line)
+                .in_slide(slide_num)
+                .with_help(format!(
+                    "the text is shown as written. Colors are {} or hex like ff8800, write \\{{ for a literal brace",
+                    names.join(", ")
+                )),
+        );
+    }
+}
+
+/// reads the opening ``` line of a code block: its language and the `{}` focus annotation
+fn parse_fence(line_no: usize, line: &str, slide_num: u32, diagnostics: &mut Vec<Diagnostic>) -> (Option<Language>, Focus) {
+    let info = line.trim_start().trim_start_matches('`');
+    let (name, annotation) = match info.split_once('{') {
+        Some((name, rest)) => (name, Some(rest)),
+        None => (info, None),
+    };
+    let language = name.split_whitespace().next().and_then(parse_language);
+
+    let Some(annotation) = annotation else {
+        return (language, Focus::default());
+    };
+    let column = column_of(line, "{");
+    let error = |message: String, diagnostics: &mut Vec<Diagnostic>| {
+        diagnostics.push(
+            Diagnostic::error(message, line_no, column, annotation.chars().count() + 1, line)
+                .in_slide(slide_num)
+                .with_help("focus lines like {3-5,8}, or step through them like {1-2|3-5|all}"),
+        );
+    };
+
+    let Some((body, _)) = annotation.split_once('}') else {
+        error("unclosed `{` in the code block annotation".to_string(), diagnostics);
+        return (language, Focus::default());
+    };
+
+    let mut focus = Focus::default();
+    for step in body.split('|').map(str::trim) {
+        if step == "all" {
+            focus.steps.push(None);
+            continue;
+        }
+
+        let mut ranges = Vec::new();
+        for item in step.split(',').map(str::trim) {
+            let range = match item.split_once('-') {
+                Some((from, to)) => from.trim().parse().ok().zip(to.trim().parse().ok()),
+                None => item.parse().ok().map(|n| (n, n)),
+            };
+            match range {
+                Some((from, to)) if from >= 1 && from <= to => ranges.push((from, to)),
+                _ => {
+                    error(format!("invalid line range `{}`", item), diagnostics);
+                    return (language, Focus::default());
+                }
+            }
+        }
+        focus.steps.push(Some(ranges));
+    }
+
+    (language, focus)
+}
+
+/// warns about focus ranges past the end of the code block they belong to, the lines that are there still work
+fn check_focus(line_no: usize, line: &str, slide_num: u32, focus: &Focus, line_count: usize, diagnostics: &mut Vec<Diagnostic>) {
+    let last = focus.steps.iter().flatten().flatten().map(|(_, to)| *to).max().unwrap_or(0);
+    if last > line_count {
+        let annotation = &line[line.find('{').unwrap_or(0)..];
+        diagnostics.push(
+            Diagnostic::warning(
+                format!("line {} is past the end of the code block, its last line is {}", last, line_count),
+                line_no,
+                column_of(line, annotation),
+                annotation.trim_end().chars().count(),
+                line,
+            )
+            .in_slide(slide_num),
+        );
+    }
+}
+
+/// the line without its `\` when it starts with `\+` or `\%`, which keep the marker as text
+fn unescape_marker(line: &str) -> Option<&str> {
+    line.strip_prefix('\\').filter(|rest| rest.starts_with(['+', '%']))
+}
+
+/// adds a text line of a slide. `+ ` in front of it makes it show up one step after
+/// the last marked line, the indentation before the marker stays and `\+` keeps a literal +
+fn push_text_line(line: &str, text_lines: &mut Vec<String>, reveal: &mut Vec<usize>) {
+    let trimmed = line.trim_start();
+    let indent = &line[..line.len() - trimmed.len()];
+
+    match trimmed.strip_prefix("+ ").or((trimmed == "+").then_some("")) {
+        Some(rest) => {
+            reveal.push(reveal.iter().max().map_or(1, |last| last + 1));
+            text_lines.push(format!("{}{}", indent, rest));
+        }
+        None => {
+            reveal.push(0);
+            text_lines.push(match unescape_marker(trimmed) {
+                Some(rest) => format!("{}{}", indent, rest),
+                None => line.to_string(),
+            });
+        }
+    }
+}
+
+/// warns about `~` lines that carry text, the text is dropped when rendering
+fn check_empty_line_marker(line_no: usize, line: &str, slide_num: u32, diagnostics: &mut Vec<Diagnostic>) {
+    let trimmed = line.trim();
+    if trimmed.len() > 1 {
+        diagnostics.push(
+            Diagnostic::warning("text after `~` is ignored", line_no, indent_column(line), trimmed.chars().count(), line)
+                .in_slide(slide_num)
+                .with_help("`~` alone on a line makes an empty line"),
+        );
+    }
+}
+
+fn join_notes(lines: Vec<&str>) -> Option<String> {
+    if lines.is_empty() {
+        None
+    } else {
+        Some(lines.join("\n"))
+    }
+}
+
+/// notes without the `|` markers they are written with
+pub fn clean_notes(comments: &str) -> Vec<String> {
+    comments
+        .lines()
+        .map(|line| {
+            let line = line.trim_start();
+            let line = line.strip_prefix('|').unwrap_or(line);
+            line.strip_prefix(' ').unwrap_or(line).to_string()
+        })
+        .collect()
+}
+
+/// parses presentation source into a Deck, one slide per paragraph.
+/// Every problem found is collected instead of stopping at the first one
+pub fn parse_deck(source: &str) -> (Deck, Vec<Diagnostic>) {
+    let (front_matter, front_matter_lines, mut diagnostics) = parse_front_matter(source);
+    let mut deck = Deck {
+        front_matter,
+        slides: Vec::new(),
+        source_lines: source.lines().map(str::to_string).collect(),
+    };
+    let mut slide_num = 1;
+
+    let (paragraphs, open_fence) = split_paragraphs(source, front_matter_lines);
+
+    if let Some((line_no, line)) = open_fence {
+        diagnostics.push(
+            Diagnostic::error("unclosed code block", line_no, indent_column(line), 3, line)
+                .with_help("close the code block with a line starting with ```"),
+        );
+    }
+
+    for paragraph in paragraphs {
+        let (directives, directive_lines) = parse_directives(&paragraph.lines, slide_num, &mut diagnostics);
+        let first_line = paragraph.lines[0];
+        let paragraph = Paragraph { lines: paragraph.lines[directive_lines..].to_vec() };
+
+        // the span covers slide content only, diagnostics point at it by start_line
+        let span = paragraph.span();
+        let lines: Vec<&str> = paragraph.lines.iter().map(|(_, l)| *l).collect();
+
+        if lines.iter().all(|line| line.trim().is_empty()) {
+            if directive_lines > 0 {
+                warn_orphan_directives(first_line, &mut diagnostics);
+            }
+            continue;
+        }
+
+        // Empty slide
+        if lines[0].starts_with('\\') && unescape_marker(lines[0]).is_none() {
+            let directive = lines[0].trim_end();
+            if directive.len() > 1 {
+                diagnostics.push(
+                    Diagnostic::error(
+                        format!("unknown directive `{}`", directive),
+                        span.start_line,
+                        1,
+                        directive.chars().count(),
+                        lines[0],
+                    )
+                    .in_slide(slide_num)
+                    .with_help("a lone `\\` creates an empty slide"),
+                );
+            }
+
+            let comments = lines
+                .iter()
+                .skip(1)
+                .filter(|l| l.trim_start().starts_with('|'))
+                .copied()
+                .collect::<Vec<&str>>()
+                .join("\n");
+
+            deck.slides.push(SlideSpec {
+                num: slide_num,
+                slide_type: SlideType::Empty,
+                lines: Vec::new(),
+                image: None,
+                notes: Some(comments),
+                directives,
+                language: None,
+                focus: Focus::default(),
+                reveal: Vec::new(),
+                span,
+            });
+            slide_num += 1;
+            continue;
+        }
+
+        // Image slide
+        if lines[0].starts_with('@') {
+            let img_path = lines[0][1..].trim();
+
+            if img_path.is_empty() {
+                diagnostics.push(
+                    Diagnostic::error("empty image path", span.start_line, 1, 1, lines[0])
+                        .in_slide(slide_num)
+                        .with_help("write the path right after `@`, e.g. `@./img/photo.png`"),
+                );
+            }
+
+            let mut text_lines = Vec::new();
+            let mut reveal = Vec::new();
+            let mut comment_lines = Vec::new();
+
+            for (line_no, line) in paragraph.lines.iter().skip(1) {
+                let l = line.trim_start();
+                if l.starts_with('|') {
+                    comment_lines.push(*line);
+                } else if l.starts_with('~') {
+                    check_empty_line_marker(*line_no, line, slide_num, &mut diagnostics);
+                    push_text_line("", &mut text_lines, &mut reveal);
+                } else if !l.is_empty() {
+                    check_markup(*line_no, line, slide_num, &mut diagnostics);
+                    push_text_line(line, &mut text_lines, &mut reveal);
+                }
+            }
+
+            let slide_type = if text_lines.is_empty() {
+                SlideType::Image
+            } else {
+                SlideType::TextImage
+            };
+
+            deck.slides.push(SlideSpec {
+                num: slide_num,
+                slide_type,
+                lines: text_lines,
+                image: Some(img_path.to_string()),
+                notes: join_notes(comment_lines),
+                directives,
+                language: None,
+                focus: Focus::default(),
+                reveal,
+                span,
+            });
+            slide_num += 1;
+            continue;
+        }
+
+        let mut comment_lines = Vec::new();
+
+        // Code
+        if lines[0].starts_with("```") {
+            let (fence_line, fence) = paragraph.lines[0];
+            let (language, focus) = parse_fence(fence_line, fence, slide_num, &mut diagnostics);
+            let mut code_lines = Vec::new();
+            let mut code_block_ended = false;
+            let mut warn_trailing_text = true;
+            for (line_no, line) in paragraph.lines.iter().skip(1) {
+                let line = line.trim_end();
+                if line.starts_with("```") && !code_block_ended {
+                    code_block_ended = true;
+                    continue;
+                }
+                if !code_block_ended {
+                    code_lines.push(line);
+                } else if line.starts_with('|') {
+                    comment_lines.push(line);
+                } else if warn_trailing_text {
+                    diagnostics.push(
+                        Diagnostic::warning(
+                            "text after the closing ``` is ignored",
+                            *line_no,
+                            1,
+                            line.chars().count(),
+                            line,
+                        )
+                        .in_slide(slide_num)
+                        .with_help("separate it with an empty line to make it a slide of its own"),
+                    );
+                    warn_trailing_text = false;
+                }
+            }
+            check_focus(fence_line, fence, slide_num, &focus, code_lines.len(), &mut diagnostics);
+
+            deck.slides.push(SlideSpec {
+                num: slide_num,
+                slide_type: SlideType::Code,
+                lines: code_lines.into_iter().map(str::to_string).collect(),
+                image: None,
+                notes: join_notes(comment_lines),
+                directives,
+                language,
+                focus,
+                reveal: Vec::new(),
+                span,
+            });
+            slide_num += 1;
+            continue;
+        }
+
+        // Text
+        let mut text_lines = Vec::new();
+        let mut reveal = Vec::new();
+        for (line_no, line) in paragraph.lines.iter() {
+            if line.trim_start().starts_with('|') {
+                comment_lines.push(*line);
+            } else if line.trim_start().starts_with('~') {
+                check_empty_line_marker(*line_no, line, slide_num, &mut diagnostics);
+                push_text_line("", &mut text_lines, &mut reveal);
+            } else {
+                check_markup(*line_no, line, slide_num, &mut diagnostics);
+                push_text_line(line, &mut text_lines, &mut reveal);
+            }
+        }
+
+        // if no text: it's source comment that is not going to be rendered anywhere
+        if text_lines.is_empty() && !comment_lines.is_empty() {
+            if directive_lines > 0 {
+                warn_orphan_directives(first_line, &mut diagnostics);
+            }
+            continue;
+        }
+
+        deck.slides.push(SlideSpec {
+            num: slide_num,
+            slide_type: SlideType::Text,
+            lines: text_lines,
+            image: None,
+            notes: join_notes(comment_lines),
+            directives,
+            language: None,
+            focus: Focus::default(),
+            reveal,
+            span,
+        });
+        slide_num += 1;
+    }
+
+    (deck, diagnostics)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn errors(diagnostics: &[Diagnostic]) -> Vec<(usize, usize, usize, &str)> {
+        diagnostics
+            .iter()
+            .map(|d| (d.line, d.column, d.len, d.message.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn paragraphs_become_slides() {
+        let (deck, diagnostics) = parse_deck("first\nslide\n\n\n@img.png\n\n```\nlet a = 1;\n\nlet b = 2;\n```\n");
+        assert!(diagnostics.is_empty());
+
+        let types: Vec<SlideType> = deck.slides.iter().map(|s| s.slide_type).collect();
+        assert_eq!(types, [SlideType::Text, SlideType::Image, SlideType::Code]);
+        assert_eq!(deck.slides[0].lines, ["first", "slide"]);
+        assert_eq!(deck.slides[0].span, Span { start_line: 1, end_line: 2 });
+        assert_eq!(deck.slides[1].image.as_deref(), Some("img.png"));
+        assert_eq!(deck.slides[1].span, Span { start_line: 5, end_line: 5 });
+        // an empty line inside a code block does not split it
+        assert_eq!(deck.slides[2].lines, ["let a = 1;", "", "let b = 2;"]);
+        assert_eq!(deck.slides.iter().map(|s| s.num).collect::<Vec<_>>(), [1, 2, 3]);
+    }
+
+    #[test]
+    fn backslash_makes_an_empty_slide() {
+        let (deck, diagnostics) = parse_deck("\\\n| nothing to see\n\ntext\n");
+        assert!(diagnostics.is_empty());
+        assert_eq!(deck.slides[0].slide_type, SlideType::Empty);
+        assert!(deck.slides[0].lines.is_empty());
+        assert_eq!(deck.slides[0].notes.as_deref(), Some("| nothing to see"));
+        assert_eq!(deck.slides[1].num, 2);
+    }
+
+    #[test]
+    fn plus_lines_reveal_one_by_one() {
+        let (deck, _) = parse_deck("always\n+ first\n  + second\n\\+ literal\n");
+        let slide = &deck.slides[0];
+        assert_eq!(slide.lines, ["always", "first", "  second", "+ literal"]);
+        assert_eq!(slide.reveal, [0, 1, 2, 0]);
+        assert_eq!(slide.steps(), 3);
+    }
+
+    #[test]
+    fn backslash_percent_is_text() {
+        let (deck, diagnostics) = parse_deck("% align: left\n\\% of users\n\n\\% at the top\n");
+        assert!(diagnostics.is_empty());
+        assert_eq!(deck.slides[0].directives.align, Some(Align::Left));
+        assert_eq!(deck.slides[0].lines, ["% of users"]);
+        assert_eq!(deck.slides[1].slide_type, SlideType::Text);
+        assert_eq!(deck.slides[1].lines, ["% at the top"]);
+
+        let (_, diagnostics) = parse_deck("% of users\nwere happy\n");
+        assert_eq!(errors(&diagnostics), [(1, 1, 10, "expected `% key: value`")]);
+    }
+
+    #[test]
+    fn bar_lines_are_notes() {
+        let (deck, _) = parse_deck("text\n| say this\n  | and this\n\n| only a comment\n");
+        assert_eq!(deck.slides.len(), 1);
+        assert_eq!(deck.slides[0].lines, ["text"]);
+        assert_eq!(deck.slides[0].notes.as_deref(), Some("| say this\n  | and this"));
+        assert_eq!(clean_notes(deck.slides[0].notes.as_deref().unwrap()), ["say this", "and this"]);
+    }
+
+    #[test]
+    fn front_matter_is_read_and_skipped() {
+        let (deck, diagnostics) = parse_deck("---\ntheme: light\n# a comment\nnumbering: true\n---\nhello\n");
+        assert!(diagnostics.is_empty());
+        let entries: Vec<(&str, &str, usize)> =
+            deck.front_matter.iter().map(|e| (e.key.as_str(), e.value.as_str(), e.line)).collect();
+        assert_eq!(entries, [("theme", "light", 2), ("numbering", "true", 4)]);
+        assert_eq!(deck.slides.len(), 1);
+        assert_eq!(deck.slides[0].span.start_line, 6);
+    }
+
+    #[test]
+    fn front_matter_mistakes() {
+        let (entries, lines, diagnostics) = parse_front_matter("---\ntheme: dark\ncolour: red\njust words\n---\n");
+        assert_eq!(lines, 5);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(
+            errors(&diagnostics),
+            [(3, 1, 6, "unknown front matter key `colour`"), (4, 1, 10, "expected `key: value`")]
+        );
+
+        // a deck without front matter starts at the first line
+        assert_eq!(parse_front_matter("hello\n---\n").1, 0);
+
+        // without the closing line only that is reported, and the whole file is left for slides
+        let (deck, diagnostics) = parse_deck("---\ntheme: dark\n\nhello\n");
+        assert_eq!(errors(&diagnostics), [(1, 1, 3, "unclosed front matter")]);
+        assert!(deck.front_matter.is_empty());
+    }
+
+    #[test]
+    fn focus_ranges() {
+        let (deck, diagnostics) = parse_deck("```rust {3-5,8}\n1\n2\n3\n4\n5\n6\n7\n8\n```\n");
+        assert!(diagnostics.is_empty());
+        let slide = &deck.slides[0];
+        assert_eq!(slide.language, Some(Language::Rust));
+        assert_eq!(slide.focus.steps, [Some(vec![(3, 5), (8, 8)])]);
+        let focused: Vec<usize> = (1..=8).filter(|line| slide.focus.is_focused(0, *line)).collect();
+        assert_eq!(focused, [3, 4, 5, 8]);
+        assert_eq!(slide.steps(), 1);
+    }
+
+    #[test]
+    fn focus_steps() {
+        let (deck, diagnostics) = parse_deck("``` {1-2|3-5|all}\n1\n2\n3\n4\n5\n```\n");
+        assert!(diagnostics.is_empty());
+        let slide = &deck.slides[0];
+        assert_eq!(slide.focus.steps, [Some(vec![(1, 2)]), Some(vec![(3, 5)]), None]);
+        assert_eq!(slide.steps(), 3);
+        assert!(slide.focus.is_focused(0, 2) && !slide.focus.is_focused(0, 3));
+        assert!(slide.focus.is_focused(1, 3) && !slide.focus.is_focused(1, 1));
+        assert!((1..=5).all(|line| slide.focus.is_focused(2, line)));
+        // without an annotation every line is focused in the only step
+        assert!(Focus::default().is_focused(0, 4));
+        assert_eq!(Focus::default().step_count(), 1);
+    }
+
+    #[test]
+    fn bad_focus_ranges() {
+        for (annotation, item) in [("{5-3}", "5-3"), ("{0}", "0"), ("{2|x}", "x")] {
+            let (deck, diagnostics) = parse_deck(&format!("```rust {}\n1\n2\n3\n4\n5\n```\n", annotation));
+            let message = format!("invalid line range `{}`", item);
+            assert_eq!(errors(&diagnostics), [(1, 9, annotation.len(), message.as_str())]);
+            assert!(diagnostics[0].help.is_some());
+            // the block is still shown, without focus
+            assert_eq!(deck.slides[0].focus, Focus::default());
+        }
+
+        let (deck, diagnostics) = parse_deck("```rust {2|3-9}\n1\n2\n3\n```\n");
+        assert_eq!(
+            errors(&diagnostics),
+            [(1, 9, 7, "line 9 is past the end of the code block, its last line is 3")]
+        );
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert_eq!(deck.slides[0].steps(), 2);
+    }
+
+    #[test]
+    fn time_spans() {
+        assert_eq!(parse_time_span("90s", 1), Ok(90));
+        assert_eq!(parse_time_span("1h15m", 1), Ok(4500));
+        assert_eq!(parse_time_span("1:02:30", 1), Ok(3750));
+        assert_eq!(parse_time_span("20", 60), Ok(1200));
+        assert!(parse_time_span("1m2h", 1).is_err());
+        assert!(parse_time_span("1:5", 1).is_err());
+
+        // too large for the seconds to fit is an error, not an overflow
+        let error = "expected a time like 90s, 2m, 1h15m or 2:30, got `307445734561825861`";
+        assert_eq!(parse_time_span("307445734561825861", 60).unwrap_err(), error);
+        assert!(parse_time_span("307445734561825861m", 1).is_err());
+        assert!(parse_time_span("5124095576030432h", 1).is_err());
+        assert!(parse_time_span("5124095576030431h60m", 1).is_err());
+        assert!(parse_time_span("5124095576030432:00:00", 1).is_err());
+        assert_eq!(parse_time_span("18446744073709551615s", 1), Ok(u64::MAX));
+    }
+
+    #[test]
+    fn diagnostics_point_at_the_problem() {
+        let (_, diagnostics) = parse_deck("\\oops\n\n@\n\ntext\n~ dropped\n\n```rust\nopen\n");
+        assert_eq!(
+            errors(&diagnostics),
+            [
+                (8, 1, 3, "unclosed code block"),
+                (1, 1, 5, "unknown directive `\\oops`"),
+                (3, 1, 1, "empty image path"),
+                (6, 1, 9, "text after `~` is ignored"),
+            ]
+        );
+        assert_eq!(diagnostics[1].slide, Some(1));
+        assert_eq!(diagnostics[3].severity, Severity::Warning);
+    }
+
+    #[test]
+    fn color_directives() {
+        let (deck, diagnostics) = parse_deck("% bg-color: #20204A\n% font-color: ffaa00\ntext\n");
+        assert!(diagnostics.is_empty());
+        assert_eq!(deck.slides[0].directives.bg_color.as_deref(), Some("20204a"));
+        assert_eq!(deck.slides[0].directives.font_color.as_deref(), Some("ffaa00"));
+
+        let (deck, diagnostics) = parse_deck("% bg-color: 20204\n% font-color: #ggaa00\ntext\n");
+        assert_eq!(
+            errors(&diagnostics),
+            [
+                (1, 13, 5, "invalid color `20204`, expected hex like ff8800"),
+                (2, 15, 7, "invalid color `#ggaa00`, expected hex like ff8800"),
+            ]
+        );
+        assert_eq!(diagnostics[0].slide, Some(1));
+        assert_eq!(deck.slides[0].directives.bg_color, None);
+    }
+
+    #[test]
+    fn background_directives() {
+        let background = |source: &str| {
+            let (deck, diagnostics) = parse_deck(source);
+            assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+            deck.slides[0].directives.background.clone()
+        };
+        let image = |path: &str, mode| Some(BackgroundOverride::Image { path: path.to_string(), mode, line: 1 });
+
+        assert_eq!(background("% background: bg.png fit\ntext\n"), image("bg.png", Some(BackgroundMode::Fit)));
+        assert_eq!(background("% background: bg.png fill\ntext\n"), image("bg.png", Some(BackgroundMode::Fill)));
+        assert_eq!(background("% background: my bg.png\ntext\n"), image("my bg.png", None));
+        assert_eq!(background("% background: none\ntext\n"), Some(BackgroundOverride::Hidden));
+        assert_eq!(background("text\n"), None);
+
+        let (_, diagnostics) = parse_deck("% background:\ntext\n");
+        assert_eq!(errors(&diagnostics), [(1, 13, 1, "expected an image path or `none`")]);
+    }
+
+    #[test]
+    fn numbering_directive() {
+        let numbering = |value: &str| {
+            let (deck, diagnostics) = parse_deck(&format!("% numbering: {}\ntext\n", value));
+            (deck.slides[0].directives.numbering, diagnostics.len())
+        };
+        assert_eq!(numbering("on"), (Some(true), 0));
+        assert_eq!(numbering("off"), (Some(false), 0));
+        assert_eq!(numbering("true"), (Some(true), 0));
+        assert_eq!(numbering("maybe"), (None, 1));
+    }
+
+    #[test]
+    fn unknown_directives_list_the_known_ones() {
+        let (deck, diagnostics) = parse_deck("% colour: red\ntext\n");
+        assert_eq!(errors(&diagnostics), [(1, 3, 6, "unknown slide directive `colour`")]);
+        assert!(diagnostics[0].is_error());
+        let help = diagnostics[0].help.as_deref().unwrap();
+        assert!(help.starts_with("known directives are: "), "{}", help);
+        assert!(DIRECTIVE_KEYS.iter().all(|key| help.contains(key)));
+        // the slide is still there
+        assert_eq!(deck.slides[0].lines, ["text"]);
+    }
+
+    #[test]
+    fn directives_need_a_slide_below() {
+        let (deck, diagnostics) = parse_deck("text\n\n% bg-color: 202040\n% numbering: off\n");
+        assert_eq!(deck.slides.len(), 1);
+        assert_eq!(errors(&diagnostics), [(3, 1, 1, "slide directives without a slide are ignored")]);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert!(diagnostics[0].help.as_deref().unwrap().contains("`\\` line"));
+    }
+}
@@ -0,0 +1,251 @@
+use macroquad::prelude::*;
+use macroquad_canvas::Canvas2D;
+use std::path::{Path, PathBuf};
+
+use crate::defaults::*;
+use crate::diagnostic::*;
+use crate::pdf::*;
+use crate::raster::*;
+use crate::scene::*;
+use crate::settings::*;
+use crate::slide::*;
+use crate::utils::*;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExportFormat {
+    Png,
+    Pdf,
+    Html,
+}
+
+/// file name of an exported slide, zero padded so files sort in slide order
+pub fn slide_file_name(index: usize, total: usize, extension: &str) -> String {
+    let width = total.to_string().len().max(3);
+    format!("slide-{:0width$}.{}", index + 1, extension, width = width)
+}
+
+/// resolves where the export goes and creates the missing directories.
+/// png goes to a directory, pdf and html are single files named after the deck by default
+pub fn prepare_output(input_path: &str, settings: &Settings) -> Result<PathBuf, i32> {
+    let format = settings.export_format.value;
+
+    let out = match (&settings.export_out.value, format) {
+        (Some(out), _) => PathBuf::from(out),
+        (None, ExportFormat::Png) => PathBuf::from("."),
+        (None, ExportFormat::Pdf) => Path::new(input_path).with_extension("pdf"),
+        (None, ExportFormat::Html) => Path::new(input_path).with_extension("html"),
+    };
+
+    let out_dir = match format {
+        ExportFormat::Png => out.clone(),
+        ExportFormat::Pdf | ExportFormat::Html => out.parent().map(Path::to_path_buf).unwrap_or_default(),
+    };
+
+    if !out_dir.as_os_str().is_empty()
+        && let Err(e) = std::fs::create_dir_all(&out_dir)
+    {
+        eprintln!("error: failed to create output directory `{}`: {}", out_dir.display(), e);
+        return Err(1);
+    }
+
+    Ok(out)
+}
+
+/// png and pdf export render in a window when there is a display to open one on,
+/// on Linux and the BSDs that is an X display. Without one they are drawn on the CPU
+pub fn has_display() -> bool {
+    if cfg!(any(target_os = "windows", target_os = "macos", target_os = "android", target_os = "ios")) {
+        return true;
+    }
+    std::env::var_os("DISPLAY").is_some_and(|display| !display.is_empty())
+}
+
+/// renders every slide offscreen at the virtual resolution, returns the process exit code.
+/// Needs an OpenGL context, run_headless does without one
+pub async fn run(input_path: String, settings: Settings) -> i32 {
+    let out = match prepare_output(&input_path, &settings) {
+        Ok(out) => out,
+        Err(code) => return code,
+    };
+
+    let scene = Scene::from_settings(&settings);
+    set_default_filter_mode(settings.filtering.value);
+
+    let (slides, diagnostics) = parse(&input_path, &scene.virtual_screen_size, &scene.faces(), &scene.mono_font, scene.wrap);
+    report(&diagnostics, &input_path);
+    if has_errors(&diagnostics) {
+        return 1;
+    }
+
+    let images = render_slides(&scene, &slides).await;
+    let layout = PdfLayout { virtual_screen_size: scene.virtual_screen_size, align: scene.align, valign: scene.valign };
+    write_output(&out, &settings, &slides, &images, &layout)
+}
+
+/// renders every slide on the CPU, for machines without a display or a GPU.
+/// Returns the process exit code
+pub fn run_headless(input_path: &str, settings: &Settings) -> i32 {
+    let out = match prepare_output(input_path, settings) {
+        Ok(out) => out,
+        Err(code) => return code,
+    };
+
+    let scene = match RasterScene::from_settings(settings) {
+        Ok(scene) => scene,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return 1;
+        }
+    };
+
+    let (deck, mut diagnostics) = read_deck(input_path);
+    let slides = match deck.map(|deck| scene.layout(&deck)) {
+        Some(Ok(slides)) => slides,
+        Some(Err(mut errors)) => {
+            diagnostics.append(&mut errors);
+            Vec::new()
+        }
+        None => Vec::new(),
+    };
+    report(&diagnostics, input_path);
+    if has_errors(&diagnostics) {
+        return 1;
+    }
+
+    // the last step shows everything a slide builds up to
+    let images: Vec<Image> = slides
+        .iter()
+        .enumerate()
+        .map(|(index, slide)| scene.draw(slide, index, slide.slide.steps() - 1))
+        .collect();
+    let slides: Vec<Slide> = slides.into_iter().map(|slide| slide.slide).collect();
+    let layout = PdfLayout { virtual_screen_size: scene.virtual_screen_size, align: scene.align, valign: scene.valign };
+    write_output(&out, settings, &slides, &images, &layout)
+}
+
+/// writes rendered slides as png files or a pdf, returns the process exit code
+fn write_output(out: &Path, settings: &Settings, slides: &[Slide], images: &[Image], layout: &PdfLayout) -> i32 {
+    match settings.export_format.value {
+        ExportFormat::Png => {
+            for (i, image) in images.iter().enumerate() {
+                let path = out.join(slide_file_name(i, images.len(), "png"));
+                if let Err(e) = save_png(image, &path) {
+                    eprintln!("error: failed to write `{}`: {}", path.display(), e);
+                    return 1;
+                }
+                println!("{}", path.display());
+            }
+        }
+        ExportFormat::Pdf => {
+            let font_bytes = read_font_bytes(settings.font_path.value.as_deref(), DEFAULT_FONT);
+            let mono_font_bytes = read_font_bytes(settings.mono_font_path.value.as_deref(), MONO_FONT);
+
+            if let Err(e) = write_pdf(
+                out,
+                slides,
+                images,
+                layout,
+                &font_bytes,
+                &mono_font_bytes,
+                settings.export_notes.value,
+            ) {
+                eprintln!("error: failed to write `{}`: {}", out.display(), e);
+                return 1;
+            }
+            println!("{}", out.display());
+        }
+        // written by html::run, main never renders slides for it
+        ExportFormat::Html => unreachable!(),
+    }
+
+    0
+}
+
+/// draws each slide into an offscreen canvas and reads the pixels back
+pub async fn render_slides(scene: &Scene, slides: &[Slide]) -> Vec<Image> {
+    let size = scene.virtual_screen_size;
+    let canvas = Canvas2D::new(size.x, size.y);
+    let mut images = Vec::with_capacity(slides.len());
+
+    for index in 0..slides.len() {
+        set_camera(&canvas.camera);
+        // the last step shows everything a slide builds up to
+        scene.draw(slides, index, slides[index].steps() - 1);
+        set_default_camera();
+
+        // show progress in the window, the canvas is rendered when the frame ends
+        clear_background(BLACK);
+        canvas.draw();
+        next_frame().await;
+
+        images.push(canvas.get_texture().get_texture_data());
+    }
+
+    images
+}
+
+/// font file the scene was loaded from, the font was already validated when the scene was loaded
+fn read_font_bytes(path: Option<&str>, fallback: &[u8]) -> Vec<u8> {
+    match path {
+        Some(path) => std::fs::read(path).unwrap_or_else(|_| fallback.to_vec()),
+        None => fallback.to_vec(),
+    }
+}
+
+/// render target rows are already top to bottom, unlike the screen Image::export_png expects
+fn save_png(image: &Image, path: &Path) -> Result<(), String> {
+    image::save_buffer(
+        path,
+        &image.bytes,
+        image.width as u32,
+        image.height as u32,
+        image::ColorType::Rgba8,
+    )
+    .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_names_are_zero_padded() {
+        assert_eq!(slide_file_name(0, 12, "png"), "slide-001.png");
+        assert_eq!(slide_file_name(11, 12, "png"), "slide-012.png");
+        assert_eq!(slide_file_name(41, 1500, "png"), "slide-0042.png");
+    }
+
+    #[test]
+    fn output_paths() {
+        let dir = std::env::temp_dir().join(format!("reiha-export-{}", std::process::id()));
+        let input = dir.join("talk.rh");
+        let input = input.to_str().unwrap();
+        let mut settings = Settings::default();
+
+        // png goes to the current directory, pdf and html next to the deck
+        assert_eq!(prepare_output(input, &settings), Ok(PathBuf::from(".")));
+        settings.export_format.value = ExportFormat::Pdf;
+        assert_eq!(prepare_output(input, &settings), Ok(dir.join("talk.pdf")));
+        assert!(dir.is_dir());
+
+        // missing directories are created
+        let nested = dir.join("a/b/slides.pdf");
+        settings.export_out.value = Some(nested.to_str().unwrap().to_string());
+        assert_eq!(prepare_output(input, &settings), Ok(nested.clone()));
+        assert!(dir.join("a/b").is_dir());
+
+        // png wants a directory, an existing file is in the way
+        let file = dir.join("file");
+        std::fs::write(&file, "").unwrap();
+        settings.export_format.value = ExportFormat::Png;
+        settings.export_out.value = Some(file.to_str().unwrap().to_string());
+        assert_eq!(prepare_output(input, &settings), Err(1));
+
+        // and so is a file where a parent directory should be
+        settings.export_format.value = ExportFormat::Pdf;
+        settings.export_out.value = Some(file.join("slides.pdf").to_str().unwrap().to_string());
+        assert_eq!(prepare_output(input, &settings), Err(1));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,422 @@
+use crate::markup::Run;
+
+/// languages code blocks can be highlighted as, named after the opening fence
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Language {
+    Rust,
+    Python,
+    Shell,
+}
+
+/// kind of a highlighted piece of code, the theme picks its color
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Token {
+    Keyword,
+    Type,
+    String,
+    Number,
+    Comment,
+    /// rust macros, python decorators and shell variables
+    Special,
+}
+
+/// language of a fence like ```rust, None for unknown ones that stay plain text
+pub fn parse_language(name: &str) -> Option<Language> {
+    match name.to_ascii_lowercase().as_str() {
+        "rust" | "rs" => Some(Language::Rust),
+        "python" | "py" => Some(Language::Python),
+        "sh" | "bash" | "shell" | "zsh" => Some(Language::Shell),
+        _ => None,
+    }
+}
+
+struct Syntax {
+    keywords: &'static [&'static str],
+    /// builtin types and literals, capitalized names count as types too where that is the convention
+    types: &'static [&'static str],
+    capitalized_types: bool,
+    line_comment: &'static str,
+    block_comment: Option<(&'static str, &'static str)>,
+    /// longest first, `"""` has to be tried before `"`
+    quotes: &'static [&'static str],
+    /// letters that can stand in front of a quote, like b"" or f""
+    string_prefixes: &'static str,
+}
+
+const RUST: Syntax = Syntax {
+    keywords: &[
+        "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern", "false",
+        "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return",
+        "self", "static", "struct", "super", "trait", "true", "type", "unsafe", "use", "where", "while",
+    ],
+    types: &[
+        "bool", "char", "str", "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32", "i64", "i128",
+        "isize", "f32", "f64", "Self",
+    ],
+    capitalized_types: true,
+    line_comment: "//",
+    block_comment: Some(("/*", "*/")),
+    quotes: &["\""],
+    string_prefixes: "br",
+};
+
+const PYTHON: Syntax = Syntax {
+    keywords: &[
+        "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del", "elif", "else",
+        "except", "False", "finally", "for", "from", "global", "if", "import", "in", "is", "lambda", "None",
+        "nonlocal", "not", "or", "pass", "raise", "return", "True", "try", "while", "with", "yield",
+    ],
+    types: &[
+        "bool", "bytes", "dict", "float", "int", "list", "object", "set", "str", "tuple", "self", "cls",
+    ],
+    capitalized_types: true,
+    line_comment: "#",
+    block_comment: None,
+    quotes: &["\"\"\"", "'''", "\"", "'"],
+    string_prefixes: "bfruBFRU",
+};
+
+const SHELL: Syntax = Syntax {
+    keywords: &[
+        "case", "do", "done", "elif", "else", "esac", "export", "fi", "for", "function", "if", "in", "local",
+        "return", "select", "then", "until", "while",
+    ],
+    types: &[
+        "cd", "echo", "exit", "printf", "read", "set", "shift", "source", "test", "unset",
+    ],
+    capitalized_types: false,
+    line_comment: "#",
+    block_comment: None,
+    quotes: &["\"", "'"],
+    string_prefixes: "",
+};
+
+fn syntax(language: Language) -> &'static Syntax {
+    match language {
+        Language::Rust => &RUST,
+        Language::Python => &PYTHON,
+        Language::Shell => &SHELL,
+    }
+}
+
+/// what a line ends inside of and the next one continues
+#[derive(Clone, Copy, PartialEq)]
+enum Open {
+    Nothing,
+    BlockComment,
+    /// index into the quotes of the syntax
+    String(usize),
+}
+
+fn starts_with(chars: &[char], i: usize, pattern: &str) -> bool {
+    (i..).zip(pattern.chars()).all(|(j, p)| chars.get(j) == Some(&p))
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// index after the end of what is open at i, and whether it was closed on this line
+fn close(chars: &[char], i: usize, end: &str, escapes: bool) -> (usize, bool) {
+    let mut j = i;
+    while j < chars.len() {
+        if escapes && chars[j] == '\\' {
+            j += 2;
+            continue;
+        }
+        if starts_with(chars, j, end) {
+            return (j + end.chars().count(), true);
+        }
+        j += 1;
+    }
+    (chars.len(), false)
+}
+
+/// rust char literals like 'a' or '\n', a lone ' is a lifetime
+fn char_literal(chars: &[char], i: usize) -> Option<usize> {
+    match (chars.get(i + 1), chars.get(i + 2)) {
+        (Some('\\'), _) => (i + 3..chars.len().min(i + 12)).find(|j| chars[*j] == '\'').map(|j| j + 1),
+        (Some(c), Some('\'')) if *c != '\'' => Some(i + 3),
+        _ => None,
+    }
+}
+
+/// splits the lines of a code block into runs, lines without tokens become a single plain run
+pub fn highlight(language: Language, text: &str) -> Vec<Vec<Run>> {
+    let syntax = syntax(language);
+    let mut open = Open::Nothing;
+
+    text.lines()
+        .map(|line| {
+            let (tokens, next) = highlight_line(syntax, language, line, open);
+            open = next;
+            merge(tokens)
+        })
+        .collect()
+}
+
+fn highlight_line(syntax: &Syntax, language: Language, line: &str, mut open: Open) -> (Vec<(char, Option<Token>)>, Open) {
+    let chars: Vec<char> = line.chars().collect();
+    let mut tokens: Vec<(char, Option<Token>)> = Vec::with_capacity(chars.len());
+    let push = |tokens: &mut Vec<(char, Option<Token>)>, from: usize, to: usize, token: Option<Token>| {
+        tokens.extend(chars[from..to].iter().map(|c| (*c, token)));
+    };
+
+    let mut i = 0;
+    while i < chars.len() {
+        // continues a comment or string from an earlier line
+        match open {
+            Open::BlockComment => {
+                let (end, closed) = close(&chars, i, syntax.block_comment.map_or("", |(_, end)| end), false);
+                push(&mut tokens, i, end, Some(Token::Comment));
+                i = end;
+                if closed {
+                    open = Open::Nothing;
+                }
+                continue;
+            }
+            Open::String(quote) => {
+                let (end, closed) = close(&chars, i, syntax.quotes[quote], language != Language::Shell || quote == 0);
+                push(&mut tokens, i, end, Some(Token::String));
+                i = end;
+                if closed {
+                    open = Open::Nothing;
+                }
+                continue;
+            }
+            Open::Nothing => {}
+        }
+
+        let c = chars[i];
+        let after_word = i > 0 && is_word(chars[i - 1]);
+
+        if starts_with(&chars, i, syntax.line_comment) && (language != Language::Shell || i == 0 || chars[i - 1].is_whitespace()) {
+            push(&mut tokens, i, chars.len(), Some(Token::Comment));
+            break;
+        }
+
+        if let Some((start, _)) = syntax.block_comment
+            && starts_with(&chars, i, start)
+        {
+            open = Open::BlockComment;
+            push(&mut tokens, i, i + start.chars().count(), Some(Token::Comment));
+            i += start.chars().count();
+            continue;
+        }
+
+        // string prefixes like b"" and f"" are colored with the string
+        let prefix = chars[i..]
+            .iter()
+            .take_while(|c| syntax.string_prefixes.contains(**c))
+            .count()
+            .min(2);
+        let quote_at = if after_word { i } else { i + prefix };
+        if let Some(quote) = syntax.quotes.iter().position(|q| starts_with(&chars, quote_at, q)) {
+            open = Open::String(quote);
+            let start = quote_at + syntax.quotes[quote].chars().count();
+            push(&mut tokens, i, start, Some(Token::String));
+            i = start;
+            continue;
+        }
+
+        if language == Language::Rust && c == '\'' {
+            match char_literal(&chars, i) {
+                Some(end) => {
+                    push(&mut tokens, i, end, Some(Token::String));
+                    i = end;
+                }
+                // lifetimes read like keywords
+                None => {
+                    let end = i + 1 + chars[i + 1..].iter().take_while(|c| is_word(**c)).count();
+                    push(&mut tokens, i, end, Some(Token::Keyword));
+                    i = end;
+                }
+            }
+            continue;
+        }
+
+        if c.is_ascii_digit() && !after_word {
+            let mut end = i + 1;
+            while end < chars.len()
+                && (is_word(chars[end]) || (chars[end] == '.' && chars.get(end + 1).is_some_and(char::is_ascii_digit)))
+            {
+                end += 1;
+            }
+            push(&mut tokens, i, end, Some(Token::Number));
+            i = end;
+            continue;
+        }
+
+        if language == Language::Shell && c == '$' {
+            let end = match chars.get(i + 1) {
+                Some('{') => close(&chars, i + 2, "}", false).0,
+                Some(n) if is_word(*n) => i + 1 + chars[i + 1..].iter().take_while(|c| is_word(**c)).count(),
+                Some(n) if "#?@*!$0-".contains(*n) => i + 2,
+                _ => i + 1,
+            };
+            push(&mut tokens, i, end, if end > i + 1 { Some(Token::Special) } else { None });
+            i = end;
+            continue;
+        }
+
+        if language == Language::Python && c == '@' && chars[..i].iter().all(|c| c.is_whitespace()) {
+            let end = i + 1 + chars[i + 1..].iter().take_while(|c| is_word(**c) || **c == '.').count();
+            push(&mut tokens, i, end, Some(Token::Special));
+            i = end;
+            continue;
+        }
+
+        if is_word(c) && !after_word {
+            let end = i + chars[i..].iter().take_while(|c| is_word(**c)).count();
+            let word: String = chars[i..end].iter().collect();
+            let macro_call = language == Language::Rust && chars.get(end) == Some(&'!') && chars.get(end + 1) != Some(&'=');
+
+            let token = if macro_call {
+                Some(Token::Special)
+            } else if syntax.keywords.contains(&word.as_str()) {
+                Some(Token::Keyword)
+            } else if syntax.types.contains(&word.as_str())
+                || (syntax.capitalized_types && word.starts_with(|c: char| c.is_uppercase()))
+            {
+                Some(Token::Type)
+            } else {
+                None
+            };
+
+            let end = if macro_call { end + 1 } else { end };
+            push(&mut tokens, i, end, token);
+            i = end;
+            continue;
+        }
+
+        push(&mut tokens, i, i + 1, None);
+        i += 1;
+    }
+
+    // only triple quoted python strings go on over the line end
+    if let Open::String(quote) = open
+        && language == Language::Python
+        && syntax.quotes[quote].chars().count() == 1
+    {
+        open = Open::Nothing;
+    }
+
+    (tokens, open)
+}
+
+/// joins neighbouring characters of the same token into runs
+fn merge(tokens: Vec<(char, Option<Token>)>) -> Vec<Run> {
+    let mut runs: Vec<Run> = Vec::new();
+
+    for (c, token) in tokens {
+        match runs.last_mut() {
+            Some(run) if run.token == token => run.text.push(c),
+            _ => runs.push(Run {
+                text: c.to_string(),
+                token,
+                ..Run::default()
+            }),
+        }
+    }
+
+    runs
+}
+
+/// ANSI color a token is printed with in the terminal
+pub fn ansi_color(token: Token) -> &'static str {
+    match token {
+        Token::Keyword => "35",
+        Token::Type => "33",
+        Token::String => "32",
+        Token::Number => "36",
+        Token::Comment => "90",
+        Token::Special => "34",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// the highlighted runs of every line, plain text left out
+    fn tokens(language: Language, text: &str) -> Vec<Vec<(String, Token)>> {
+        highlight(language, text)
+            .into_iter()
+            .map(|runs| runs.into_iter().filter_map(|run| Some((run.text, run.token?))).collect())
+            .collect()
+    }
+
+    fn line(pieces: &[(&str, Token)]) -> Vec<(String, Token)> {
+        pieces.iter().map(|(text, token)| (text.to_string(), *token)).collect()
+    }
+
+    #[test]
+    fn fence_names() {
+        assert_eq!(parse_language("rs"), Some(Language::Rust));
+        assert_eq!(parse_language("Python"), Some(Language::Python));
+        assert_eq!(parse_language("zsh"), Some(Language::Shell));
+        assert_eq!(parse_language("haskell"), None);
+    }
+
+    #[test]
+    fn rust() {
+        let text = concat!(
+            "fn main<'a>(x: &'a str) -> u8 {\n",
+            "    println!(\"{}\", 'c', b\"x\", 1.5); /* open\n",
+            "  still */ Vec::new() // done",
+        );
+        assert_eq!(
+            tokens(Language::Rust, text),
+            [
+                line(&[
+                    ("fn", Token::Keyword),
+                    ("'a", Token::Keyword),
+                    ("'a", Token::Keyword),
+                    ("str", Token::Type),
+                    ("u8", Token::Type),
+                ]),
+                line(&[
+                    ("println!", Token::Special),
+                    ("\"{}\"", Token::String),
+                    ("'c'", Token::String),
+                    ("b\"x\"", Token::String),
+                    ("1.5", Token::Number),
+                    ("/* open", Token::Comment),
+                ]),
+                line(&[("  still */", Token::Comment), ("Vec", Token::Type), ("// done", Token::Comment)]),
+            ]
+        );
+        // != is not a macro call and digits inside names are not numbers
+        assert_eq!(tokens(Language::Rust, "a!=x2"), [line(&[])]);
+    }
+
+    #[test]
+    fn python() {
+        let text = "@app.route\ndef f(self):\n    s = f'''a\nb''' # c";
+        assert_eq!(
+            tokens(Language::Python, text),
+            [
+                line(&[("@app.route", Token::Special)]),
+                line(&[("def", Token::Keyword), ("self", Token::Type)]),
+                line(&[("f'''a", Token::String)]),
+                line(&[("b'''", Token::String), ("# c", Token::Comment)]),
+            ]
+        );
+        // single quoted strings end with the line
+        assert_eq!(tokens(Language::Python, "'open\nx")[1], line(&[]));
+    }
+
+    #[test]
+    fn shell() {
+        assert_eq!(
+            tokens(Language::Shell, "echo \"$HOME\" ${PATH} $1 a#b # c"),
+            [line(&[
+                ("echo", Token::Type),
+                ("\"$HOME\"", Token::String),
+                ("${PATH}", Token::Special),
+                ("$1", Token::Special),
+                ("# c", Token::Comment),
+            ])]
+        );
+    }
+}
@@ -0,0 +1,636 @@
+use macroquad::prelude::*;
+use std::path::Path;
+
+use crate::defaults::*;
+use crate::diagnostic::*;
+use crate::document::*;
+use crate::markup::*;
+use crate::settings::*;
+use crate::slide::*;
+use crate::style::*;
+use crate::theming::*;
+use crate::utils::*;
+use crate::wrap::*;
+
+/// everything besides the deck that ends up in the exported page
+pub struct HtmlOptions<'a> {
+    pub title: String,
+    pub theme: Theme,
+    pub virtual_screen_size: Vec2,
+    pub numbering: bool,
+    pub numbering_anchor: NumberingAnchor,
+    pub linear_filtering: bool,
+    pub bg_image_path: Option<&'a str>,
+    pub bg_filter: bool,
+    pub bg_mode: BackgroundMode,
+    pub align: Align,
+    pub valign: VAlign,
+    pub font: &'a [u8],
+    pub mono_font: &'a [u8],
+    /// browsers fake the styles without a font of their own
+    pub bold_font: Option<&'a [u8]>,
+    pub italic_font: Option<&'a [u8]>,
+    pub wrap: bool,
+    /// faces of the embedded fonts to wrap text with, only loaded when some slide wraps
+    pub wrap_faces: Option<FontFaces<'a, fontdue::Font>>,
+}
+
+const BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+
+    for chunk in data.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+
+        out.push(BASE64_ALPHABET[(n >> 18) as usize & 63] as char);
+        out.push(BASE64_ALPHABET[(n >> 12) as usize & 63] as char);
+        out.push(if chunk.len() > 1 { BASE64_ALPHABET[(n >> 6) as usize & 63] as char } else { '=' });
+        out.push(if chunk.len() > 2 { BASE64_ALPHABET[n as usize & 63] as char } else { '=' });
+    }
+
+    out
+}
+
+/// mime type guessed from the first bytes of an image file
+fn image_mime(data: &[u8]) -> &'static str {
+    match data {
+        [0x89, b'P', b'N', b'G', ..] => "image/png",
+        [0xFF, 0xD8, ..] => "image/jpeg",
+        [b'G', b'I', b'F', ..] => "image/gif",
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => "image/webp",
+        [b'B', b'M', ..] => "image/bmp",
+        _ => "application/octet-stream",
+    }
+}
+
+fn data_uri(mime: &str, data: &[u8]) -> String {
+    format!("data:{};base64,{}", mime, base64(data))
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn css_color(color: Color) -> String {
+    format!("#{}", to_hex_color(color))
+}
+
+fn background_size(mode: BackgroundMode) -> &'static str {
+    match mode {
+        BackgroundMode::Fill => "cover",
+        BackgroundMode::Fit => "contain",
+    }
+}
+
+/// styled runs as inline elements, lines stay separated by newlines inside the pre.
+/// Only markup and code colors are written out, the rest inherits the font color of the slide
+fn runs_html(lines: &[Vec<Run>], palette: &Palette) -> String {
+    let line_html = |runs: &Vec<Run>| -> String {
+        runs.iter()
+            .map(|run| {
+                let mut html = escape(&run.text);
+                if let Some(color) = run.color.map(Color::from_hex).or_else(|| run.token.map(|token| palette.token_color(token))) {
+                    html = format!("<span style=\"color:{}\">{}</span>", css_color(color), html);
+                }
+                if run.highlight {
+                    html = format!("<mark>{}</mark>", html);
+                }
+                if run.italic {
+                    html = format!("<i>{}</i>", html);
+                }
+                if run.bold {
+                    html = format!("<b>{}</b>", html);
+                }
+                html
+            })
+            .collect()
+    };
+
+    lines.iter().map(line_html).collect::<Vec<String>>().join("\n")
+}
+
+/// lines tagged with the step they show up at and the steps they are dimmed at,
+/// the page script toggles them
+fn steps_html(lines: &[Vec<Run>], reveal: &[usize], palette: &Palette, spec: &SlideSpec) -> String {
+    lines
+        .iter()
+        .enumerate()
+        .map(|(i, runs)| {
+            let dimmed: Vec<String> = (0..spec.steps())
+                .filter(|step| !spec.focus.is_focused(*step, i + 1))
+                .map(|step| step.to_string())
+                .collect();
+            format!(
+                "<span data-from=\"{}\" data-dim=\"{}\">{}</span>",
+                reveal.get(i).copied().unwrap_or(0),
+                dimmed.join(" "),
+                runs_html(std::slice::from_ref(runs), palette)
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// @font-face rules for the bold and italic fonts that are set
+fn style_font_faces(options: &HtmlOptions) -> String {
+    let face = |font: Option<&[u8]>, descriptor: &str| match font {
+        Some(font) => format!(
+            "@font-face {{ font-family: \"reiha\"; {} src: url({}); }}\n",
+            descriptor,
+            data_uri("font/ttf", font)
+        ),
+        None => String::new(),
+    };
+    face(options.bold_font, "font-weight: bold;") + &face(options.italic_font, "font-style: italic;")
+}
+
+/// css for the slide number, positions match Scene::from_settings
+fn numbering_css(anchor: NumberingAnchor, size: &Vec2) -> String {
+    let left = format!("left:{}px;", size.x / 400f32);
+    let bottom = format!("bottom:{}px;", size.y / 300f32);
+    let (x, y) = match anchor {
+        NumberingAnchor::BottomLeft => (left, bottom),
+        NumberingAnchor::BottomCenter => ("left:50%;transform:translateX(-50%);".to_string(), bottom),
+        NumberingAnchor::BottomRight => ("right:0;".to_string(), bottom),
+        NumberingAnchor::TopLeft => (left, "top:0;".to_string()),
+        NumberingAnchor::TopCenter => ("left:50%;transform:translateX(-50%);".to_string(), "top:0;".to_string()),
+        NumberingAnchor::TopRight => ("right:0;".to_string(), "top:0;".to_string()),
+    };
+    format!("{}{}font-size:{}px;", x, y, (size.x as u16 / 32u16))
+}
+
+/// renders the deck as a single standalone page, images and fonts are embedded.
+/// Output only depends on the input, so it can be diffed
+pub fn render_html(deck: &Deck, options: &HtmlOptions) -> Result<String, Vec<Diagnostic>> {
+    let mut errors = Vec::new();
+    let mut slides_html = String::new();
+    let mut backgrounds: Vec<(String, String)> = Vec::new();
+
+    for spec in &deck.slides {
+        let kind = match spec.slide_type {
+            SlideType::Empty => "empty",
+            SlideType::Text => "text",
+            SlideType::Image => "image",
+            SlideType::TextImage => "text-image",
+            SlideType::Code => "code",
+        };
+
+        let directives = &spec.directives;
+        let align = format!(
+            " align-{} valign-{}",
+            align_name(directives.align.unwrap_or(options.align)),
+            valign_name(directives.valign.unwrap_or(options.valign))
+        );
+        let mut attributes = format!(" data-num=\"{}\"", spec.num);
+        if let Some(color) = &directives.font_color {
+            attributes.push_str(&format!(" style=\"color:#{}\"", color));
+        }
+        if let Some(color) = &directives.bg_color {
+            attributes.push_str(&format!(" data-bg-color=\"#{}\"", color));
+        }
+        if spec.steps() > 1 {
+            attributes.push_str(&format!(" data-steps=\"{}\"", spec.steps()));
+        }
+        if let Some(numbering) = directives.numbering {
+            attributes.push_str(if numbering { " data-numbering=\"on\"" } else { " data-numbering=\"off\"" });
+        }
+
+        let mut backdrop = String::new();
+        match &directives.background {
+            None => {}
+            Some(BackgroundOverride::Hidden) => attributes.push_str(" data-background=\"none\""),
+            Some(BackgroundOverride::Image { path, mode, line }) => {
+                attributes.push_str(" data-background=\"image\"");
+                // every image is embedded once, slides refer to it by class
+                let index = match backgrounds.iter().position(|(p, _)| p == path) {
+                    Some(index) => Some(index),
+                    None => match std::fs::read(path) {
+                        Ok(data) => {
+                            backgrounds.push((path.clone(), data_uri(image_mime(&data), &data)));
+                            Some(backgrounds.len() - 1)
+                        }
+                        Err(e) => {
+                            errors.push(image_diagnostic_at(deck, *line, spec.num, path, &e.to_string()));
+                            None
+                        }
+                    },
+                };
+                if let Some(index) = index {
+                    backdrop = format!(
+                        "<div class=\"backdrop backdrop-{}\" style=\"background-size:{}\"></div>\n",
+                        index,
+                        background_size(mode.unwrap_or(options.bg_mode))
+                    );
+                }
+            }
+        }
+
+        slides_html.push_str(&format!("<section class=\"slide {}{}\"{}>\n{}", kind, align, attributes, backdrop));
+
+        if let Some(img_path) = &spec.image {
+            match std::fs::read(img_path) {
+                Ok(data) => slides_html.push_str(&format!(
+                    "<div class=\"picture\"><img alt=\"\" src=\"{}\"></div>\n",
+                    data_uri(image_mime(&data), &data)
+                )),
+                Err(e) => errors.push(image_diagnostic(deck, spec, img_path, &e.to_string())),
+            }
+        }
+
+        if spec.text().is_some() {
+            // code colors follow the background the slide ends up with
+            let bg_color = directives.bg_color.as_deref().and_then(|hex| parse_hex_color(hex).ok());
+            let theme = Theme {
+                background_color: bg_color.unwrap_or(options.theme.background_color),
+                font_color: options.theme.font_color,
+            };
+            let wrap_faces = options.wrap_faces.as_ref().filter(|_| directives.wrap.unwrap_or(options.wrap));
+            let (runs, reveal) = slide_lines(spec, wrap_faces, &options.virtual_screen_size);
+            let text_html = if spec.steps() > 1 {
+                steps_html(&runs, &reveal, &theme.palette(), spec)
+            } else {
+                runs_html(&runs, &theme.palette())
+            };
+            slides_html.push_str(&format!("<div class=\"area\"><pre class=\"fit\">{}</pre></div>\n", text_html));
+        }
+
+        if let Some(notes) = spec.notes.as_deref().filter(|n| !n.trim().is_empty()) {
+            slides_html.push_str(&format!("<aside class=\"notes\">{}</aside>\n", escape(&clean_notes(notes).join("\n"))));
+        }
+
+        slides_html.push_str("</section>\n");
+    }
+
+    let background = match options.bg_image_path {
+        Some(path) => match std::fs::read(path) {
+            Ok(data) => format!(
+                "background-image:url({});background-size:{};background-position:center;background-repeat:no-repeat;{}",
+                data_uri(image_mime(&data), &data),
+                background_size(options.bg_mode),
+                if options.bg_filter { "" } else { "image-rendering:pixelated;" }
+            ),
+            Err(e) => {
+                errors.push(Diagnostic::file_error(format!("failed to load background image `{}`: {}", path, e)));
+                String::new()
+            }
+        },
+        None => String::new(),
+    };
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let slide_backgrounds: String = backgrounds
+        .iter()
+        .enumerate()
+        .map(|(i, (_, uri))| format!(".backdrop-{} {{ background-image: url({}); }}\n", i, uri))
+        .collect();
+
+    let size = options.virtual_screen_size;
+
+    Ok(HTML_TEMPLATE
+        .replace("{title}", &escape(&options.title))
+        .replace("{width}", &size.x.to_string())
+        .replace("{height}", &size.y.to_string())
+        .replace("{text_image_split}", &(CFACT * 100f32).round().to_string())
+        .replace("{bg_color}", &css_color(options.theme.background_color))
+        .replace("{font_color}", &css_color(options.theme.font_color))
+        .replace("{background}", &background)
+        .replace("{slide_backgrounds}", &slide_backgrounds)
+        .replace("{image_rendering}", if options.linear_filtering { "auto" } else { "pixelated" })
+        .replace("{numbering_css}", &numbering_css(options.numbering_anchor, &size))
+        .replace("{numbering_hidden}", if options.numbering { "" } else { " hidden" })
+        .replace("{font}", &data_uri("font/ttf", options.font))
+        .replace("{mono_font}", &data_uri("font/ttf", options.mono_font))
+        .replace("{style_fonts}", &style_font_faces(options))
+        .replace("{highlight}", &format!(
+            "rgba({}, {}, {}, {})",
+            (HIGHLIGHT_COLOR.r * 255f32).round(),
+            (HIGHLIGHT_COLOR.g * 255f32).round(),
+            (HIGHLIGHT_COLOR.b * 255f32).round(),
+            HIGHLIGHT_COLOR.a
+        ))
+        .replace("{slides}", &slides_html))
+}
+
+/// writes the deck as a standalone HTML file without opening a window, returns the exit code
+pub fn run(input_path: &str, out: &Path, settings: &Settings) -> i32 {
+    let content = match std::fs::read_to_string(input_path) {
+        Ok(content) => content,
+        Err(e) => {
+            report(&[Diagnostic::file_error(format!("failed to read `{}`: {}", input_path, e))], input_path);
+            return 1;
+        }
+    };
+
+    let (deck, mut diagnostics) = parse_deck(&content);
+    if has_errors(&diagnostics) {
+        report(&diagnostics, input_path);
+        return 1;
+    }
+
+    let read_font = |path: Option<&str>, fallback: &[u8]| match path {
+        Some(path) => std::fs::read(path).map_err(|e| format!("failed to read font `{}`: {}", path, e)),
+        None => Ok(fallback.to_vec()),
+    };
+    let style_font = |path: &Option<String>| path.as_deref().map(|path| read_font(Some(path), &[])).transpose();
+    let fonts = (|| -> Result<_, String> {
+        Ok((
+            read_font(settings.font_path.value.as_deref(), DEFAULT_FONT)?,
+            read_font(settings.mono_font_path.value.as_deref(), MONO_FONT)?,
+            style_font(&settings.bold_font_path.value)?,
+            style_font(&settings.italic_font_path.value)?,
+        ))
+    })();
+    let (font, mono_font, bold_font, italic_font) = match fonts {
+        Ok(fonts) => fonts,
+        Err(e) => {
+            report(&[Diagnostic::file_error(e)], input_path);
+            return 1;
+        }
+    };
+
+    // wrapped lines are broken where the window would break them, measured with the same fonts
+    let wraps = settings.wrap.value || deck.slides.iter().any(|spec| spec.directives.wrap == Some(true));
+    let load = |data: &[u8]| {
+        fontdue::Font::from_bytes(data, fontdue::FontSettings::default()).map_err(|e| format!("failed to load font: {}", e))
+    };
+    let wrap_fonts = wraps
+        .then(|| -> Result<_, String> {
+            Ok((
+                load(&font)?,
+                bold_font.as_deref().map(load).transpose()?,
+                italic_font.as_deref().map(load).transpose()?,
+            ))
+        })
+        .transpose();
+    let wrap_fonts = match wrap_fonts {
+        Ok(fonts) => fonts,
+        Err(e) => {
+            report(&[Diagnostic::file_error(e)], input_path);
+            return 1;
+        }
+    };
+
+    let options = HtmlOptions {
+        title: Path::new(input_path)
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| "Reiha".to_string()),
+        theme: settings.theme.value,
+        virtual_screen_size: settings.virtual_resolution.value,
+        numbering: settings.numbering.value,
+        numbering_anchor: settings.numbering_anchor.value,
+        linear_filtering: settings.filtering.value == FilterMode::Linear,
+        bg_image_path: settings.bg_image_path.value.as_deref(),
+        bg_filter: settings.bg_filter.value,
+        bg_mode: settings.bg_mode.value,
+        align: settings.text_align.value,
+        valign: settings.text_valign.value,
+        font: &font,
+        mono_font: &mono_font,
+        bold_font: bold_font.as_deref(),
+        italic_font: italic_font.as_deref(),
+        wrap: settings.wrap.value,
+        wrap_faces: wrap_fonts.as_ref().map(|(regular, bold, italic)| FontFaces {
+            regular,
+            bold: bold.as_ref(),
+            italic: italic.as_ref(),
+        }),
+    };
+
+    let html = match render_html(&deck, &options) {
+        Ok(html) => html,
+        Err(mut errors) => {
+            diagnostics.append(&mut errors);
+            report(&diagnostics, input_path);
+            return 1;
+        }
+    };
+    report(&diagnostics, input_path);
+
+    if let Err(e) = std::fs::write(out, html) {
+        eprintln!("error: failed to write `{}`: {}", out.display(), e);
+        return 1;
+    }
+    println!("{}", out.display());
+
+    0
+}
+
+const HTML_TEMPLATE: &str = r##"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<meta name="generator" content="reiha">
+<title>{title}</title>
+<style>
+@font-face { font-family: "reiha"; src: url({font}); }
+@font-face { font-family: "reiha-mono"; src: url({mono_font}); }
+{style_fonts}html, body { margin: 0; height: 100%; background: #000; overflow: hidden; }
+#stage {
+    position: absolute; left: 50%; top: 50%;
+    width: {width}px; height: {height}px;
+    transform-origin: 0 0;
+    overflow: hidden;
+}
+#stage.swapped { --bg: {font_color}; --fg: {bg_color}; }
+#stage { --bg: {bg_color}; --fg: {font_color}; background-color: var(--bg); color: var(--fg); }
+#background { position: absolute; inset: 0; {background} }
+.slide { position: absolute; inset: 0; display: none; font-family: "reiha"; }
+.slide.current { display: block; }
+.slide img, .backdrop { image-rendering: {image_rendering}; }
+.backdrop { position: absolute; inset: 0; background-position: center; background-repeat: no-repeat; }
+{slide_backgrounds}
+.picture { position: absolute; left: 0; top: 0; width: 100%; height: 100%; display: flex; align-items: center; justify-content: center; }
+.picture img { max-width: 100%; max-height: 100%; min-width: 100%; min-height: 100%; object-fit: contain; }
+.area { position: absolute; left: 0; top: 0; width: 100%; height: 100%; display: flex; align-items: center; justify-content: center; }
+.text-image .picture { height: {text_image_split}%; }
+.text-image .area { top: {text_image_split}%; height: calc(100% - {text_image_split}%); }
+.fit { margin: 0; font: inherit; line-height: 1; white-space: pre; text-align: left; }
+.code .fit { font-family: "reiha-mono"; }
+.fit mark { background: {highlight}; color: inherit; }
+.fit .dim { opacity: 0.3; }
+.fit .later { visibility: hidden; }
+.align-left .area { justify-content: flex-start; }
+.align-left .fit { margin-left: 2%; }
+.align-center .fit { text-align: center; }
+.align-right .area { justify-content: flex-end; }
+.align-right .fit { margin-right: 2%; text-align: right; }
+.valign-top .area { align-items: flex-start; }
+.valign-bottom .area { align-items: flex-end; }
+.valign-top .fit, .valign-bottom .fit { position: relative; }
+.valign-top .fit { top: 2%; }
+.valign-bottom .fit { top: -2%; }
+.notes { display: none; }
+#number { position: absolute; line-height: 1; font-family: "reiha"; {numbering_css} }
+#notes {
+    position: fixed; left: 0; right: 0; bottom: 0; max-height: 40%; overflow: auto;
+    margin: 0; padding: 1em; background: rgba(0, 0, 0, 0.85); color: #fff;
+    font: 16px/1.4 monospace; white-space: pre-wrap;
+}
+</style>
+</head>
+<body>
+<div id="stage">
+<div id="background"></div>
+{slides}<div id="number"{numbering_hidden}></div>
+</div>
+<pre id="notes" hidden></pre>
+<script>
+(function () {
+    var stage = document.getElementById("stage");
+    var number = document.getElementById("number");
+    var background = document.getElementById("background");
+    var notes = document.getElementById("notes");
+    var slides = Array.prototype.slice.call(document.querySelectorAll(".slide"));
+    var current = 0;
+    var step = 0;
+
+    // same rule as find_max_font_size: largest size that fits 96% of the area
+    function fit(element) {
+        var area = element.parentNode;
+        var maxWidth = area.clientWidth * 0.96;
+        var maxHeight = area.clientHeight * 0.96;
+        var low = 4, high = 1024;
+        while (low < high) {
+            var mid = Math.ceil((low + high) / 2);
+            element.style.fontSize = mid + "px";
+            if (element.scrollWidth <= maxWidth && element.scrollHeight <= maxHeight) {
+                low = mid;
+            } else {
+                high = mid - 1;
+            }
+        }
+        element.style.fontSize = low + "px";
+    }
+
+    function scale() {
+        var factor = Math.min(window.innerWidth / {width}, window.innerHeight / {height});
+        stage.style.transform = "scale(" + factor + ") translate(-50%, -50%)";
+    }
+
+    function steps(slide) {
+        return parseInt(slide.getAttribute("data-steps"), 10) || 1;
+    }
+
+    // hides the lines still to come and dims code lines out of focus, hidden lines keep their space
+    function showStep(index) {
+        step = index;
+        var lines = slides[current].querySelectorAll("[data-from]");
+        Array.prototype.forEach.call(lines, function (line) {
+            line.classList.toggle("later", parseInt(line.getAttribute("data-from"), 10) > step);
+            line.classList.toggle("dim", (" " + line.getAttribute("data-dim") + " ").indexOf(" " + step + " ") >= 0);
+        });
+    }
+
+    // steps of a slide come before the next one, going back lands on the last step
+    function next() {
+        if (step + 1 < steps(slides[current])) { showStep(step + 1); }
+        else if (current + 1 < slides.length) { show(current + 1); }
+    }
+
+    function previous() {
+        if (step > 0) { showStep(step - 1); }
+        else if (current > 0) { show(current - 1, true); }
+    }
+
+    function show(index, lastStep) {
+        current = Math.max(0, Math.min(slides.length - 1, index));
+        slides.forEach(function (slide, i) { slide.classList.toggle("current", i === current); });
+        var slide = slides[current];
+        if (!slide) { return; }
+        var text = slide.querySelector(".fit");
+        if (text) { fit(text); }
+        showStep(lastStep ? steps(slide) - 1 : 0);
+        number.textContent = current + 1;
+        // slide directives: own colors, background and numbering
+        var numbering = slide.getAttribute("data-numbering");
+        stage.style.backgroundColor = slide.getAttribute("data-bg-color") || "";
+        number.style.color = slide.style.color;
+        number.style.visibility = numbering === "off" ? "hidden" : "";
+        number.style.display = numbering === "on" ? "block" : "";
+        background.hidden = slide.hasAttribute("data-background");
+        var aside = slide.querySelector(".notes");
+        notes.textContent = "[slide " + (current + 1) + "/" + slides.length + "]\n" + (aside ? aside.textContent : "");
+        if (location.hash !== "#" + (current + 1)) { history.replaceState(null, "", "#" + (current + 1)); }
+    }
+
+    document.addEventListener("keydown", function (event) {
+        switch (event.key) {
+            case "ArrowRight": case "ArrowDown": case "j": case "l": case "PageDown": case " ":
+                next(); break;
+            case "ArrowLeft": case "ArrowUp": case "k": case "h": case "PageUp":
+                previous(); break;
+            case "Home": show(0); break;
+            case "End": show(slides.length - 1); break;
+            case "f": case "F11":
+                event.preventDefault();
+                if (document.fullscreenElement) { document.exitFullscreen(); }
+                else { document.documentElement.requestFullscreen(); }
+                break;
+            case "n": number.hidden = !number.hidden; break;
+            case "s": stage.classList.toggle("swapped"); break;
+            case "p": notes.hidden = !notes.hidden; break;
+        }
+    });
+    document.addEventListener("click", next);
+    document.addEventListener("contextmenu", function (event) { event.preventDefault(); previous(); });
+    window.addEventListener("resize", scale);
+
+    scale();
+    var start = parseInt(location.hash.slice(1), 10);
+    document.fonts.ready.then(function () { show(isNaN(start) ? 0 : start - 1); });
+    show(isNaN(start) ? 0 : start - 1);
+})();
+</script>
+</body>
+</html>
+"##;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXPECTED: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/test/html/deck.html");
+
+    /// REIHA_BLESS=1 cargo test writes the expected page after an intended change
+    #[test]
+    fn deck_renders_like_the_golden_file() {
+        let (deck, diagnostics) = parse_deck(include_str!("../test/html/deck.rh"));
+        assert!(diagnostics.is_empty());
+
+        let options = HtmlOptions {
+            title: "deck".to_string(),
+            theme: DARK_THEME,
+            virtual_screen_size: VIRTUAL_SCREEN_SIZE,
+            numbering: true,
+            numbering_anchor: NumberingAnchor::BottomRight,
+            linear_filtering: false,
+            bg_image_path: None,
+            bg_filter: true,
+            bg_mode: BackgroundMode::Fill,
+            align: Align::default(),
+            valign: VAlign::default(),
+            font: b"regular font",
+            mono_font: b"mono font",
+            bold_font: None,
+            italic_font: None,
+            wrap: false,
+            wrap_faces: None,
+        };
+        let html = render_html(&deck, &options).expect("the fixture has no images to miss");
+
+        if std::env::var_os("REIHA_BLESS").is_some() {
+            std::fs::write(EXPECTED, &html).unwrap();
+        }
+        assert_eq!(html, std::fs::read_to_string(EXPECTED).unwrap());
+    }
+}
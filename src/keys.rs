@@ -0,0 +1,161 @@
+use macroquad::prelude::*;
+
+/// what a key press does while presenting
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Next,
+    Previous,
+    Fullscreen,
+    SwapTheme,
+    Preview,
+    Numbering,
+    /// presenter view in the presentation window, on or off
+    Presenter,
+    /// stops the clock and starts it again, e.g. for questions in the middle of the talk
+    Pause,
+    /// clock back to zero, e.g. after a rehearsal run
+    ResetTimer,
+    Quit,
+}
+
+pub const ACTIONS: &[(Action, &str)] = &[
+    (Action::Next, "next"),
+    (Action::Previous, "previous"),
+    (Action::Fullscreen, "fullscreen"),
+    (Action::SwapTheme, "swap_theme"),
+    (Action::Preview, "preview"),
+    (Action::Numbering, "numbering"),
+    (Action::Presenter, "presenter"),
+    (Action::Pause, "pause"),
+    (Action::ResetTimer, "reset_timer"),
+    (Action::Quit, "quit"),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Trigger {
+    Key(KeyCode),
+    Mouse(MouseButton),
+}
+
+const KEY_NAMES: &[(&str, KeyCode)] = &[
+    ("Right", KeyCode::Right), ("Left", KeyCode::Left), ("Up", KeyCode::Up), ("Down", KeyCode::Down),
+    ("PageUp", KeyCode::PageUp), ("PageDown", KeyCode::PageDown), ("Home", KeyCode::Home), ("End", KeyCode::End),
+    ("Space", KeyCode::Space), ("Enter", KeyCode::Enter), ("Tab", KeyCode::Tab), ("Backspace", KeyCode::Backspace),
+    ("Escape", KeyCode::Escape), ("Insert", KeyCode::Insert), ("Delete", KeyCode::Delete),
+    ("A", KeyCode::A), ("B", KeyCode::B), ("C", KeyCode::C), ("D", KeyCode::D), ("E", KeyCode::E),
+    ("F", KeyCode::F), ("G", KeyCode::G), ("H", KeyCode::H), ("I", KeyCode::I), ("J", KeyCode::J),
+    ("K", KeyCode::K), ("L", KeyCode::L), ("M", KeyCode::M), ("N", KeyCode::N), ("O", KeyCode::O),
+    ("P", KeyCode::P), ("Q", KeyCode::Q), ("R", KeyCode::R), ("S", KeyCode::S), ("T", KeyCode::T),
+    ("U", KeyCode::U), ("V", KeyCode::V), ("W", KeyCode::W), ("X", KeyCode::X), ("Y", KeyCode::Y),
+    ("Z", KeyCode::Z),
+    ("0", KeyCode::Key0), ("1", KeyCode::Key1), ("2", KeyCode::Key2), ("3", KeyCode::Key3), ("4", KeyCode::Key4),
+    ("5", KeyCode::Key5), ("6", KeyCode::Key6), ("7", KeyCode::Key7), ("8", KeyCode::Key8), ("9", KeyCode::Key9),
+    ("F1", KeyCode::F1), ("F2", KeyCode::F2), ("F3", KeyCode::F3), ("F4", KeyCode::F4), ("F5", KeyCode::F5),
+    ("F6", KeyCode::F6), ("F7", KeyCode::F7), ("F8", KeyCode::F8), ("F9", KeyCode::F9), ("F10", KeyCode::F10),
+    ("F11", KeyCode::F11), ("F12", KeyCode::F12),
+];
+
+/// key name as written in config.toml, e.g. "J", "PageDown", "MouseLeft"
+pub fn parse_trigger(name: &str) -> Result<Trigger, String> {
+    match name {
+        "MouseLeft" => return Ok(Trigger::Mouse(MouseButton::Left)),
+        "MouseRight" => return Ok(Trigger::Mouse(MouseButton::Right)),
+        "MouseMiddle" => return Ok(Trigger::Mouse(MouseButton::Middle)),
+        _ => {}
+    }
+
+    KEY_NAMES
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, code)| Trigger::Key(*code))
+        .ok_or_else(|| format!("unknown key '{}'", name))
+}
+
+pub fn trigger_name(trigger: Trigger) -> String {
+    match trigger {
+        Trigger::Mouse(MouseButton::Left) => "MouseLeft".to_string(),
+        Trigger::Mouse(MouseButton::Right) => "MouseRight".to_string(),
+        Trigger::Mouse(MouseButton::Middle) => "MouseMiddle".to_string(),
+        Trigger::Mouse(button) => format!("{:?}", button),
+        Trigger::Key(code) => KEY_NAMES
+            .iter()
+            .find(|(_, c)| *c == code)
+            .map(|(name, _)| name.to_string())
+            .unwrap_or_else(|| format!("{:?}", code)),
+    }
+}
+
+pub fn parse_action(name: &str) -> Option<Action> {
+    ACTIONS.iter().find(|(_, n)| *n == name).map(|(action, _)| *action)
+}
+
+/// keys bound to every action, a binding replaces all default keys of its action
+#[derive(Clone, Debug)]
+pub struct KeyMap {
+    bindings: Vec<(Action, Vec<Trigger>)>,
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        use Trigger::*;
+        Self {
+            bindings: vec![
+                (
+                    Action::Next,
+                    vec![
+                        Key(KeyCode::Right),
+                        Key(KeyCode::Down),
+                        Key(KeyCode::J),
+                        Key(KeyCode::L),
+                        Key(KeyCode::PageDown),
+                        Mouse(MouseButton::Left),
+                    ],
+                ),
+                (
+                    Action::Previous,
+                    vec![
+                        Key(KeyCode::Left),
+                        Key(KeyCode::Up),
+                        Key(KeyCode::K),
+                        Key(KeyCode::H),
+                        Key(KeyCode::PageUp),
+                        Mouse(MouseButton::Right),
+                    ],
+                ),
+                (Action::Fullscreen, vec![Key(KeyCode::F), Key(KeyCode::F11)]),
+                (Action::SwapTheme, vec![Key(KeyCode::S)]),
+                (Action::Preview, vec![Key(KeyCode::P)]),
+                (Action::Numbering, vec![Key(KeyCode::N)]),
+                (Action::Presenter, vec![Key(KeyCode::V)]),
+                (Action::Pause, vec![Key(KeyCode::T)]),
+                (Action::ResetTimer, vec![Key(KeyCode::R)]),
+                (Action::Quit, vec![Key(KeyCode::Q), Key(KeyCode::Escape)]),
+            ],
+        }
+    }
+}
+
+impl KeyMap {
+    pub fn bind(&mut self, action: Action, triggers: Vec<Trigger>) {
+        match self.bindings.iter_mut().find(|(a, _)| *a == action) {
+            Some((_, bound)) => *bound = triggers,
+            None => self.bindings.push((action, triggers)),
+        }
+    }
+
+    pub fn triggers(&self, action: Action) -> &[Trigger] {
+        self.bindings
+            .iter()
+            .find(|(a, _)| *a == action)
+            .map(|(_, triggers)| triggers.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// true on the frame one of the action's keys went down
+    pub fn pressed(&self, action: Action) -> bool {
+        self.triggers(action).iter().any(|trigger| match trigger {
+            Trigger::Key(code) => is_key_pressed(*code),
+            Trigger::Mouse(button) => is_mouse_button_pressed(*button),
+        })
+    }
+}
@@ -1,367 +1,389 @@
 use macroquad::prelude::*;
 use macroquad_canvas::Canvas2D;
 
+mod check;
+mod cli;
 mod config_handle;
+mod dashboard;
 mod defaults;
+mod diagnostic;
+mod document;
+mod export;
+mod highlight;
+mod html;
+mod keys;
+mod markup;
+mod notes;
+mod pacing;
+mod pdf;
+mod presenter;
+mod raster;
+mod rehearsal;
+mod scene;
+mod settings;
 mod slide;
+mod stats;
+mod style;
+mod subset;
 mod theming;
+mod timer;
+mod transition;
 mod utils;
-
-use crate::config_handle::*;
-use crate::defaults::*;
-use crate::slide::*;
-use crate::theming::*;
+mod watch;
+mod wrap;
+
+use crate::dashboard::*;
+use crate::diagnostic::*;
+use crate::keys::*;
+use crate::pacing::*;
+use crate::presenter::*;
+use crate::rehearsal::*;
+use crate::scene::*;
+use crate::settings::*;
+use crate::timer::*;
+use crate::transition::*;
 use crate::utils::*;
+use crate::watch::*;
 
-#[macroquad::main("Reiha")]
-async fn main() {
-    let config = Config::from_file();
-
-    let mut theme = config.theme.unwrap_or(DARK_THEME);
-    let mut filtering = config.filtering.unwrap_or(FilterMode::Nearest);
-    let mut font: Font = if let Some(path) = &config.font_path {
-        let data = std::fs::read(path).expect("Failed to read font file from config");
-        load_ttf_font_from_bytes(&data).expect("Failed to load font from config")
-    } else {
-        load_ttf_font_from_bytes(DEFAULT_FONT).unwrap()
-    };
-    let mut mono_font: Font = if let Some(path) = &config.mono_font_path {
-        let data = std::fs::read(path).expect("Failed to read font file from config");
-        load_ttf_font_from_bytes(&data).expect("Failed to load font from config")
-    } else {
-        load_ttf_font_from_bytes(MONO_FONT).unwrap()
-    };
-    let mut virtual_screen_size = config.virtual_resolution.unwrap_or(VIRTUAL_SCREEN_SIZE);
-    let mut numbering = config.numbering.unwrap_or(false);
-    let mut preview = config.preview.unwrap_or(false);
-    let mut numbering_anchor = config.numbering_anchor.unwrap_or(NumberingAnchor::BottomLeft);
-
-    let mut bg_image: Option<Texture2D> = None; // load later
-    let mut bg_image_path: Option<String> = config.bg_image_path;
-    let mut bg_scale: f32 = 1f32; // change later
-    let mut bg_filter: bool = config.bg_filter.unwrap_or(true);
-    let mut bg_mode: BackgroundMode = config.bg_mode.unwrap_or(BackgroundMode::Fill);
-
-    // FIXME: why didn't I used a struct?
-
-    let args: Vec<String> = std::env::args().collect();
-    if args.len() < 2 || args.contains(&"--help".to_string())|| args.contains(&"-h".to_string()) {
-        println!(
-            "Usage: reiha <path/to/presentaion> <options>\n\
-            Options:\n\
-            -t, --theme dark|light|<bg_hex>x<font_hex> - Set theme\n\
-            -l, --linear - set texture filtering for images to linear, default is nearest\n\
-            -f, --font <path/to/font> - Use a custom font\n\
-            -m, --mono-font <path/to/font> - Use a custom font\n\
-            -r, --resolution <width>x<height> - Set virtual resolution (default 1600x1200) (max 3840x3840)\n\
-            -n, --numbering - turn on the slide numbering\n\
-            -a, --numbering-anchor <position> - position: [ bl | bc | br | tl | tc | tr ]. If incorrect defaults to bl (bottom left)\n\
-            -b, --background <path/to/image.png> <filtering> <mode> - filtering: [ linear | l | nearest | n ], mode: [ fit | fill ].\n\
-            -p, --preview - shows next slide in your terminal if there is such\n\n\
-            Reiha optionally looks at /home/$USER/.config/reiha/config\n\
-            config syntax is options provided line by line.\n\
-            ____________________________________________________________\n\
-            Reiha | ver1.4.1 | bk "
-        );
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() {
+        print!("{}", cli::help());
         return;
     }
 
-    let input_path = &args[1];
-
-    for i in 2..args.len() {
-        match args[i].as_str() {
-            "-t" | "--theme" => {
-                if let Some(value) = args.get(i + 1) {
-                    if value == "dark" {
-                        theme = DARK_THEME;
-                    } else if value == "light" {
-                        theme = LIGHT_THEME;
-                    } else if value.contains('x') {
-                        let parts: Vec<&str> = value.split('x').collect();
-                        if parts.len() == 2 {
-                            if let (Ok(bg), Ok(font)) =
-                                (parse_hex_color(parts[0]), parse_hex_color(parts[1]))
-                            {
-                                theme = Theme {
-                                    background_color: bg,
-                                    font_color: font,
-                                };
-                            } else {
-                                eprintln!("Invalid hex values in -t argument, using DARK_THEME");
-                            }
-                        } else {
-                            eprintln!("Invalid format for -t argument, expected HEXxHEX");
-                        }
-                    } else {
-                        eprintln!("Unknown theme option '{}', using DARK_THEME", value);
-                    }
-                }
-            }
-            "-l" | "--linear" => {
-                filtering = FilterMode::Linear;
-            }
-            "-f" | "--font" => {
-                if let Some(path) = args.get(i + 1) {
-                    let data = std::fs::read(path).expect("Failed to read font file");
-                    font = load_ttf_font_from_bytes(&data).expect("Failed to load font");
-                }
-            }
-            "-m" | "--mono-font" => {
-                if let Some(path) = args.get(i + 1) {
-                    let data = std::fs::read(path).expect("Failed to read font file");
-                    mono_font = load_ttf_font_from_bytes(&data).expect("Failed to load font");
-                }
-            }
-            "-r" | "--resolution" => {
-                if let Some(value) = args.get(i + 1) {
-                    if let Some((w, h)) = value.split_once('x') {
-                        if let (Ok(w), Ok(h)) = (w.parse::<f32>(), h.parse::<f32>()) {
-                            if w > 3840.0 || h > 3840.0 {
-                                eprintln!(
-                                    "Error: Resolution too large ({}x{}). Max allowed is 3840x3840.",
-                                    w, h
-                                );
-                                std::process::exit(1);
-                            }
-                            virtual_screen_size = vec2(w, h);
-                        } else {
-                            eprintln!(
-                                "Error: Invalid resolution format. Use <width>x<height> (e.g., 1600x1200)"
-                            );
-                            std::process::exit(1);
-                        }
-                    }
-                }
-            }
-            "-n" | "--numbering" => {
-                numbering = true;
-            }
-            "-a" | "--numbering-anchor" => {
-                if let Some(val) = args.get(i + 1) {
-                    match val.as_str() {
-                        "bl" => numbering_anchor = NumberingAnchor::BottomLeft,
-                        "bc" => numbering_anchor = NumberingAnchor::BottomCenter,
-                        "br" => numbering_anchor = NumberingAnchor::BottomRight,
-                        "tl" => numbering_anchor = NumberingAnchor::TopLeft,
-                        "tc" => numbering_anchor = NumberingAnchor::TopCenter,
-                        "tr" => numbering_anchor = NumberingAnchor::TopRight,
-                        _    => numbering_anchor = NumberingAnchor::BottomLeft
-                    }
-                }
-            }
-            "-b" | "--background" => {
-                if let Some(path) = args.get(i + 1) {
-                    bg_image_path = Some(path.clone());
-                }
-                if let Some(filter) = args.get(i + 2) {
-                    match filter.as_str() {
-                        "l" | "linear" => bg_filter = true,
-                        "n" | "nearest" => bg_filter = false,
-                        _ => panic!("Incorrect usage of background image! It takes 3 arguments. Second is filtering[nearest[n] and linear[l]]")
-                    }
-                }
-                if let Some(mode) = args.get(i + 3) {
-                    match mode.as_str() {
-                        "fit"  => bg_mode = BackgroundMode::Fit,
-                        "fill" => bg_mode = BackgroundMode::Fill,
-                        _ => panic!("Incorrect usage of background image! It takes 3 arguments. Third is mode[fit and fill]")
-                    }
-                }
+    let (command, input_path, options) = match cli::parse_args(&args) {
+        Ok(cli::Invocation::Help) => {
+            print!("{}", cli::help());
+            return;
+        }
+        Ok(cli::Invocation::Version) => {
+            println!("reiha {}", env!("CARGO_PKG_VERSION"));
+            return;
+        }
+        Ok(cli::Invocation::Run { command, input_path, options }) => (command, input_path, options),
+        Err(e) => {
+            eprintln!("error: {}\n\nFor more information, try '--help'.", e);
+            std::process::exit(2);
+        }
+    };
+
+    let settings = match Settings::load(input_path.as_deref(), &options) {
+        Ok(settings) => settings,
+        Err(e) => {
+            eprintln!("error: {}\n\nFor more information, try '--help'.", e);
+            std::process::exit(2);
+        }
+    };
+    // every command but config has a path, parse_args made sure of that
+    let input_path = input_path.unwrap_or_default();
+
+    match command {
+        cli::Command::Present if std::env::var_os(PRESENTER_WINDOW_VAR).is_some() => {
+            macroquad::Window::new("Reiha presenter", presenter::run_window(input_path, options));
+        }
+        cli::Command::Present => {
+            macroquad::Window::new("Reiha", present(input_path, settings, options));
+        }
+        cli::Command::Check => std::process::exit(check::run(&input_path, &settings)),
+        cli::Command::Notes => std::process::exit(notes::run(&input_path)),
+        cli::Command::Stats => std::process::exit(stats::run(&input_path)),
+        cli::Command::Config => {
+            let explain = options.iter().any(|(opt, _)| opt.long == "--explain");
+            print_settings(&settings, explain);
+        }
+        cli::Command::Export => {
+            if settings.export_format.value == export::ExportFormat::Html {
+                let code = match export::prepare_output(&input_path, &settings) {
+                    Ok(out) => html::run(&input_path, &out, &settings),
+                    Err(code) => code,
+                };
+                std::process::exit(code);
             }
-            "-p" | "--preview" => {
-                preview = true;
+            if !export::has_display() {
+                std::process::exit(export::run_headless(&input_path, &settings));
             }
-            _ => {}
+            macroquad::Window::new("Reiha export", async move {
+                std::process::exit(export::run(input_path, settings).await);
+            });
         }
     }
+}
 
-    if !bg_image_path.is_none() {
-        bg_image = Some(load_texture(&bg_image_path.unwrap()).await.expect("Failed to load background image"));
-        if bg_filter { bg_image.as_ref().unwrap().set_filter(FilterMode::Linear) }
-        else { bg_image.as_ref().unwrap().set_filter(FilterMode::Nearest) }
-    }
-
-    if !bg_image.is_none() {
-        let screen_height: f32 = virtual_screen_size.y;
-        let screen_width: f32 = virtual_screen_size.x;
-
-        let bgi = bg_image.as_ref().unwrap();
-        let scale_x = screen_width / bgi.width();
-        let scale_y = screen_height / bgi.height();
+async fn present(input_path: String, mut settings: Settings, options: Vec<cli::ParsedOption>) {
+    let input_path = &input_path;
 
-        match bg_mode {
-            BackgroundMode::Fill => bg_scale = scale_x.max(scale_y),
-            BackgroundMode::Fit  => bg_scale = scale_x.min(scale_y)
-        }
-    }
+    let mut scene = Scene::from_settings(&settings);
+    let mut virtual_screen_size = scene.virtual_screen_size;
+    let filtering = settings.filtering.value;
+    let mut preview = settings.preview.value;
 
     set_default_filter_mode(filtering);
     println!("Filter set");
 
-    let virtual_screen = Canvas2D::new(virtual_screen_size.x, virtual_screen_size.y);
+    let mut virtual_screen = Canvas2D::new(virtual_screen_size.x, virtual_screen_size.y);
     println!(
         "Virtual Screen created {}x{}",
         virtual_screen_size.x, virtual_screen_size.y
     );
 
+    let mut transitions = Transitions::new(virtual_screen_size);
+    let mut presenter_view = PresenterView::new(virtual_screen_size);
+    let mut split = settings.presenter.value == PresenterMode::Split;
     let mut is_fullscreen = false;
 
-    let slides: Vec<Slide> = parse(input_path, &virtual_screen_size, &font, &mono_font).await;
+    let (mut slides, diagnostics) = parse(input_path, &virtual_screen_size, &scene.faces(), &scene.mono_font, scene.wrap);
+    report(&diagnostics, input_path);
+    if has_errors(&diagnostics) {
+        std::process::exit(1);
+    }
     println!("Data parsed");
 
+    // the deck loads, so the presenter window will load it too
+    let mut presenter_window = None;
+    if settings.presenter.value == PresenterMode::Window {
+        match PresenterWindow::spawn() {
+            Ok(window) => presenter_window = Some(window),
+            Err(e) => eprintln!("warning: {}", e),
+        }
+    }
+
     let mut current_slide = 0;
+    // step of the current slide: its code focus group or how many `+` lines are shown
+    let mut current_step = 0;
     let mut sec_timer: f32 = 0f32;
+    let mut watcher = Watcher::new(input_path, &settings);
+    let mut front_matter = front_matter_settings(input_path);
+    // problems of the last reload, shown in the terminal until the file is fixed
+    let mut reload_diagnostics: Vec<Diagnostic> = Vec::new();
     println!("Control vars created");
 
-    let start_time = std::time::Instant::now();
+    let mut schedule = Schedule::new(&slides, &settings);
+    let mut timer = Timer::new(slides.len());
+    // a broken rehearsals file is left alone, this run is not added to it
+    let mut rehearsals = match Rehearsals::load(input_path) {
+        Ok(rehearsals) => Some(rehearsals),
+        Err(e) => {
+            eprintln!("warning: {}", e);
+            None
+        }
+    };
+    let mut comparisons = rehearsals.as_ref().map(|r| r.compare(&slides)).unwrap_or_default();
     println!("Timestamp placed");
 
-    let show_in_terminal = true;
-
-    let numbering_position: Vec2;
-    match numbering_anchor {
-        NumberingAnchor::BottomLeft => {
-            numbering_position = Vec2 {
-                x: virtual_screen_size.x / 400f32,
-                y: virtual_screen_size.y - virtual_screen_size.y / 300f32,
-            };
-        },
-        NumberingAnchor::BottomCenter => {
-            numbering_position = Vec2 {
-                x: virtual_screen_size.x / 2f32,
-                y: virtual_screen_size.y - virtual_screen_size.y / 300f32,
-            };
-        },
-        NumberingAnchor::BottomRight => {
-            numbering_position = Vec2 {
-                x: virtual_screen_size.x,
-                y: virtual_screen_size.y - virtual_screen_size.y / 300f32,
-            };
-        },
-        NumberingAnchor::TopLeft => {
-            numbering_position = Vec2 {
-                x: virtual_screen_size.x / 400f32,
-                y: virtual_screen_size.y - virtual_screen_size.y,
-            };
-        },
-        NumberingAnchor::TopCenter => {
-            numbering_position = Vec2 {
-                x: virtual_screen_size.x / 2f32,
-                y: virtual_screen_size.y - virtual_screen_size.y,
-            };
-        },
-        NumberingAnchor::TopRight => {
-            numbering_position = Vec2 {
-                x: virtual_screen_size.x,
-                y: virtual_screen_size.y - virtual_screen_size.y,
-            };
-        },
-    }
-
-    let numbering_size = (virtual_screen_size.x as u16 / 32u16) as u16;
+    // closing the window ends the loop like quit, so the terminal gets restored
+    prevent_quit();
 
     println!("Main loop start");
+    let mut dashboard = Dashboard::new(input_path);
     loop {
         sec_timer -= get_frame_time();
-        clear_background(BLACK);
-        {
-            set_camera(&virtual_screen.camera);
-            clear_background(theme.background_color);
 
-            if !bg_image.is_none() {
-                draw_img_background(&bg_image.as_ref().unwrap(), &bg_scale, &virtual_screen_size);
+        if let Some(reload) = watcher.poll(input_path, get_frame_time()) {
+            reload_diagnostics.clear();
+            let new_front_matter = front_matter_settings(input_path);
+            if new_front_matter != front_matter {
+                // settings of the deck changed, build everything from the layered settings again
+                let reloaded = Settings::load(Some(input_path), &options)
+                    .and_then(|new_settings| Ok((Scene::try_from_settings(&new_settings)?, new_settings)));
+                match reloaded {
+                    Ok((new_scene, new_settings)) => {
+                        if new_scene.virtual_screen_size != virtual_screen_size {
+                            virtual_screen_size = new_scene.virtual_screen_size;
+                            virtual_screen = Canvas2D::new(virtual_screen_size.x, virtual_screen_size.y);
+                            transitions = Transitions::new(virtual_screen_size);
+                            presenter_view = PresenterView::new(virtual_screen_size);
+                        }
+                        set_default_filter_mode(new_settings.filtering.value);
+                        preview = new_settings.preview.value;
+                        scene = new_scene;
+                        settings = new_settings;
+                        watcher = Watcher::new(input_path, &settings);
+                    }
+                    Err(e) => reload_diagnostics.push(Diagnostic::file_error(e)),
+                }
+                front_matter = new_front_matter;
+            } else if reload == Reload::Everything
+                && let Err(e) = scene.reload_assets(&settings)
+            {
+                reload_diagnostics.push(Diagnostic::file_error(e));
             }
 
-            if let Some(slide) = slides.get(current_slide) {
-                slide.draw(&font, &mono_font, &theme.font_color, &virtual_screen_size);
-                if numbering {
-                    draw_numbering(
-                        &current_slide,
-                        &font,
-                        &numbering_position,
-                        &numbering_size,
-                        &theme,
-                        &numbering_anchor,
-                    );
+            let (new_slides, diagnostics) = parse(input_path, &virtual_screen_size, &scene.faces(), &scene.mono_font, scene.wrap);
+            if !has_errors(&diagnostics) {
+                current_slide = follow_slide(&slides, &new_slides, current_slide);
+                timer.reload(&slides, &new_slides, current_slide);
+                slides = new_slides;
+                comparisons = rehearsals.as_ref().map(|r| r.compare(&slides)).unwrap_or_default();
+                current_step = current_step.min(slides.get(current_slide).map_or(0, |s| s.steps() - 1));
+                transitions.stop();
+                if let Some(window) = &mut presenter_window {
+                    window.reload();
                 }
-                let elapsed = start_time.elapsed().as_secs();
-                if show_in_terminal {
-                    if sec_timer <= 0f32 {
-                        clear_screen();
-                        print_time(Some(elapsed));
-                        slide.print(slides.len());
-                        if preview {
-                            if let Some(next_slide) = slides.get(current_slide + 1) {
-                                println!( "\n\n\n\
-                                    ___[ Next Slide ]____________________________________________\
-                                    ");
-                                next_slide.print(slides.len());
-                            }
-                        }
+            }
+            reload_diagnostics.extend(diagnostics);
+            schedule = Schedule::new(&slides, &settings);
+            sec_timer = 0f32;
+        }
 
-                        sec_timer = 1f32;
-                    }
-                }
+        let clock = Clock {
+            paused: timer.is_paused(),
+            ..schedule.clock(current_slide, timer.elapsed().as_secs(), timer.on_slide())
+        };
+
+        clear_background(BLACK);
+        {
+            transitions.draw(&scene, &slides, (current_slide, current_step), &virtual_screen);
+            if settings.progress_bar.value {
+                set_camera(&virtual_screen.camera);
+                draw_progress_bar(&clock, current_slide, slides.len(), &virtual_screen_size);
+                set_default_camera();
             }
 
-            set_default_camera();
+            if sec_timer <= 0f32 {
+                dashboard.draw(&DashboardState {
+                    slides: &slides,
+                    slide: current_slide,
+                    step: current_step,
+                    clock,
+                    comparison: comparisons.get(current_slide).copied().unwrap_or_default(),
+                    preview,
+                    diagnostics: &reload_diagnostics,
+                });
+                sec_timer = 1f32;
+            }
+        }
+        if split {
+            let state = PresenterState {
+                slide: current_slide,
+                step: current_step,
+                elapsed: clock.elapsed,
+                on_slide: clock.on_slide,
+                paused: clock.paused,
+            };
+            let comparison = comparisons.get(current_slide).copied().unwrap_or_default();
+            presenter_view.draw(&scene, &slides, state, &clock, &comparison, &virtual_screen);
+        } else {
+            virtual_screen.draw();
         }
-        virtual_screen.draw();
 
         // Inputs
-        if is_key_pressed(KeyCode::Right)
-            || is_key_pressed(KeyCode::Down)
-            || is_key_pressed(KeyCode::J)
-            || is_key_pressed(KeyCode::L)
-            || is_key_pressed(KeyCode::PageDown)
-            || is_mouse_button_pressed(MouseButton::Left)
-        {
-            if current_slide < slides.len() - 1 {
+        let keys = &settings.keys;
+        // the presenter window sends the keys pressed in it
+        let remote = match presenter_window.as_mut().map(PresenterWindow::actions) {
+            Some(Some(actions)) => actions,
+            Some(None) => {
+                presenter_window = None;
+                Vec::new()
+            }
+            None => Vec::new(),
+        };
+        let pressed = |action| keys.pressed(action) || remote.contains(&action);
+
+        // steps of a slide come before the next one, going back lands on the last step
+        let shown = (current_slide, current_step);
+        if pressed(Action::Next) {
+            if slides.get(current_slide).is_some_and(|slide| current_step + 1 < slide.steps()) {
+                current_step += 1;
+                sec_timer = 0f32;
+            } else if current_slide + 1 < slides.len() {
                 current_slide += 1;
+                current_step = 0;
                 sec_timer = 0f32;
             }
         }
 
-        if is_key_pressed(KeyCode::Left)
-            || is_key_pressed(KeyCode::Up)
-            || is_key_pressed(KeyCode::K)
-            || is_key_pressed(KeyCode::H)
-            || is_key_pressed(KeyCode::PageUp)
-            || is_mouse_button_pressed(MouseButton::Right)
-        {
-            if current_slide > 0 {
+        if pressed(Action::Previous) {
+            if current_step > 0 {
+                current_step -= 1;
+                sec_timer = 0f32;
+            } else if current_slide > 0 {
                 current_slide -= 1;
+                current_step = slides[current_slide].steps() - 1;
                 sec_timer = 0f32;
             }
         }
 
-        if is_key_pressed(KeyCode::F) || is_key_pressed(KeyCode::F11) {
+        if current_slide != shown.0 {
+            transitions.start(&scene, &slides, shown, current_slide);
+            timer.enter(current_slide);
+        }
+
+        if keys.pressed(Action::Fullscreen) {
             is_fullscreen = !is_fullscreen;
             set_fullscreen(is_fullscreen);
         }
 
-        if is_key_pressed(KeyCode::S) {
-            let new_theme = Theme{
-                background_color: theme.font_color,
-                font_color: theme.background_color
-            };
-            theme = new_theme;
+        if pressed(Action::SwapTheme) {
+            scene.swap_theme();
+            if let Some(window) = &mut presenter_window {
+                window.forward(Action::SwapTheme);
+            }
         }
 
-        if is_key_pressed(KeyCode::P) {
+        if pressed(Action::Preview) {
             preview = !preview;
             sec_timer = 0f32;
         }
 
-        if is_key_pressed(KeyCode::N) {
-            numbering = !numbering;
+        if pressed(Action::Numbering) {
+            scene.numbering = !scene.numbering;
+            if let Some(window) = &mut presenter_window {
+                window.forward(Action::Numbering);
+            }
+        }
+
+        if keys.pressed(Action::Presenter) {
+            split = !split;
+        }
+
+        if pressed(Action::Pause) {
+            timer.toggle_pause();
+            sec_timer = 0f32;
         }
 
-        if is_key_pressed(KeyCode::Q) || is_key_pressed(KeyCode::Escape) {
+        if pressed(Action::ResetTimer) {
+            timer.reset();
+            sec_timer = 0f32;
+        }
+
+        if pressed(Action::Quit) || is_quit_requested() {
             break;
         }
 
+        if let Some(window) = &mut presenter_window {
+            window.show(PresenterState {
+                slide: current_slide,
+                step: current_step,
+                elapsed: timer.elapsed().as_secs(),
+                on_slide: timer.on_slide(),
+                paused: timer.is_paused(),
+            });
+        }
+
         //draw_fps();
         next_frame().await
     }
+
+    // the report goes to the normal screen, so the dashboard has to leave first
+    drop(dashboard);
+    let report = timer.report(&slides, input_path, &comparisons);
+    match &settings.timing_report.value {
+        Some(path) => {
+            if let Err(e) = std::fs::write(path, report) {
+                eprintln!("error: failed to write `{}`: {}", path, e);
+            }
+        }
+        None => print!("{}", report),
+    }
+
+    if settings.rehearse.value
+        && let Some(rehearsals) = &mut rehearsals
+    {
+        match rehearsals.save(&slides, &timer.dwell()) {
+            Ok(true) => println!("rehearsal saved to `{}`", rehearsals.path().display()),
+            Ok(false) => {}
+            Err(e) => eprintln!("error: {}", e),
+        }
+    }
 }
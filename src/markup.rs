@@ -0,0 +1,257 @@
+use crate::document::SlideType;
+use crate::highlight::*;
+
+/// piece of a text line drawn with one face and color
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Run {
+    pub text: String,
+    pub bold: bool,
+    pub italic: bool,
+    pub highlight: bool,
+    /// 0xrrggbb, None is the font color of the slide
+    pub color: Option<u32>,
+    /// set on highlighted code, the palette of the slide colors it
+    pub token: Option<Token>,
+}
+
+/// markup that could not be read, column is 1-based and counts characters
+#[derive(Clone, Debug, PartialEq)]
+pub struct StyleError {
+    pub column: usize,
+    pub len: usize,
+    pub message: String,
+}
+
+/// colors `{name|text}` takes by name, as 0xrrggbb
+pub const COLOR_NAMES: &[(&str, u32)] = &[
+    ("red", 0xe74c3c),
+    ("orange", 0xe67e22),
+    ("yellow", 0xf1c40f),
+    ("green", 0x2ecc71),
+    ("cyan", 0x1abc9c),
+    ("blue", 0x3498db),
+    ("purple", 0x9b59b6),
+    ("pink", 0xff6b9d),
+    ("gray", 0x95a5a6),
+    ("white", 0xffffff),
+    ("black", 0x000000),
+];
+
+#[derive(Clone, Copy, PartialEq)]
+enum Marker {
+    Bold,
+    Italic,
+    Highlight,
+}
+
+/// emphasis marker starting at i and its length, `**` and `__` count like `*` and `_`
+fn marker_at(chars: &[char], i: usize) -> Option<(Marker, usize)> {
+    let double = |c: char| chars.get(i + 1) == Some(&c);
+    match chars[i] {
+        '*' => Some((Marker::Bold, if double('*') { 2 } else { 1 })),
+        '_' => Some((Marker::Italic, if double('_') { 2 } else { 1 })),
+        '=' if double('=') => Some((Marker::Highlight, 2)),
+        _ => None,
+    }
+}
+
+/// markers open at the start of a word, so snake_case and 2*3*4 stay as they are
+fn can_open(chars: &[char], i: usize, len: usize) -> bool {
+    let before = i.checked_sub(1).map(|j| chars[j]);
+    let after = chars.get(i + len);
+    !before.is_some_and(char::is_alphanumeric) && after.is_some_and(|c| !c.is_whitespace())
+}
+
+fn can_close(chars: &[char], i: usize, len: usize) -> bool {
+    let before = i.checked_sub(1).map(|j| chars[j]);
+    let after = chars.get(i + len);
+    before.is_some_and(|c| !c.is_whitespace()) && !after.is_some_and(|c| c.is_alphanumeric())
+}
+
+fn closes_later(chars: &[char], from: usize, marker: Marker) -> bool {
+    (from + 1..chars.len()).any(|j| {
+        chars[j - 1] != '\\'
+            && marker_at(chars, j).is_some_and(|(m, len)| m == marker && can_close(chars, j, len))
+    })
+}
+
+/// `{name|` at i, returns the name and the index after `|`
+fn color_opener(chars: &[char], i: usize) -> Option<(String, usize)> {
+    let name: String = chars[i + 1..]
+        .iter()
+        .take_while(|c| c.is_ascii_alphanumeric() || **c == '#')
+        .collect();
+    let bar = i + 1 + name.chars().count();
+    let closed = chars[bar.min(chars.len())..].contains(&'}');
+    (!name.is_empty() && chars.get(bar) == Some(&'|') && closed).then_some((name, bar + 1))
+}
+
+/// `rrggbb` or `#rrggbb` as 0xrrggbb
+pub fn parse_hex_rgb(s: &str) -> Option<u32> {
+    let s = s.trim_start_matches('#');
+    if s.len() != 6 {
+        return None;
+    }
+    u32::from_str_radix(s, 16).ok()
+}
+
+/// a color name or hex color as 0xrrggbb
+pub fn parse_color(name: &str) -> Option<u32> {
+    COLOR_NAMES
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, color)| *color)
+        .or_else(|| parse_hex_rgb(name))
+}
+
+/// splits a line written with `*bold*`, `_italic_`, `==highlight==` and `{color|text}` into runs.
+/// Markers without a partner are kept as text, `\` in front of a marker keeps it as text too.
+/// A color that is not known is kept as text with its braces, and reported
+pub fn parse_line(line: &str) -> (Vec<Run>, Vec<StyleError>) {
+    let chars: Vec<char> = line.chars().collect();
+    let mut runs = Vec::new();
+    let mut errors = Vec::new();
+    let mut current = Run::default();
+    // open colors, None for an unknown one that is kept as text
+    let mut colors: Vec<Option<u32>> = Vec::new();
+
+    // starts a new run with the styles changed by f
+    let mut switch = |current: &mut Run, f: &mut dyn FnMut(&mut Run)| {
+        if !current.text.is_empty() {
+            runs.push(current.clone());
+        }
+        current.text.clear();
+        f(current);
+    };
+
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+
+        if c == '\\' && chars.get(i + 1).is_some_and(|n| "*_={}\\".contains(*n)) {
+            current.text.push(chars[i + 1]);
+            i += 2;
+            continue;
+        }
+
+        if let Some((marker, len)) = marker_at(&chars, i) {
+            let active = match marker {
+                Marker::Bold => current.bold,
+                Marker::Italic => current.italic,
+                Marker::Highlight => current.highlight,
+            };
+            let toggles = if active {
+                can_close(&chars, i, len)
+            } else {
+                can_open(&chars, i, len) && closes_later(&chars, i + len, marker)
+            };
+            if toggles {
+                switch(&mut current, &mut |run| match marker {
+                    Marker::Bold => run.bold = !run.bold,
+                    Marker::Italic => run.italic = !run.italic,
+                    Marker::Highlight => run.highlight = !run.highlight,
+                });
+                i += len;
+                continue;
+            }
+        }
+
+        if c == '{'
+            && let Some((name, next)) = color_opener(&chars, i)
+        {
+            match parse_color(&name) {
+                Some(color) => {
+                    colors.push(Some(color));
+                    switch(&mut current, &mut |run| run.color = Some(color));
+                    i = next;
+                    continue;
+                }
+                None => {
+                    colors.push(None);
+                    errors.push(StyleError {
+                        column: i + 2,
+                        len: name.chars().count(),
+                        message: format!("unknown color `{}`", name),
+                    });
+                }
+            }
+        }
+
+        if c == '}'
+            && let Some(Some(_)) = colors.pop()
+        {
+            let color = colors.iter().rev().flatten().next().copied();
+            switch(&mut current, &mut |run| run.color = color);
+            i += 1;
+            continue;
+        }
+
+        current.text.push(c);
+        i += 1;
+    }
+
+    if !current.text.is_empty() {
+        runs.push(current);
+    }
+
+    (runs, errors)
+}
+
+/// lines of a slide as runs, code is highlighted if its language is known and taken as it is otherwise
+pub fn slide_runs(slide_type: SlideType, language: Option<Language>, text: &str) -> Vec<Vec<Run>> {
+    match (slide_type, language) {
+        (SlideType::Code, Some(language)) => highlight(language, text),
+        (SlideType::Code, None) => text.lines().map(plain_run).collect(),
+        _ => text.lines().map(|line| parse_line(line).0).collect(),
+    }
+}
+
+fn plain_run(line: &str) -> Vec<Run> {
+    if line.is_empty() {
+        Vec::new()
+    } else {
+        vec![Run { text: line.to_string(), ..Run::default() }]
+    }
+}
+
+/// text without markup, one line per line of runs
+pub fn plain_text(lines: &[Vec<Run>]) -> String {
+    lines
+        .iter()
+        .map(|runs| runs.iter().map(|run| run.text.as_str()).collect::<String>())
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn colored(runs: &[Run]) -> Vec<(&str, Option<u32>)> {
+        runs.iter().map(|run| (run.text.as_str(), run.color)).collect()
+    }
+
+    #[test]
+    fn styles_and_escapes() {
+        let (runs, errors) = parse_line("*bold* _it_ ==hi== snake_case \\*not\\*");
+        assert!(errors.is_empty());
+        assert!(runs[0].bold && runs[0].text == "bold");
+        assert!(runs[2].italic && runs[2].text == "it");
+        assert!(runs[4].highlight && runs[4].text == "hi");
+        assert_eq!(plain_text(&[runs]), "bold it hi snake_case *not*");
+    }
+
+    #[test]
+    fn colors_nest() {
+        let (runs, errors) = parse_line("{red|a {00ff00|b} c}");
+        assert!(errors.is_empty());
+        assert_eq!(colored(&runs), [("a ", Some(0xe74c3c)), ("b", Some(0x00ff00)), (" c", Some(0xe74c3c))]);
+    }
+
+    #[test]
+    fn unknown_color_is_kept_as_text() {
+        let (runs, errors) = parse_line("{red|a {teal|b} c}");
+        assert_eq!(colored(&runs), [("a {teal|b} c", Some(0xe74c3c))]);
+        assert_eq!(errors, [StyleError { column: 9, len: 4, message: "unknown color `teal`".to_string() }]);
+    }
+}
@@ -0,0 +1,40 @@
+use crate::diagnostic::*;
+use crate::document::*;
+use crate::markup::*;
+use crate::utils::*;
+
+/// prints the speaker notes of every slide, returns the process exit code
+pub fn run(input_path: &str) -> i32 {
+    let (deck, diagnostics) = read_deck(input_path);
+    report(&diagnostics, input_path);
+    let Some(deck) = deck else {
+        return 1;
+    };
+
+    let total = deck.slides.len();
+    for (i, spec) in deck.slides.iter().enumerate() {
+        // first line of the slide reminds which one it is
+        let title = match spec.slide_type {
+            SlideType::Empty => "[ empty ]".to_string(),
+            SlideType::Image => "[ image ]".to_string(),
+            SlideType::Code => "[ code ]".to_string(),
+            SlideType::Text | SlideType::TextImage => {
+                let first = spec.lines.iter().find(|l| !l.trim().is_empty());
+                first.map(|l| plain_text(&[parse_line(l.trim()).0])).unwrap_or_default()
+            }
+        };
+
+        if i > 0 {
+            println!();
+        }
+        println!("[slide {}/{}] {}", spec.num, total, title);
+
+        if let Some(notes) = &spec.notes {
+            for line in clean_notes(notes) {
+                println!("{}", line);
+            }
+        }
+    }
+
+    0
+}
@@ -0,0 +1,266 @@
+use macroquad::prelude::*;
+
+use crate::settings::*;
+use crate::slide::*;
+use crate::utils::*;
+
+/// how the talk does against its plan
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Pace {
+    OnTrack,
+    /// seconds until the current slide is planned to start
+    Ahead(u64),
+    /// seconds past the planned end of the current slide
+    Behind(u64),
+}
+
+/// how worried the timers look, the thresholds come from the settings
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd)]
+pub enum Urgency {
+    #[default]
+    Fine,
+    Warning,
+    Alarm,
+}
+
+/// what the timers show at one moment
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Clock {
+    /// seconds since the presentation started
+    pub elapsed: u64,
+    /// seconds on the current slide
+    pub on_slide: u64,
+    /// seconds left of the talk, negative once it runs over. None without a duration
+    pub remaining: Option<i64>,
+    pub pace: Option<Pace>,
+    /// seconds the current slide may take, from its `% time` directive
+    pub slide_time: Option<u64>,
+    pub urgency: Urgency,
+    /// the clock was stopped with the pause key
+    pub paused: bool,
+}
+
+impl Clock {
+    /// "12:05" or "12:05 paused"
+    pub fn time_text(&self) -> String {
+        match self.paused {
+            true => format!("{} paused", format_time(self.elapsed)),
+            false => format_time(self.elapsed),
+        }
+    }
+
+    /// "12:05 left" or "1:10 over"
+    pub fn remaining_text(&self) -> Option<String> {
+        self.remaining.map(|secs| match secs {
+            0.. => format!("{} left", format_time(secs as u64)),
+            _ => format!("{} over", format_time(secs.unsigned_abs())),
+        })
+    }
+
+    /// "on pace", "0:45 ahead" or "1:10 behind"
+    pub fn pace_text(&self) -> Option<String> {
+        self.pace.map(|pace| match pace {
+            Pace::OnTrack => "on pace".to_string(),
+            Pace::Ahead(secs) => format!("{} ahead", format_time(secs)),
+            Pace::Behind(secs) => format!("{} behind", format_time(secs)),
+        })
+    }
+
+    /// "slide 0:45 of 1:30" for slides with a time of their own
+    pub fn slide_text(&self) -> Option<String> {
+        self.slide_time
+            .map(|time| format!("slide {} of {}", format_time(self.on_slide), format_time(time)))
+    }
+
+    /// time left, pace and slide time, whichever there is a plan for
+    pub fn plan_text(&self) -> String {
+        [self.remaining_text(), self.pace_text(), self.slide_text()]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<String>>()
+            .join("   ")
+    }
+}
+
+/// planned seconds of every slide, from the talk duration and the `% time` directives
+pub struct Schedule {
+    duration: Option<u64>,
+    /// `% time` of every slide
+    times: Vec<Option<u64>>,
+    /// planned seconds of every slide, slides without a time share what the others leave
+    plan: Vec<u64>,
+    warning: u64,
+    alarm: u64,
+}
+
+impl Schedule {
+    pub fn new(slides: &[Slide], settings: &Settings) -> Self {
+        let duration = settings.duration.value;
+        let times: Vec<Option<u64>> = slides.iter().map(|slide| slide.time).collect();
+        let planned = times.iter().flatten().fold(0u64, |sum, time| sum.saturating_add(*time));
+        let unplanned = times.iter().filter(|time| time.is_none()).count() as u64;
+        let share = match duration {
+            Some(duration) if unplanned > 0 => duration.saturating_sub(planned) / unplanned,
+            _ => 0,
+        };
+
+        Self {
+            duration,
+            plan: times.iter().map(|time| time.unwrap_or(share)).collect(),
+            times,
+            warning: settings.pace_warning.value,
+            alarm: settings.pace_alarm.value,
+        }
+    }
+
+    /// timers on a slide, `on_slide` seconds after it came up
+    pub fn clock(&self, slide: usize, elapsed: u64, on_slide: u64) -> Clock {
+        let remaining = self.duration.map(|duration| duration.min(i64::MAX as u64) as i64 - elapsed as i64);
+        let pace = self.duration.map(|_| {
+            let start = self.plan.iter().take(slide).fold(0u64, |sum, time| sum.saturating_add(*time));
+            let end = start.saturating_add(self.plan.get(slide).copied().unwrap_or(0));
+            if elapsed > end {
+                Pace::Behind(elapsed - end)
+            } else if elapsed < start {
+                Pace::Ahead(start - elapsed)
+            } else {
+                Pace::OnTrack
+            }
+        });
+        let slide_time = self.times.get(slide).copied().flatten();
+
+        // behind the plan of the talk or over the time of the slide, whichever is worse
+        let behind = match pace {
+            Some(Pace::Behind(secs)) => secs,
+            _ => 0,
+        };
+        let late = behind.max(slide_time.map_or(0, |time| on_slide.saturating_sub(time)));
+        let urgency = if remaining.is_some_and(|secs| secs < 0) || late >= self.alarm {
+            Urgency::Alarm
+        } else if late >= self.warning {
+            Urgency::Warning
+        } else {
+            Urgency::Fine
+        };
+
+        Clock { elapsed, on_slide, remaining, pace, slide_time, urgency, paused: false }
+    }
+}
+
+pub fn urgency_color(urgency: Urgency) -> Color {
+    match urgency {
+        Urgency::Fine => Color::from_hex(0x3fb950),
+        Urgency::Warning => Color::from_hex(0xd29922),
+        Urgency::Alarm => Color::from_hex(0xf85149),
+    }
+}
+
+/// ANSI color the plan is printed with in the terminal
+pub fn urgency_ansi(urgency: Urgency) -> &'static str {
+    match urgency {
+        Urgency::Fine => "32",
+        Urgency::Warning => "33",
+        Urgency::Alarm => "31",
+    }
+}
+
+/// thin bar along the bottom of the slide, as long as the part of the talk that is over.
+/// Without a duration it follows the slides instead
+pub fn draw_progress_bar(clock: &Clock, slide: usize, slide_count: usize, virtual_screen_size: &Vec2) {
+    let progress = match clock.remaining {
+        Some(remaining) => clock.elapsed as f32 / (clock.elapsed as f32 + remaining.max(0) as f32).max(1f32),
+        None => (slide + 1) as f32 / slide_count.max(1) as f32,
+    };
+    let height = (virtual_screen_size.y / 200f32).max(2f32);
+    let color = Color { a: 0.6, ..urgency_color(clock.urgency) };
+
+    draw_rectangle(
+        0f32,
+        virtual_screen_size.y - height,
+        virtual_screen_size.x * progress.min(1f32),
+        height,
+        color,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// schedule of slides with the given `% time`s, warning at 30 and alarm at 90 seconds late
+    fn schedule(times: &[Option<u64>], duration: Option<u64>) -> Schedule {
+        let slides: Vec<Slide> = times
+            .iter()
+            .enumerate()
+            .map(|(i, time)| Slide { time: *time, ..Slide::plain(i as u32 + 1, "") })
+            .collect();
+        let mut settings = Settings::default();
+        settings.duration.value = duration;
+        settings.pace_warning.value = 30;
+        settings.pace_alarm.value = 90;
+        Schedule::new(&slides, &settings)
+    }
+
+    #[test]
+    fn slides_without_a_time_share_the_rest() {
+        let shared = schedule(&[None, Some(120), None, None], Some(600));
+        assert_eq!(shared.plan, [160, 120, 160, 160]);
+
+        // nothing is left to share when the times take more than the talk
+        let overbooked = schedule(&[Some(80), Some(60), None], Some(100));
+        assert_eq!(overbooked.plan, [80, 60, 0]);
+
+        // and there is no plan without a duration
+        let open_ended = schedule(&[None, Some(60)], None);
+        assert_eq!(open_ended.plan, [0, 60]);
+        assert_eq!(open_ended.clock(1, 10, 10).pace, None);
+        assert_eq!(open_ended.clock(1, 10, 10).remaining, None);
+    }
+
+    #[test]
+    fn pace_at_slide_boundaries() {
+        // the second slide is planned from 160 to 280 seconds
+        let schedule = schedule(&[None, Some(120), None, None], Some(600));
+        let pace = |elapsed| schedule.clock(1, elapsed, 0).pace;
+
+        assert_eq!(pace(150), Some(Pace::Ahead(10)));
+        assert_eq!(pace(160), Some(Pace::OnTrack));
+        assert_eq!(pace(280), Some(Pace::OnTrack));
+        assert_eq!(pace(281), Some(Pace::Behind(1)));
+    }
+
+    #[test]
+    fn running_over_the_talk_is_an_alarm() {
+        let schedule = schedule(&[None, None], Some(600));
+
+        let on_time = schedule.clock(1, 600, 300);
+        assert_eq!(on_time.remaining, Some(0));
+        assert_eq!(on_time.urgency, Urgency::Fine);
+
+        // a second over is only a second behind, but the talk is over
+        let over = schedule.clock(1, 601, 301);
+        assert_eq!(over.remaining, Some(-1));
+        assert_eq!(over.pace, Some(Pace::Behind(1)));
+        assert_eq!(over.urgency, Urgency::Alarm);
+        assert_eq!(over.remaining_text().unwrap(), "0:01 over");
+    }
+
+    #[test]
+    fn warning_and_alarm_thresholds() {
+        // the first slide is planned to end at 160 seconds
+        let schedule = schedule(&[None, Some(120), None, None], Some(600));
+        let urgency = |elapsed| schedule.clock(0, elapsed, elapsed).urgency;
+
+        assert_eq!(urgency(189), Urgency::Fine);
+        assert_eq!(urgency(190), Urgency::Warning);
+        assert_eq!(urgency(249), Urgency::Warning);
+        assert_eq!(urgency(250), Urgency::Alarm);
+
+        // a slide over its own time counts even when the talk is on pace
+        let clock = schedule.clock(1, 200, 150);
+        assert_eq!(clock.pace, Some(Pace::OnTrack));
+        assert_eq!(clock.urgency, Urgency::Warning);
+        assert_eq!(clock.slide_text().unwrap(), "slide 2:30 of 2:00");
+        assert_eq!(schedule.clock(1, 200, 210).urgency, Urgency::Alarm);
+    }
+}
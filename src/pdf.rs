@@ -0,0 +1,473 @@
+use macroquad::prelude::*;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use crate::document::clean_notes;
+use crate::slide::*;
+use crate::style::*;
+use crate::subset::*;
+use crate::wrap::*;
+
+/// where speaker notes go in the exported PDF
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NotesMode {
+    /// a page with the notes after every slide that has them
+    Pages,
+    /// a collapsed sticky note on the slide page
+    Annotations,
+}
+
+/// slides are treated as 96 dpi, so 1600x1200 becomes a 1200x900pt page
+const PT_PER_PX: f32 = 0.75;
+
+const NOTES_TITLE_SIZE: f32 = 28f32;
+const NOTES_TEXT_SIZE: f32 = 18f32;
+
+/// minimal PDF object writer, keeps byte offsets for the xref table
+struct PdfWriter {
+    buf: Vec<u8>,
+    offsets: Vec<usize>,
+}
+
+impl PdfWriter {
+    fn new() -> Self {
+        Self {
+            buf: b"%PDF-1.7\n%\xE2\xE3\xCF\xD3\n".to_vec(),
+            offsets: Vec::new(),
+        }
+    }
+
+    /// reserves an object id so it can be referenced before it is written
+    fn reserve(&mut self) -> usize {
+        self.offsets.push(0);
+        self.offsets.len()
+    }
+
+    fn object(&mut self, id: usize, body: &str) {
+        self.offsets[id - 1] = self.buf.len();
+        self.buf.extend_from_slice(format!("{} 0 obj\n{}\nendobj\n", id, body).as_bytes());
+    }
+
+    /// writes a stream, dict is the content of the dictionary without Length
+    fn stream(&mut self, id: usize, dict: &str, data: &[u8]) {
+        self.offsets[id - 1] = self.buf.len();
+        self.buf.extend_from_slice(
+            format!("{} 0 obj\n<< {} /Length {} >>\nstream\n", id, dict, data.len()).as_bytes(),
+        );
+        self.buf.extend_from_slice(data);
+        self.buf.extend_from_slice(b"\nendstream\nendobj\n");
+    }
+
+    fn deflated_stream(&mut self, id: usize, dict: &str, data: &[u8]) {
+        let compressed = miniz_oxide::deflate::compress_to_vec_zlib(data, 6);
+        self.stream(id, &format!("{} /Filter /FlateDecode", dict), &compressed);
+    }
+
+    fn finish(mut self, root: usize) -> Vec<u8> {
+        let xref = self.buf.len();
+        let mut table = format!("xref\n0 {}\n0000000000 65535 f \n", self.offsets.len() + 1);
+        for offset in &self.offsets {
+            table.push_str(&format!("{:010} 00000 n \n", offset));
+        }
+        table.push_str(&format!(
+            "trailer\n<< /Size {} /Root {} 0 R >>\nstartxref\n{}\n%%EOF\n",
+            self.offsets.len() + 1,
+            root,
+            xref
+        ));
+        self.buf.extend_from_slice(table.as_bytes());
+        self.buf
+    }
+}
+
+/// TrueType font embedded as a CID font, so text in the PDF stays searchable.
+/// Glyphs the text does not use are left out of it
+struct EmbeddedFont<'a> {
+    name: &'static str,
+    bytes: &'a [u8],
+    face: fontdue::Font,
+    /// glyph id to the character it was used for, feeds the ToUnicode map
+    used: BTreeMap<u16, char>,
+    font_id: usize,
+}
+
+impl<'a> EmbeddedFont<'a> {
+    fn new(name: &'static str, bytes: &'a [u8], writer: &mut PdfWriter) -> Result<Self, String> {
+        let face = fontdue::Font::from_bytes(bytes, fontdue::FontSettings::default())
+            .map_err(|e| format!("failed to load font for PDF: {}", e))?;
+
+        Ok(Self {
+            name,
+            bytes,
+            face,
+            used: BTreeMap::new(),
+            font_id: writer.reserve(),
+        })
+    }
+
+    /// text as a hex string of glyph ids for the Identity-H encoding
+    fn encode(&mut self, text: &str) -> String {
+        let mut hex = String::from("<");
+        for character in text.chars() {
+            let glyph = self.face.lookup_glyph_index(character);
+            self.used.entry(glyph).or_insert(character);
+            hex.push_str(&format!("{:04X}", glyph));
+        }
+        hex.push('>');
+        hex
+    }
+
+    fn write(&self, writer: &mut PdfWriter) {
+        let descendant = writer.reserve();
+        let descriptor = writer.reserve();
+        let file = writer.reserve();
+        let to_unicode = writer.reserve();
+
+        let metrics = self.face.horizontal_line_metrics(1000f32);
+        let ascent = metrics.map(|m| m.ascent).unwrap_or(800f32);
+        let descent = metrics.map(|m| m.descent).unwrap_or(-200f32);
+
+        let widths = self
+            .used
+            .keys()
+            .map(|glyph| {
+                let advance = self.face.metrics_indexed(*glyph, 1000f32).advance_width;
+                format!("{} [{:.0}]", glyph, advance)
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        writer.object(
+            self.font_id,
+            &format!(
+                "<< /Type /Font /Subtype /Type0 /BaseFont /{} /Encoding /Identity-H \
+                /DescendantFonts [{} 0 R] /ToUnicode {} 0 R >>",
+                self.name, descendant, to_unicode
+            ),
+        );
+        writer.object(
+            descendant,
+            &format!(
+                "<< /Type /Font /Subtype /CIDFontType2 /BaseFont /{} \
+                /CIDSystemInfo << /Registry (Adobe) /Ordering (Identity) /Supplement 0 >> \
+                /FontDescriptor {} 0 R /CIDToGIDMap /Identity /DW 1000 /W [{}] >>",
+                self.name, descriptor, widths
+            ),
+        );
+        writer.object(
+            descriptor,
+            &format!(
+                "<< /Type /FontDescriptor /FontName /{} /Flags 4 /FontBBox [-1000 {:.0} 2000 {:.0}] \
+                /ItalicAngle 0 /Ascent {:.0} /Descent {:.0} /CapHeight {:.0} /StemV 80 /FontFile2 {} 0 R >>",
+                self.name, descent, ascent, ascent, descent, ascent, file
+            ),
+        );
+        // only the outlines the text layer uses, a CJK font is megabytes otherwise
+        let glyphs: BTreeSet<u16> = self.used.keys().copied().collect();
+        let subset = subset_font(self.bytes, &glyphs);
+        let bytes = subset.as_deref().unwrap_or(self.bytes);
+        writer.deflated_stream(file, &format!("/Length1 {}", bytes.len()), bytes);
+
+        let mut cmap = String::from(
+            "/CIDInit /ProcSet findresource begin\n12 dict begin\nbegincmap\n\
+            /CIDSystemInfo << /Registry (Adobe) /Ordering (UCS) /Supplement 0 >> def\n\
+            /CMapName /Adobe-Identity-UCS def\n/CMapType 2 def\n\
+            1 begincodespacerange\n<0000> <FFFF>\nendcodespacerange\n",
+        );
+        let mappings: Vec<_> = self.used.iter().collect();
+        for chunk in mappings.chunks(100) {
+            cmap.push_str(&format!("{} beginbfchar\n", chunk.len()));
+            for (glyph, character) in chunk {
+                cmap.push_str(&format!("<{:04X}> {}\n", glyph, utf16_hex(&character.to_string())));
+            }
+            cmap.push_str("endbfchar\n");
+        }
+        cmap.push_str("endcmap\nCMapName currentdict /CMap defineresource pop\nend\nend\n");
+        writer.deflated_stream(to_unicode, "", cmap.as_bytes());
+    }
+}
+
+/// UTF-16BE hex string, as ToUnicode maps expect it
+fn utf16_hex(text: &str) -> String {
+    let mut hex = String::from("<");
+    for unit in text.encode_utf16() {
+        hex.push_str(&format!("{:04X}", unit));
+    }
+    hex.push('>');
+    hex
+}
+
+/// PDF text string, UTF-16BE with a byte order mark
+fn text_string(text: &str) -> String {
+    format!("<FEFF{}", &utf16_hex(text)[1..])
+}
+
+/// how the slides were laid out, so the text layer lands where the text was drawn
+pub struct PdfLayout {
+    pub virtual_screen_size: Vec2,
+    /// alignment of slides without their own
+    pub align: Align,
+    pub valign: VAlign,
+}
+
+/// writes one PDF page per slide. Pages are the rendered images with the slide text laid
+/// over them as invisible text in the embedded font, so the PDF can be searched and copied from
+pub fn write_pdf(
+    path: &Path,
+    slides: &[Slide],
+    images: &[Image],
+    layout: &PdfLayout,
+    font_bytes: &[u8],
+    mono_font_bytes: &[u8],
+    notes: Option<NotesMode>,
+) -> Result<(), String> {
+    let virtual_screen_size = &layout.virtual_screen_size;
+    let mut writer = PdfWriter::new();
+    let catalog = writer.reserve();
+    let pages = writer.reserve();
+
+    let mut font = EmbeddedFont::new("ReihaRegular", font_bytes, &mut writer)?;
+    let mut mono_font = EmbeddedFont::new("ReihaMono", mono_font_bytes, &mut writer)?;
+
+    let page_w = virtual_screen_size.x * PT_PER_PX;
+    let page_h = virtual_screen_size.y * PT_PER_PX;
+    let resources = format!(
+        "/Font << /F1 {} 0 R /F2 {} 0 R >>",
+        font.font_id, mono_font.font_id
+    );
+
+    let mut page_ids = Vec::new();
+
+    for (slide, image) in slides.iter().zip(images) {
+        let page = writer.reserve();
+        let contents = writer.reserve();
+        let xobject = writer.reserve();
+        page_ids.push(page);
+
+        let rgb: Vec<u8> = image.bytes.chunks(4).flat_map(|px| [px[0], px[1], px[2]]).collect();
+        writer.deflated_stream(
+            xobject,
+            &format!(
+                "/Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /DeviceRGB /BitsPerComponent 8",
+                image.width, image.height
+            ),
+            &rgb,
+        );
+
+        let mut content = format!("q {:.2} 0 0 {:.2} 0 0 cm /Im1 Do Q\n", page_w, page_h);
+
+        if let (Some(text), Some(font_size)) = (&slide.text, slide.font_size) {
+            let (embedded, face_name, start_pos_y) = match slide.slide_type {
+                SlideType::Code => (&mut mono_font, "F2", None),
+                SlideType::TextImage => (&mut font, "F1", Some(virtual_screen_size.y * CFACT)),
+                _ => (&mut font, "F1", None),
+            };
+
+            let (align, valign) = slide.alignment((layout.align, layout.valign));
+            // bold and italic runs are measured with the regular face, the layer only has to be close
+            let faces = FontFaces::regular(&embedded.face);
+            let origins = line_origins(&slide.lines, &faces, font_size, virtual_screen_size, start_pos_y, align, valign);
+
+            // render mode 3: text is neither filled nor stroked, only selectable
+            content.push_str(&format!("BT 3 Tr /{} {:.2} Tf\n", face_name, font_size as f32 * PT_PER_PX));
+            for (line, origin) in text.lines().zip(origins) {
+                content.push_str(&format!(
+                    "1 0 0 1 {:.2} {:.2} Tm {} Tj\n",
+                    origin.x * PT_PER_PX,
+                    page_h - origin.y * PT_PER_PX,
+                    embedded.encode(line)
+                ));
+            }
+            content.push_str("ET\n");
+        }
+
+        writer.deflated_stream(contents, "", content.as_bytes());
+
+        let comments =
+            slide.comments.as_deref().filter(|c| clean_notes(c).iter().any(|line| !line.trim().is_empty()));
+
+        let mut annots = String::new();
+        if let (Some(NotesMode::Annotations), Some(comments)) = (notes, comments) {
+            let annot = writer.reserve();
+            writer.object(
+                annot,
+                &format!(
+                    "<< /Type /Annot /Subtype /Text /Name /Comment /Open false \
+                    /Rect [{:.2} {:.2} {:.2} {:.2}] /Contents {} >>",
+                    page_w - 40f32,
+                    page_h - 40f32,
+                    page_w - 16f32,
+                    page_h - 16f32,
+                    text_string(&clean_notes(comments).join("\n"))
+                ),
+            );
+            annots = format!(" /Annots [{} 0 R]", annot);
+        }
+
+        writer.object(
+            page,
+            &format!(
+                "<< /Type /Page /Parent {} 0 R /MediaBox [0 0 {:.2} {:.2}] \
+                /Resources << {} /XObject << /Im1 {} 0 R >> >> /Contents {} 0 R{} >>",
+                pages, page_w, page_h, resources, xobject, contents, annots
+            ),
+        );
+
+        if let (Some(NotesMode::Pages), Some(comments)) = (notes, comments) {
+            let margin = page_w * 0.06;
+            let line_height = NOTES_TEXT_SIZE * 1.4;
+            let lines: Vec<String> = clean_notes(comments)
+                .iter()
+                .flat_map(|line| wrap_words(line, &font.face, NOTES_TEXT_SIZE as u16, page_w - 2f32 * margin))
+                .collect();
+            // lines that fit between the title and the bottom margin, long notes go on over more pages
+            let per_page = (((page_h - 2f32 * margin - 2f32 * NOTES_TITLE_SIZE) / line_height) as usize).max(1);
+
+            for (i, page_lines) in lines.chunks(per_page).enumerate() {
+                let notes_page = writer.reserve();
+                let notes_contents = writer.reserve();
+                page_ids.push(notes_page);
+
+                let title = match i {
+                    0 => format!("Notes: slide {}", slide.num),
+                    _ => format!("Notes: slide {}, continued", slide.num),
+                };
+                let mut y = page_h - margin - NOTES_TITLE_SIZE;
+                let mut content = format!(
+                    "BT /F1 {:.2} Tf 1 0 0 1 {:.2} {:.2} Tm {} Tj ET\n",
+                    NOTES_TITLE_SIZE,
+                    margin,
+                    y,
+                    font.encode(&title)
+                );
+                y -= NOTES_TITLE_SIZE;
+
+                content.push_str(&format!("BT /F1 {:.2} Tf\n", NOTES_TEXT_SIZE));
+                for line in page_lines {
+                    y -= line_height;
+                    content.push_str(&format!("1 0 0 1 {:.2} {:.2} Tm {} Tj\n", margin, y, font.encode(line)));
+                }
+                content.push_str("ET\n");
+
+                writer.deflated_stream(notes_contents, "", content.as_bytes());
+                writer.object(
+                    notes_page,
+                    &format!(
+                        "<< /Type /Page /Parent {} 0 R /MediaBox [0 0 {:.2} {:.2}] \
+                        /Resources << {} >> /Contents {} 0 R >>",
+                        pages, page_w, page_h, resources, notes_contents
+                    ),
+                );
+            }
+        }
+    }
+
+    font.write(&mut writer);
+    mono_font.write(&mut writer);
+
+    let kids = page_ids.iter().map(|id| format!("{} 0 R", id)).collect::<Vec<_>>().join(" ");
+    writer.object(pages, &format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids, page_ids.len()));
+    writer.object(catalog, &format!("<< /Type /Catalog /Pages {} 0 R >>", pages));
+
+    std::fs::write(path, writer.finish(catalog)).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::defaults::*;
+    use crate::utils::*;
+
+    /// object number and byte offset of every in-use entry of the xref table
+    fn xref_entries(pdf: &[u8]) -> Vec<(usize, usize)> {
+        // the header has binary bytes, offsets are counted in bytes
+        let tail = pdf.windows(10).rposition(|w| w == b"startxref\n").unwrap();
+        let start: usize = String::from_utf8_lossy(&pdf[tail + 10..]).lines().next().unwrap().parse().unwrap();
+        let text = String::from_utf8_lossy(&pdf[start..]);
+        assert!(text.starts_with("xref\n"));
+
+        text
+            .lines()
+            .skip(3)
+            .take_while(|line| !line.starts_with("trailer"))
+            .enumerate()
+            .map(|(i, line)| (i + 1, line[..10].parse().unwrap()))
+            .collect()
+    }
+
+    /// every stream of the file inflated, content streams are deflated
+    fn inflated_streams(pdf: &[u8]) -> Vec<String> {
+        let mut streams = Vec::new();
+        let mut rest = pdf;
+        while let Some(start) = rest.windows(7).position(|w| w == b"stream\n") {
+            let data = &rest[start + 7..];
+            let end = data.windows(10).position(|w| w == b"\nendstream").unwrap();
+            if let Ok(inflated) = miniz_oxide::inflate::decompress_to_vec_zlib(&data[..end]) {
+                streams.push(String::from_utf8_lossy(&inflated).into_owned());
+            }
+            rest = &data[end + 10..];
+        }
+        streams
+    }
+
+    #[test]
+    fn xref_points_at_the_objects() {
+        let mut writer = PdfWriter::new();
+        let first = writer.reserve();
+        let second = writer.reserve();
+        let third = writer.reserve();
+        // written out of order, the table still has to follow the ids
+        writer.object(third, "<< /Type /Test >>");
+        writer.deflated_stream(second, "", b"some content");
+        writer.object(first, "(first)");
+        let pdf = writer.finish(first);
+
+        let entries = xref_entries(&pdf);
+        assert_eq!(entries.len(), 3);
+        for (id, offset) in entries {
+            assert!(pdf[offset..].starts_with(format!("{} 0 obj\n", id).as_bytes()), "object {}", id);
+        }
+        assert!(String::from_utf8_lossy(&pdf).contains("trailer\n<< /Size 4 /Root 1 0 R >>"));
+    }
+
+    #[test]
+    fn note_lines_wrap_at_words() {
+        let mut writer = PdfWriter::new();
+        let font = EmbeddedFont::new("Mono", MONO_FONT, &mut writer).unwrap();
+        // the mono font is half an em wide, 5pt a character at size 10
+        assert_eq!(font.face.measure_width("aaaa", 10), 20f32);
+
+        assert_eq!(wrap_words("aaa bbb ccc", &font.face, 10, 36f32), ["aaa bbb", "ccc"]);
+        assert_eq!(wrap_words("aaa bbb ccc", &font.face, 10, 60f32), ["aaa bbb ccc"]);
+    }
+
+    #[test]
+    fn long_notes_go_on_over_more_pages() {
+        let path = std::env::temp_dir().join(format!("reiha-notes-{}.pdf", std::process::id()));
+        let mut slide = Slide::plain(1, "hello");
+        slide.comments = Some((1..=60).map(|i| format!("note {}", i)).collect::<Vec<_>>().join("\n"));
+        let slides = [slide, Slide::plain(2, "no notes")];
+        let images = vec![Image { bytes: vec![0; 16 * 12 * 4], width: 16, height: 12 }; 2];
+        let layout = PdfLayout { virtual_screen_size: vec2(1600f32, 1200f32), align: Align::Block, valign: VAlign::Middle };
+
+        write_pdf(&path, &slides, &images, &layout, MONO_FONT, MONO_FONT, Some(NotesMode::Pages)).unwrap();
+        let pdf = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // 900pt high pages hold 27 note lines: two slides and three notes pages for the first
+        assert!(String::from_utf8_lossy(&pdf).contains("/Count 5"));
+        for (id, offset) in xref_entries(&pdf) {
+            assert!(pdf[offset..].starts_with(format!("{} 0 obj\n", id).as_bytes()), "object {}", id);
+        }
+
+        let mut writer = PdfWriter::new();
+        let mut font = EmbeddedFont::new("Mono", MONO_FONT, &mut writer).unwrap();
+        let title = font.encode("Notes: slide 1");
+        let continued = font.encode("Notes: slide 1, continued");
+        let streams = inflated_streams(&pdf);
+        let count = |text: &str| streams.iter().filter(|stream| stream.contains(&format!("{} Tj", text))).count();
+        assert_eq!(count(&title), 1);
+        assert_eq!(count(&continued), 2);
+        assert_eq!(count(&font.encode("note 60")), 1);
+    }
+}
@@ -0,0 +1,429 @@
+use macroquad::prelude::*;
+use macroquad_canvas::Canvas2D;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{Receiver, TryRecvError, channel};
+
+use crate::cli::*;
+use crate::diagnostic::*;
+use crate::document::clean_notes;
+use crate::keys::*;
+use crate::pacing::*;
+use crate::rehearsal::*;
+use crate::scene::*;
+use crate::settings::*;
+use crate::slide::*;
+use crate::utils::*;
+use crate::wrap::*;
+
+/// where speaker notes, the next slide and the clock are shown while presenting
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PresenterMode {
+    /// only in the terminal
+    Off,
+    /// in the presentation window instead of the slide, for rehearsing on a single screen
+    Split,
+    /// in a second window, the first one shows only the slide
+    Window,
+}
+
+/// set for the reiha process that draws the presenter window.
+/// It gets the state of the audience window on stdin and sends its key presses back on stdout
+pub const PRESENTER_WINDOW_VAR: &str = "REIHA_PRESENTER_WINDOW";
+
+const BACKGROUND: Color = Color::from_hex(0x111111);
+const TEXT: Color = Color::from_hex(0xdddddd);
+const DIM: Color = Color::from_hex(0x777777);
+const FRAME: Color = Color::from_hex(0x444444);
+
+/// what the presenter view shows, the audience window decides it
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PresenterState {
+    pub slide: usize,
+    pub step: usize,
+    /// seconds since the presentation started
+    pub elapsed: u64,
+    /// seconds on the current slide
+    pub on_slide: u64,
+    pub paused: bool,
+}
+
+/// current slide, a thumbnail of the next one, the notes of the current one, clock and slide counter
+pub struct PresenterView {
+    next_canvas: Canvas2D,
+}
+
+impl PresenterView {
+    pub fn new(virtual_screen_size: Vec2) -> Self {
+        Self { next_canvas: Canvas2D::new(virtual_screen_size.x, virtual_screen_size.y) }
+    }
+
+    /// draws over the whole window, `current` holds the current slide as the audience sees it
+    pub fn draw(
+        &self,
+        scene: &Scene,
+        slides: &[Slide],
+        state: PresenterState,
+        clock: &Clock,
+        comparison: &Rehearsed,
+        current: &Canvas2D,
+    ) {
+        let (width, height) = (screen_width(), screen_height());
+        let margin = height / 40f32;
+        let label_size = (height / 36f32).max(12f32) as u16;
+        let left_width = width * 0.6 - margin * 1.5;
+        let right_x = width * 0.6 + margin * 0.5;
+        let right_width = width * 0.4 - margin * 1.5;
+
+        let has_next = state.slide + 1 < slides.len();
+        if has_next {
+            set_camera(&self.next_canvas.camera);
+            scene.draw(slides, state.slide + 1, 0);
+            set_default_camera();
+        }
+
+        clear_background(BACKGROUND);
+
+        // current slide, with the clock, the plan, the rehearsed times and the counter under it
+        let slide_area = Rect::new(margin, margin, left_width, height * 0.75);
+        let slide_rect = draw_slide(current, slide_area);
+        let counter = slides.get(state.slide).map(|slide| slide.counter(slides.len(), state.step)).unwrap_or_default();
+        let clock_size = (height / 12f32) as u16;
+        let clock_y = slide_rect.bottom() + margin + clock_size as f32;
+        let clock_color = if clock.paused { DIM } else { TEXT };
+        draw_label(&clock.time_text(), margin, clock_y, &scene.font, clock_size, clock_color);
+        let mut line_y = clock_y + margin + label_size as f32;
+        let plan = clock.plan_text();
+        if !plan.is_empty() {
+            draw_label(&plan, margin, line_y, &scene.font, label_size, urgency_color(clock.urgency));
+            line_y += margin / 2f32 + label_size as f32;
+        }
+        if let Some(rehearsed) = comparison.text(clock.elapsed, clock.on_slide) {
+            let color = if comparison.is_long(clock.on_slide) { urgency_color(Urgency::Warning) } else { DIM };
+            draw_label(&rehearsed, margin, line_y, &scene.font, label_size, color);
+            line_y += margin / 2f32 + label_size as f32;
+        }
+        draw_label(&counter, margin, line_y, &scene.font, label_size, DIM);
+
+        // next slide
+        draw_label("next", right_x, margin + label_size as f32, &scene.font, label_size, DIM);
+        let next_area = Rect::new(right_x, margin * 1.5 + label_size as f32, right_width, height * 0.35);
+        let next_rect = if has_next {
+            draw_slide(&self.next_canvas, next_area)
+        } else {
+            let rect = fit_rect(scene.virtual_screen_size, next_area);
+            draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, 2f32, FRAME);
+            draw_label("end of the presentation", rect.x + margin, rect.center().y, &scene.font, label_size, DIM);
+            rect
+        };
+
+        // notes of the current slide fill the rest of the right side
+        let notes_y = next_rect.bottom() + margin * 2f32;
+        draw_label("notes", right_x, notes_y, &scene.font, label_size, DIM);
+        let notes =
+            slides.get(state.slide).and_then(|slide| slide.comments.as_deref()).map(clean_notes).unwrap_or_default();
+        let notes_area = Rect::new(right_x, notes_y + margin, right_width, height - notes_y - margin * 2f32);
+        draw_notes(&notes, notes_area, &scene.font, (height / 28f32) as u16);
+    }
+}
+
+/// largest rect with the aspect of `size` at the top left of the area
+fn fit_rect(size: Vec2, area: Rect) -> Rect {
+    let scale = (area.w / size.x).min(area.h / size.y);
+    Rect::new(area.x, area.y, size.x * scale, size.y * scale)
+}
+
+fn draw_slide(canvas: &Canvas2D, area: Rect) -> Rect {
+    let texture = canvas.get_texture();
+    let rect = fit_rect(texture.size(), area);
+    draw_texture_ex(texture, rect.x, rect.y, WHITE, DrawTextureParams {
+        dest_size: Some(rect.size()),
+        ..Default::default()
+    });
+    draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, 2f32, FRAME);
+    rect
+}
+
+/// text at its baseline
+fn draw_label(text: &str, x: f32, y: f32, font: &Font, font_size: u16, color: Color) {
+    draw_text_ex(text, x, y, TextParams { font: Some(font), font_size, color, ..Default::default() });
+}
+
+/// notes wrapped to the area, the font gets smaller until they fit or it is hard to read
+fn draw_notes(notes: &[String], area: Rect, font: &Font, max_font_size: u16) {
+    let min_font_size = 14u16.min(max_font_size);
+    let mut font_size = max_font_size;
+    let lines = loop {
+        let lines: Vec<String> = notes
+            .iter()
+            .flat_map(|line| wrap_words(line, font, font_size, area.w))
+            .collect();
+        if lines.len() as f32 * font_size as f32 * 1.25 <= area.h || font_size <= min_font_size {
+            break lines;
+        }
+        font_size = ((font_size as f32 * 0.9) as u16).max(min_font_size);
+    };
+
+    let line_height = font_size as f32 * 1.25;
+    for (i, line) in lines.iter().enumerate() {
+        let y = area.y + font_size as f32 + i as f32 * line_height;
+        if y > area.bottom() {
+            break;
+        }
+        draw_label(line, area.x, y, font, font_size, TEXT);
+    }
+}
+
+/// what the audience window tells the presenter window, a line each.
+/// Key presses go back the other way as actions
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Message {
+    Show(PresenterState),
+    /// the presentation was reloaded
+    Reload,
+    /// an action that changes how slides look
+    Action(Action),
+}
+
+impl Message {
+    fn to_line(self) -> String {
+        match self {
+            Message::Show(state) => format!(
+                "show {} {} {} {} {}",
+                state.slide, state.step, state.elapsed, state.on_slide, state.paused as u8
+            ),
+            Message::Reload => "reload".to_string(),
+            Message::Action(action) => {
+                ACTIONS.iter().find(|(a, _)| *a == action).map(|(_, name)| name.to_string()).unwrap_or_default()
+            }
+        }
+    }
+
+    /// None for lines that are not a message, they are skipped
+    fn parse(line: &str) -> Option<Self> {
+        match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+            ["show", slide, step, elapsed, on_slide, paused] => Some(Message::Show(PresenterState {
+                slide: slide.parse().ok()?,
+                step: step.parse().ok()?,
+                elapsed: elapsed.parse().ok()?,
+                on_slide: on_slide.parse().ok()?,
+                paused: match *paused {
+                    "0" => false,
+                    "1" => true,
+                    _ => return None,
+                },
+            })),
+            ["reload"] => Some(Message::Reload),
+            [name] => parse_action(name).map(Message::Action),
+            _ => None,
+        }
+    }
+}
+
+/// lines read on a thread of their own, so the render loop never waits for them
+fn read_lines<R: Read + Send + 'static>(reader: R) -> Receiver<String> {
+    let (sender, receiver) = channel();
+    std::thread::spawn(move || {
+        for line in BufReader::new(reader).lines() {
+            let Ok(line) = line else { break };
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+/// the presenter window as seen from the audience window: a second reiha process
+pub struct PresenterWindow {
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+    /// last state sent, it is only sent again once it changes
+    sent: Option<PresenterState>,
+}
+
+impl PresenterWindow {
+    /// starts reiha again with the same arguments, it opens the presenter window
+    pub fn spawn() -> Result<Self, String> {
+        let exe = std::env::current_exe().map_err(|e| format!("failed to start the presenter window: {}", e))?;
+        let mut child = Command::new(exe)
+            .args(std::env::args_os().skip(1))
+            .env(PRESENTER_WINDOW_VAR, "1")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("failed to start the presenter window: {}", e))?;
+
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let lines = read_lines(child.stdout.take().expect("stdout is piped"));
+        Ok(Self { child, stdin, lines, sent: None })
+    }
+
+    fn send(&mut self, message: &str) {
+        // a closed window shows up in actions(), nothing to do here
+        let _ = writeln!(self.stdin, "{}", message).and_then(|_| self.stdin.flush());
+    }
+
+    pub fn show(&mut self, state: PresenterState) {
+        if self.sent != Some(state) {
+            self.send(&Message::Show(state).to_line());
+            self.sent = Some(state);
+        }
+    }
+
+    /// the presentation was reloaded, the presenter window loads it again too
+    pub fn reload(&mut self) {
+        self.send(&Message::Reload.to_line());
+        self.sent = None;
+    }
+
+    /// passes on an action that changes how slides look, like swapping the theme
+    pub fn forward(&mut self, action: Action) {
+        self.send(&Message::Action(action).to_line());
+    }
+
+    /// actions pressed in the presenter window since the last call, None once it was closed
+    pub fn actions(&mut self) -> Option<Vec<Action>> {
+        let mut actions = Vec::new();
+        loop {
+            match self.lines.try_recv() {
+                Ok(line) => {
+                    if let Some(Message::Action(action)) = Message::parse(&line) {
+                        actions.push(action);
+                    }
+                }
+                Err(TryRecvError::Empty) => return Some(actions),
+                Err(TryRecvError::Disconnected) => return None,
+            }
+        }
+    }
+}
+
+impl Drop for PresenterWindow {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// settings, scene and slides the way the audience window loads them
+fn load(input_path: &str, options: &[ParsedOption]) -> Result<(Settings, Scene, Vec<Slide>), String> {
+    let settings = Settings::load(Some(input_path), options)?;
+    let scene = Scene::try_from_settings(&settings)?;
+    let (slides, diagnostics) = parse(input_path, &scene.virtual_screen_size, &scene.faces(), &scene.mono_font, scene.wrap);
+    if has_errors(&diagnostics) {
+        // the audience window reports them
+        return Err(format!("could not load `{}`", input_path));
+    }
+    Ok((settings, scene, slides))
+}
+
+/// times of every slide in the saved rehearsals, the audience window reports a broken file
+fn compare_rehearsals(input_path: &str, slides: &[Slide]) -> Vec<Rehearsed> {
+    Rehearsals::load(input_path).map(|rehearsals| rehearsals.compare(slides)).unwrap_or_default()
+}
+
+/// main loop of the presenter window process, it ends with the audience window
+pub async fn run_window(input_path: String, options: Vec<ParsedOption>) {
+    let Ok((mut settings, mut scene, mut slides)) = load(&input_path, &options) else {
+        std::process::exit(1);
+    };
+    set_default_filter_mode(settings.filtering.value);
+
+    let mut current_canvas = Canvas2D::new(scene.virtual_screen_size.x, scene.virtual_screen_size.y);
+    let mut view = PresenterView::new(scene.virtual_screen_size);
+    let mut state = PresenterState::default();
+    let mut schedule = Schedule::new(&slides, &settings);
+    let mut comparisons = compare_rehearsals(&input_path, &slides);
+    let mut is_fullscreen = false;
+    let messages = read_lines(std::io::stdin());
+    let mut out = std::io::stdout();
+
+    loop {
+        loop {
+            let message = match messages.try_recv() {
+                Ok(message) => message,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return,
+            };
+            match Message::parse(&message) {
+                Some(Message::Show(shown)) => state = shown,
+                Some(Message::Reload) => {
+                    // keeps what it has when the deck does not load, like the audience window
+                    if let Ok(loaded) = load(&input_path, &options) {
+                        (settings, scene, slides) = loaded;
+                        set_default_filter_mode(settings.filtering.value);
+                        current_canvas = Canvas2D::new(scene.virtual_screen_size.x, scene.virtual_screen_size.y);
+                        view = PresenterView::new(scene.virtual_screen_size);
+                        schedule = Schedule::new(&slides, &settings);
+                        comparisons = compare_rehearsals(&input_path, &slides);
+                    }
+                }
+                Some(Message::Action(Action::SwapTheme)) => scene.swap_theme(),
+                Some(Message::Action(Action::Numbering)) => scene.numbering = !scene.numbering,
+                _ => {}
+            }
+        }
+
+        set_camera(&current_canvas.camera);
+        scene.draw(&slides, state.slide, state.step);
+        set_default_camera();
+        let clock = Clock { paused: state.paused, ..schedule.clock(state.slide, state.elapsed, state.on_slide) };
+        let comparison = comparisons.get(state.slide).copied().unwrap_or_default();
+        view.draw(&scene, &slides, state, &clock, &comparison, &current_canvas);
+
+        if settings.keys.pressed(Action::Fullscreen) {
+            is_fullscreen = !is_fullscreen;
+            set_fullscreen(is_fullscreen);
+        }
+
+        // everything else is up to the audience window
+        for (action, _) in ACTIONS {
+            if !matches!(action, Action::Fullscreen | Action::Presenter) && settings.keys.pressed(*action) {
+                let _ = writeln!(out, "{}", Message::Action(*action).to_line()).and_then(|_| out.flush());
+            }
+        }
+
+        next_frame().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_read_back_as_sent() {
+        let state = PresenterState { slide: 3, step: 1, elapsed: 125, on_slide: 40, paused: true };
+        for message in [
+            Message::Show(state),
+            Message::Show(PresenterState::default()),
+            Message::Reload,
+            Message::Action(Action::SwapTheme),
+            Message::Action(Action::Numbering),
+            Message::Action(Action::Next),
+        ] {
+            assert_eq!(Message::parse(&message.to_line()), Some(message));
+        }
+        assert_eq!(Message::Show(state).to_line(), "show 3 1 125 40 1");
+        assert_eq!(Message::parse("  reload \r"), Some(Message::Reload));
+    }
+
+    #[test]
+    fn malformed_messages_are_skipped() {
+        for line in [
+            "",
+            "show",
+            "show 3 1 125 40",
+            "show 3 1 125 40 1 7",
+            "show -3 1 125 40 1",
+            "show 3 x 125 40 1",
+            "show 3 1 125 40 yes",
+            "reload now",
+            "dance",
+        ] {
+            assert_eq!(Message::parse(line), None, "{:?}", line);
+        }
+    }
+}
@@ -0,0 +1,483 @@
+use macroquad::prelude::*;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use crate::defaults::*;
+use crate::diagnostic::*;
+use crate::document::*;
+use crate::markup::*;
+use crate::settings::*;
+use crate::slide::*;
+use crate::style::*;
+use crate::theming::*;
+use crate::utils::*;
+
+/// metrics and coverage bitmap of a rasterized glyph
+type Glyph = (fontdue::Metrics, Vec<u8>);
+
+/// a font drawn on the CPU, glyphs are rasterized once per size
+pub struct RasterFont {
+    font: fontdue::Font,
+    glyphs: RefCell<HashMap<(char, u16), Glyph>>,
+}
+
+impl RasterFont {
+    /// loads a font from path if given, embedded fallback otherwise
+    pub fn load(path: Option<&str>, fallback: &[u8]) -> Result<Self, String> {
+        let data = match path {
+            Some(path) => std::fs::read(path).map_err(|e| format!("failed to read font `{}`: {}", path, e))?,
+            None => fallback.to_vec(),
+        };
+        let font = fontdue::Font::from_bytes(data, fontdue::FontSettings::default()).map_err(|e| match path {
+            Some(path) => format!("failed to load font `{}`: {}", path, e),
+            None => format!("failed to load embedded font: {}", e),
+        })?;
+        Ok(Self { font, glyphs: RefCell::new(HashMap::new()) })
+    }
+
+    /// calls f with the metrics and coverage bitmap of a glyph
+    fn with_glyph<T>(&self, character: char, size: u16, f: impl FnOnce(&fontdue::Metrics, &[u8]) -> T) -> T {
+        let mut glyphs = self.glyphs.borrow_mut();
+        let (metrics, bitmap) = glyphs
+            .entry((character, size))
+            .or_insert_with(|| self.font.rasterize(character, size as f32));
+        f(metrics, bitmap)
+    }
+
+    /// width and height of a text like measure_text gives them
+    fn dimensions(&self, text: &str, size: u16) -> Vec2 {
+        let mut width = 0f32;
+        let mut min_y = f32::MAX;
+        let mut max_y = f32::MIN;
+        for character in text.chars() {
+            self.with_glyph(character, size, |metrics, _| {
+                width += metrics.advance_width;
+                min_y = min_y.min(metrics.ymin as f32);
+                max_y = max_y.max((metrics.height as i32 + metrics.ymin) as f32);
+            });
+        }
+        if min_y > max_y { vec2(width, 0f32) } else { vec2(width, max_y - min_y) }
+    }
+}
+
+impl MeasureText for RasterFont {
+    fn measure_width(&self, text: &str, font_size: u16) -> f32 {
+        self.font.measure_width(text, font_size)
+    }
+}
+
+/// rgba pixels drawn on the CPU, export uses it when there is no display to open a window on
+pub struct Raster {
+    width: usize,
+    height: usize,
+    bytes: Vec<u8>,
+}
+
+impl Raster {
+    /// an opaque canvas filled with the color
+    pub fn new(size: &Vec2, color: Color) -> Self {
+        let (width, height) = (size.x as usize, size.y as usize);
+        let pixel: [u8; 4] = Color { a: 1f32, ..color }.into();
+        Self { width, height, bytes: pixel.repeat(width * height) }
+    }
+
+    /// mixes the color into a pixel, coverage is how much of the pixel it covers
+    fn blend(&mut self, x: i32, y: i32, color: Color, coverage: f32) {
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            return;
+        }
+        let alpha = (color.a * coverage).clamp(0f32, 1f32);
+        let i = (y as usize * self.width + x as usize) * 4;
+        for (channel, value) in [color.r, color.g, color.b].into_iter().enumerate() {
+            let old = self.bytes[i + channel] as f32;
+            self.bytes[i + channel] = (value * 255f32 * alpha + old * (1f32 - alpha)).round() as u8;
+        }
+    }
+
+    pub fn fill_rect(&mut self, x: f32, y: f32, w: f32, h: f32, color: Color) {
+        for py in y.round() as i32..(y + h).round() as i32 {
+            for px in x.round() as i32..(x + w).round() as i32 {
+                self.blend(px, py, color, 1f32);
+            }
+        }
+    }
+
+    /// draws an image scaled by the factor and centered in an area at the top left of the canvas,
+    /// like draw_img_scaled_and_centered
+    pub fn draw_image(&mut self, image: &Image, scale: f32, area: &Vec2, filter: FilterMode) {
+        let Some(source) = image::RgbaImage::from_raw(image.width as u32, image.height as u32, image.bytes.clone())
+        else {
+            return;
+        };
+        let width = (image.width as f32 * scale).round().max(1f32) as u32;
+        let height = (image.height as f32 * scale).round().max(1f32) as u32;
+        let filter = match filter {
+            FilterMode::Linear => image::imageops::FilterType::Triangle,
+            FilterMode::Nearest => image::imageops::FilterType::Nearest,
+        };
+        let scaled = image::imageops::resize(&source, width, height, filter);
+
+        let left = ((area.x - width as f32) / 2f32).round() as i32;
+        let top = ((area.y - height as f32) / 2f32).round() as i32;
+        for (x, y, pixel) in scaled.enumerate_pixels() {
+            let [r, g, b, a] = pixel.0;
+            self.blend(left + x as i32, top + y as i32, Color::from_rgba(r, g, b, 255), a as f32 / 255f32);
+        }
+    }
+
+    /// draws text from the baseline position like draw_text_ex, slant shears it around the baseline
+    pub fn draw_text(&mut self, text: &str, position: Vec2, font: &RasterFont, size: u16, color: Color, slant: f32) {
+        let mut advance = 0f32;
+        for character in text.chars() {
+            font.with_glyph(character, size, |metrics, bitmap| {
+                let left = position.x + advance + metrics.xmin as f32;
+                let top = position.y - metrics.height as f32 - metrics.ymin as f32;
+                for (i, coverage) in bitmap.iter().enumerate().filter(|(_, c)| **c > 0) {
+                    let y = top + (i / metrics.width) as f32;
+                    let x = left + (i % metrics.width) as f32 + slant * (position.y - y);
+                    self.blend(x.round() as i32, y.round() as i32, color, *coverage as f32 / 255f32);
+                }
+                advance += metrics.advance_width;
+            });
+        }
+    }
+
+    /// draws a line of runs from the baseline position like draw_runs
+    pub fn draw_runs(
+        &mut self,
+        runs: &[Run],
+        position: Vec2,
+        faces: &FontFaces<RasterFont>,
+        size: u16,
+        palette: &Palette,
+    ) {
+        let mut x = position.x;
+        for run in runs {
+            let face = faces.face(run);
+            let width = run_width(run, &run.text, faces, size);
+
+            if run.highlight {
+                self.fill_rect(x, position.y - size as f32 * 0.85, width, size as f32, HIGHLIGHT_COLOR);
+            }
+
+            let slant = if face.faux_italic { FAUX_ITALIC_SLANT } else { 0f32 };
+            let color = run_color(run, palette);
+            self.draw_text(&run.text, vec2(x, position.y), face.font, size, color, slant);
+            if face.faux_bold {
+                let bold_position = vec2(x + faux_bold_offset(size), position.y);
+                self.draw_text(&run.text, bold_position, face.font, size, color, slant);
+            }
+
+            x += width;
+        }
+    }
+
+    pub fn into_image(self) -> Image {
+        Image { bytes: self.bytes, width: self.width as u16, height: self.height as u16 }
+    }
+}
+
+/// decoded image of a slide background directive, drawn instead of the scene one
+pub enum RasterBackground {
+    Hidden,
+    Image { image: Image, mode: Option<BackgroundMode> },
+}
+
+/// a laid out slide with the images it draws kept decoded, textures need a window
+pub struct RasterSlide {
+    pub slide: Slide,
+    pub image: Option<Image>,
+    pub background: Option<RasterBackground>,
+}
+
+/// what Scene is to drawing in a window: fonts, background and numbering, loaded without one
+pub struct RasterScene {
+    pub theme: Theme,
+    pub font: RasterFont,
+    pub mono_font: RasterFont,
+    pub bold_font: Option<RasterFont>,
+    pub italic_font: Option<RasterFont>,
+    pub virtual_screen_size: Vec2,
+    pub bg_image: Option<Image>,
+    pub bg_mode: BackgroundMode,
+    pub bg_filter: FilterMode,
+    pub filter: FilterMode,
+    pub align: Align,
+    pub valign: VAlign,
+    pub wrap: bool,
+    pub numbering: bool,
+    pub numbering_anchor: NumberingAnchor,
+}
+
+impl RasterScene {
+    pub fn from_settings(settings: &Settings) -> Result<Self, String> {
+        let style_font =
+            |path: &Option<String>| path.as_deref().map(|path| RasterFont::load(Some(path), &[])).transpose();
+        let bg_image = match &settings.bg_image_path.value {
+            Some(path) => {
+                Some(decode_image(path).map_err(|e| format!("failed to load background image `{}`: {}", path, e))?)
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            theme: settings.theme.value,
+            font: RasterFont::load(settings.font_path.value.as_deref(), DEFAULT_FONT)?,
+            mono_font: RasterFont::load(settings.mono_font_path.value.as_deref(), MONO_FONT)?,
+            bold_font: style_font(&settings.bold_font_path.value)?,
+            italic_font: style_font(&settings.italic_font_path.value)?,
+            virtual_screen_size: settings.virtual_resolution.value,
+            bg_image,
+            bg_mode: settings.bg_mode.value,
+            bg_filter: if settings.bg_filter.value { FilterMode::Linear } else { FilterMode::Nearest },
+            filter: settings.filtering.value,
+            align: settings.text_align.value,
+            valign: settings.text_valign.value,
+            wrap: settings.wrap.value,
+            numbering: settings.numbering.value,
+            numbering_anchor: settings.numbering_anchor.value,
+        })
+    }
+
+    pub fn faces(&self) -> FontFaces<'_, RasterFont> {
+        FontFaces {
+            regular: &self.font,
+            bold: self.bold_font.as_ref(),
+            italic: self.italic_font.as_ref(),
+        }
+    }
+
+    /// lays out a deck like utils::layout, images stay decoded
+    pub fn layout(&self, deck: &Deck) -> Result<Vec<RasterSlide>, Vec<Diagnostic>> {
+        let mut slides = Vec::new();
+        let mut errors = Vec::new();
+        let faces = self.faces();
+
+        for spec in &deck.slides {
+            let image = match &spec.image {
+                Some(img_path) => match decode_image(img_path) {
+                    Ok(image) => Some(image),
+                    Err(e) => {
+                        errors.push(image_diagnostic(deck, spec, img_path, &e));
+                        continue;
+                    }
+                },
+                None => None,
+            };
+
+            let background = match &spec.directives.background {
+                None => None,
+                Some(BackgroundOverride::Hidden) => Some(RasterBackground::Hidden),
+                Some(BackgroundOverride::Image { path, mode, line }) => match decode_image(path) {
+                    Ok(image) => Some(RasterBackground::Image { image, mode: *mode }),
+                    Err(e) => {
+                        errors.push(image_diagnostic_at(deck, *line, spec.num, path, &e));
+                        continue;
+                    }
+                },
+            };
+
+            let wrap = spec.directives.wrap.unwrap_or(self.wrap);
+            let style = directive_style(&spec.directives);
+            let slide = Slide::new(spec, None, style, wrap, &self.virtual_screen_size, &faces, &self.mono_font);
+            slides.push(RasterSlide { slide, image, background });
+        }
+
+        if errors.is_empty() { Ok(slides) } else { Err(errors) }
+    }
+
+    /// draws a slide at the given step like Scene::draw
+    pub fn draw(&self, slide: &RasterSlide, index: usize, step: usize) -> Image {
+        let size = &self.virtual_screen_size;
+        let style = &slide.slide.style;
+        let theme = Theme {
+            background_color: style.bg_color.unwrap_or(self.theme.background_color),
+            font_color: style.font_color.unwrap_or(self.theme.font_color),
+        };
+        let mut raster = Raster::new(size, theme.background_color);
+
+        match &slide.background {
+            None => {
+                if let Some(image) = &self.bg_image {
+                    raster.draw_image(image, background_fit(image, self.bg_mode, size), size, self.bg_filter);
+                }
+            }
+            Some(RasterBackground::Hidden) => {}
+            Some(RasterBackground::Image { image, mode }) => {
+                let scale = background_fit(image, mode.unwrap_or(self.bg_mode), size);
+                raster.draw_image(image, scale, size, self.bg_filter);
+            }
+        }
+
+        let image_area = match slide.slide.slide_type {
+            SlideType::TextImage => vec2(size.x, size.y * CFACT),
+            _ => *size,
+        };
+        if let Some(image) = &slide.image {
+            raster.draw_image(image, background_fit(image, BackgroundMode::Fit, &image_area), &image_area, self.filter);
+        }
+
+        let palette = theme.palette();
+        let text = &slide.slide;
+        let mono_faces = FontFaces::regular(&self.mono_font);
+        let faces = match text.slide_type {
+            SlideType::Code => mono_faces,
+            _ => self.faces(),
+        };
+        if let Some(font_size) = text.font_size {
+            let (align, valign) = text.alignment((self.align, self.valign));
+            let start_pos_y = (text.slide_type == SlideType::TextImage).then_some(size.y * CFACT);
+            let origins = line_origins(&text.lines, &faces, font_size, size, start_pos_y, align, valign);
+            let dimmed = palette.dimmed();
+
+            for (i, (runs, position)) in text.lines.iter().zip(origins).enumerate() {
+                if !text.is_revealed(step, i) {
+                    continue;
+                }
+                let palette = if text.focus.is_focused(step, i + 1) { &palette } else { &dimmed };
+                raster.draw_runs(runs, position, &faces, font_size, palette);
+            }
+        }
+
+        if style.numbering.unwrap_or(self.numbering) {
+            let number = (index + 1).to_string();
+            let number_size = numbering_size(size);
+            let dimensions = self.font.dimensions(&number, number_size);
+            let position = numbering_position(self.numbering_anchor, size)
+                + numbering_offset(&self.numbering_anchor, dimensions.x, dimensions.y);
+            raster.draw_text(&number, position, &self.font, number_size, theme.font_color, 0f32);
+        }
+
+        raster.into_image()
+    }
+}
+
+/// scale that fits or fills an area with an image
+fn background_fit(image: &Image, mode: BackgroundMode, area: &Vec2) -> f32 {
+    let scale_x = area.x / image.width as f32;
+    let scale_y = area.y / image.height as f32;
+
+    match mode {
+        BackgroundMode::Fill => scale_x.max(scale_y),
+        BackgroundMode::Fit => scale_x.min(scale_y),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixel(image: &Image, x: usize, y: usize) -> [u8; 4] {
+        let i = (y * image.width as usize + x) * 4;
+        [image.bytes[i], image.bytes[i + 1], image.bytes[i + 2], image.bytes[i + 3]]
+    }
+
+    /// whether any pixel in the rectangle differs from the color
+    fn drawn(image: &Image, (x0, y0): (usize, usize), (x1, y1): (usize, usize), color: [u8; 4]) -> bool {
+        (y0..y1).any(|y| (x0..x1).any(|x| pixel(image, x, y) != color))
+    }
+
+    const BLACK_PIXEL: [u8; 4] = [0, 0, 0, 255];
+
+    #[test]
+    fn canvas_starts_opaque_in_the_background_color() {
+        let image = Raster::new(&vec2(4f32, 3f32), Color::new(1f32, 0f32, 0f32, 0.5)).into_image();
+        assert_eq!((image.width, image.height), (4, 3));
+        assert_eq!(pixel(&image, 3, 2), [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn rectangles_blend_with_what_is_below() {
+        let mut raster = Raster::new(&vec2(4f32, 4f32), BLACK);
+        raster.fill_rect(1f32, 1f32, 2f32, 2f32, Color::new(1f32, 1f32, 1f32, 0.5));
+        let image = raster.into_image();
+        assert_eq!(pixel(&image, 1, 1), [128, 128, 128, 255]);
+        assert_eq!(pixel(&image, 2, 2), [128, 128, 128, 255]);
+        assert_eq!(pixel(&image, 3, 3), BLACK_PIXEL);
+    }
+
+    #[test]
+    fn images_are_scaled_and_centered() {
+        let red = Image { bytes: [255, 0, 0, 255].repeat(2), width: 2, height: 1 };
+        let mut raster = Raster::new(&vec2(8f32, 8f32), BLACK);
+        raster.draw_image(&red, 2f32, &vec2(8f32, 8f32), FilterMode::Nearest);
+        let image = raster.into_image();
+
+        // 4x2 in the middle of 8x8
+        assert_eq!(pixel(&image, 2, 3), [255, 0, 0, 255]);
+        assert_eq!(pixel(&image, 5, 4), [255, 0, 0, 255]);
+        assert_eq!(pixel(&image, 1, 3), BLACK_PIXEL);
+        assert_eq!(pixel(&image, 6, 4), BLACK_PIXEL);
+        assert_eq!(pixel(&image, 2, 2), BLACK_PIXEL);
+        assert_eq!(pixel(&image, 2, 5), BLACK_PIXEL);
+    }
+
+    #[test]
+    fn image_fit_and_fill() {
+        let wide = Image { bytes: vec![0; 4 * 4 * 2], width: 4, height: 2 };
+        let area = vec2(8f32, 8f32);
+        assert_eq!(background_fit(&wide, BackgroundMode::Fit, &area), 2f32);
+        assert_eq!(background_fit(&wide, BackgroundMode::Fill, &area), 4f32);
+    }
+
+    #[test]
+    fn text_sits_on_the_baseline() {
+        let font = RasterFont::load(None, MONO_FONT).unwrap();
+        let mut raster = Raster::new(&vec2(100f32, 100f32), BLACK);
+        raster.draw_text("H", vec2(10f32, 60f32), &font, 40, WHITE, 0f32);
+        let image = raster.into_image();
+
+        assert!(drawn(&image, (10, 30), (40, 60), BLACK_PIXEL));
+        assert!(!drawn(&image, (0, 61), (100, 100), BLACK_PIXEL));
+        assert!(!drawn(&image, (0, 0), (100, 20), BLACK_PIXEL));
+    }
+
+    #[test]
+    fn faux_italic_leans_right() {
+        let font = RasterFont::load(None, MONO_FONT).unwrap();
+        let draw = |slant: f32| {
+            let mut raster = Raster::new(&vec2(100f32, 100f32), BLACK);
+            raster.draw_text("l", vec2(30f32, 80f32), &font, 60, WHITE, slant);
+            raster.into_image()
+        };
+        let leftmost_at = |image: &Image, y: usize| (0..100).find(|x| pixel(image, *x, y) != BLACK_PIXEL);
+
+        let upright = draw(0f32);
+        let italic = draw(FAUX_ITALIC_SLANT);
+        // near the top of the stem the slant moves the glyph by about 0.2 * 40
+        assert!(leftmost_at(&italic, 40).unwrap() >= leftmost_at(&upright, 40).unwrap() + 6);
+    }
+
+    #[test]
+    fn decks_render_at_the_virtual_resolution() {
+        let mut settings = Settings::default();
+        settings.virtual_resolution.value = vec2(160f32, 120f32);
+        settings.numbering.value = true;
+        settings.numbering_anchor.value = NumberingAnchor::TopRight;
+        let scene = RasterScene::from_settings(&settings).unwrap();
+
+        let (deck, diagnostics) = parse_deck("hello\n\n% bg-color: ff0000\n% numbering: off\n\\\n");
+        assert!(!has_errors(&diagnostics));
+        let slides = scene.layout(&deck).unwrap();
+        assert_eq!(slides.len(), 2);
+
+        let background: [u8; 4] = Color { a: 1f32, ..scene.theme.background_color }.into();
+        let text = scene.draw(&slides[0], 0, 0);
+        assert_eq!((text.width, text.height), (160, 120));
+        assert!(drawn(&text, (0, 30), (160, 90), background));
+        // the number is in the top right corner
+        assert!(drawn(&text, (140, 0), (160, 10), background));
+        assert!(!drawn(&text, (0, 100), (160, 120), background));
+
+        let empty = scene.draw(&slides[1], 1, 0);
+        assert!(!drawn(&empty, (0, 0), (160, 120), [255, 0, 0, 255]));
+    }
+
+    #[test]
+    fn missing_images_are_diagnostics() {
+        let scene = RasterScene::from_settings(&Settings::default()).unwrap();
+        let (deck, _) = parse_deck("@missing.png\n\n% background: also-missing.png\ntext\n");
+        let errors = scene.layout(&deck).err().unwrap();
+        assert_eq!(errors.len(), 2);
+    }
+}
@@ -0,0 +1,267 @@
+use serde_json::{Value, json};
+use std::path::{Path, PathBuf};
+
+use crate::slide::*;
+use crate::utils::*;
+
+/// how long a slide took in the saved rehearsals
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Rehearsed {
+    /// seconds in the last rehearsal
+    pub last: Option<u64>,
+    /// seconds on average over every rehearsal that had the slide
+    pub average: Option<u64>,
+    /// seconds the last rehearsal was in when it got to the slide
+    pub last_start: Option<u64>,
+}
+
+impl Rehearsed {
+    /// "last 0:52  avg 0:47  run +1:10", where run is how far this run is behind the last one
+    pub fn text(&self, elapsed: u64, on_slide: u64) -> Option<String> {
+        let mut parts = Vec::new();
+        if let Some(last) = self.last {
+            parts.push(format!("last {}", format_time(last)));
+        }
+        if let Some(average) = self.average {
+            parts.push(format!("avg {}", format_time(average)));
+        }
+        if let Some(start) = self.last_start {
+            let diff = elapsed.saturating_sub(on_slide) as i64 - start as i64;
+            let sign = if diff < 0 { '-' } else { '+' };
+            parts.push(format!("run {}{}", sign, format_time(diff.unsigned_abs())));
+        }
+        (!parts.is_empty()).then(|| parts.join("  "))
+    }
+
+    /// the slide is on longer than it took the last time, or on average without a last time
+    pub fn is_long(&self, on_slide: u64) -> bool {
+        self.last.or(self.average).is_some_and(|time| on_slide > time)
+    }
+}
+
+/// one saved run, (slide number, content hash, seconds) for every slide that was shown
+struct Run {
+    slides: Vec<(u32, u64, u64)>,
+}
+
+impl Run {
+    /// number and seconds the slide with this content had, the entry nearest to the number wins
+    /// when it is there twice
+    fn find(&self, num: u32, hash: u64) -> Option<(u32, u64)> {
+        self.slides
+            .iter()
+            .filter(|(_, h, _)| *h == hash)
+            .min_by_key(|(n, _, _)| n.abs_diff(num))
+            .map(|(n, _, seconds)| (*n, *seconds))
+    }
+
+    /// seconds spent on the slides before this number
+    fn before(&self, num: u32) -> u64 {
+        self.slides.iter().filter(|(n, _, _)| *n < num).map(|(_, _, seconds)| seconds).sum()
+    }
+}
+
+/// per-slide times of earlier runs, kept in a JSON file next to the presentation
+pub struct Rehearsals {
+    path: PathBuf,
+    /// oldest first
+    runs: Vec<Run>,
+}
+
+impl Rehearsals {
+    /// no file yet means no rehearsals
+    pub fn load(input_path: &str) -> Result<Self, String> {
+        let path = rehearsals_path(input_path);
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self { path, runs: Vec::new() }),
+            Err(e) => return Err(format!("failed to read `{}`: {}", path.display(), e)),
+        };
+        let runs = parse_runs(&content).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(Self { path, runs })
+    }
+
+    /// how long every slide took in the rehearsals, matched by content so moved slides keep their times
+    pub fn compare(&self, slides: &[Slide]) -> Vec<Rehearsed> {
+        slides
+            .iter()
+            .map(|slide| {
+                let hash = slide_hash(slide);
+                let times: Vec<u64> =
+                    self.runs.iter().filter_map(|run| run.find(slide.num, hash)).map(|(_, seconds)| seconds).collect();
+                let found = self.runs.last().and_then(|run| Some((run, run.find(slide.num, hash)?)));
+                let last = found.map(|(_, (_, seconds))| seconds);
+                let last_start = found.map(|(run, (num, _))| run.before(num));
+                let average = (!times.is_empty()).then(|| times.iter().sum::<u64>() / times.len() as u64);
+                Rehearsed { last, average, last_start }
+            })
+            .collect()
+    }
+
+    /// adds this run and writes the file, slides that were never shown are left out.
+    /// false when no slide was shown long enough to count
+    pub fn save(&mut self, slides: &[Slide], dwell: &[u64]) -> Result<bool, String> {
+        let run = Run {
+            slides: slides
+                .iter()
+                .zip(dwell)
+                .filter(|(_, seconds)| **seconds > 0)
+                .map(|(slide, seconds)| (slide.num, slide_hash(slide), *seconds))
+                .collect(),
+        };
+        if run.slides.is_empty() {
+            return Ok(false);
+        }
+        self.runs.push(run);
+
+        let runs: Vec<Value> = self
+            .runs
+            .iter()
+            .map(|run| {
+                let slides: Vec<Value> = run
+                    .slides
+                    .iter()
+                    .map(|(num, hash, seconds)| json!({ "slide": num, "hash": format!("{:016x}", hash), "seconds": seconds }))
+                    .collect();
+                json!({ "slides": slides })
+            })
+            .collect();
+        let content = serde_json::to_string_pretty(&json!({ "runs": runs })).expect("plain values serialize");
+        std::fs::write(&self.path, content + "\n").map_err(|e| format!("failed to write `{}`: {}", self.path.display(), e))?;
+        Ok(true)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// `talk.md` keeps its rehearsals in `talk.rehearsals.json`
+pub fn rehearsals_path(input_path: &str) -> PathBuf {
+    Path::new(input_path).with_extension("rehearsals.json")
+}
+
+fn parse_runs(content: &str) -> Result<Vec<Run>, String> {
+    let root: Value = serde_json::from_str(content).map_err(|e| e.to_string())?;
+    let runs = root.get("runs").and_then(Value::as_array).ok_or("expected a `runs` array")?;
+
+    runs.iter()
+        .map(|run| {
+            let slides = run.get("slides").and_then(Value::as_array).ok_or("expected a `slides` array in every run")?;
+            let slides = slides
+                .iter()
+                .map(|entry| {
+                    let num = entry.get("slide").and_then(Value::as_u64).and_then(|num| u32::try_from(num).ok());
+                    let hash = entry.get("hash").and_then(Value::as_str).and_then(|h| u64::from_str_radix(h, 16).ok());
+                    let seconds = entry.get("seconds").and_then(Value::as_u64);
+                    match (num, hash, seconds) {
+                        (Some(num), Some(hash), Some(seconds)) => Ok((num, hash, seconds)),
+                        _ => Err(format!("invalid slide entry {}", entry)),
+                    }
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            Ok(Run { slides })
+        })
+        .collect()
+}
+
+/// FNV-1a of what the slide shows, so a slide keeps its times while it does not change
+pub fn slide_hash(slide: &Slide) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    let content = [slide.text.as_deref(), Some("\0"), slide.image_path.as_deref()];
+    for byte in content.into_iter().flatten().flat_map(str::bytes) {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash_follows_what_the_slide_shows() {
+        let mut slide = Slide::plain(1, "hello");
+        let hash = slide_hash(&slide);
+        slide.num = 7;
+        slide.comments = Some("| notes".to_string());
+        assert_eq!(slide_hash(&slide), hash);
+
+        assert_ne!(slide_hash(&Slide::plain(1, "hello!")), hash);
+        slide.image_path = Some("a.png".to_string());
+        assert_ne!(slide_hash(&slide), hash);
+        // FNV-1a of "hello\0"
+        assert_eq!(hash, 0xa9bc8acca21f39b1);
+    }
+
+    #[test]
+    fn runs_are_saved_and_compared() {
+        let dir = std::env::temp_dir().join(format!("reiha-rehearsals-{}", std::process::id()));
+        // runs left over from an earlier test would count too
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("talk.rh");
+        let input_path = input.to_str().unwrap();
+
+        let slides = [Slide::plain(1, "a"), Slide::plain(2, "b"), Slide::plain(3, "c")];
+        let mut rehearsals = Rehearsals::load(input_path).unwrap();
+        assert_eq!(rehearsals.path(), dir.join("talk.rehearsals.json"));
+        assert_eq!(rehearsals.save(&slides, &[0, 0, 0]), Ok(false));
+        assert_eq!(rehearsals.save(&slides, &[10, 0, 30]), Ok(true));
+        assert_eq!(Rehearsals::load(input_path).unwrap().save(&slides, &[20, 5, 40]), Ok(true));
+
+        // "c" moved to the front, "d" is new
+        let moved = [Slide::plain(1, "c"), Slide::plain(2, "a"), Slide::plain(3, "d")];
+        let comparisons = Rehearsals::load(input_path).unwrap().compare(&moved);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            comparisons,
+            [
+                Rehearsed { last: Some(40), average: Some(35), last_start: Some(25) },
+                Rehearsed { last: Some(20), average: Some(15), last_start: Some(0) },
+                Rehearsed::default(),
+            ]
+        );
+    }
+
+    #[test]
+    fn broken_files_are_errors() {
+        assert!(parse_runs("not json").is_err());
+        assert_eq!(parse_runs("{}").err().unwrap(), "expected a `runs` array");
+        assert_eq!(
+            parse_runs(r#"{"runs": [{"slides": [{"slide": 1, "hash": "zz", "seconds": 3}]}]}"#).err().unwrap(),
+            r#"invalid slide entry {"hash":"zz","seconds":3,"slide":1}"#
+        );
+        // slide numbers are u32, larger ones are not cut down to fit
+        assert!(parse_runs(r#"{"runs": [{"slides": [{"slide": 4294967296, "hash": "ff", "seconds": 3}]}]}"#).is_err());
+        let runs = parse_runs(r#"{"runs": [{"slides": [{"slide": 2, "hash": "00ff", "seconds": 3}]}]}"#).unwrap();
+        assert_eq!(runs[0].slides, [(2, 0xff, 3)]);
+    }
+
+    #[test]
+    fn only_a_missing_file_is_no_rehearsals() {
+        let dir = std::env::temp_dir().join(format!("reiha-rehearsals-unreadable-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("talk.rh");
+        let input_path = input.to_str().unwrap();
+
+        assert!(Rehearsals::load(input_path).unwrap().runs.is_empty());
+        // a file that is there but can not be read is not silently replaced on save
+        std::fs::create_dir(dir.join("talk.rehearsals.json")).unwrap();
+        let error = Rehearsals::load(input_path).err();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(error.unwrap().starts_with("failed to read `"));
+    }
+
+    #[test]
+    fn comparison_text() {
+        let rehearsed = Rehearsed { last: Some(52), average: Some(47), last_start: Some(60) };
+        assert_eq!(rehearsed.text(140, 10).as_deref(), Some("last 0:52  avg 0:47  run +1:10"));
+        assert_eq!(rehearsed.text(40, 0).as_deref(), Some("last 0:52  avg 0:47  run -0:20"));
+        assert!(rehearsed.is_long(53) && !rehearsed.is_long(52));
+        assert_eq!(Rehearsed::default().text(10, 10), None);
+    }
+}
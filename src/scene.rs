@@ -0,0 +1,208 @@
+use macroquad::prelude::*;
+
+use crate::defaults::*;
+use crate::settings::*;
+use crate::slide::*;
+use crate::style::*;
+use crate::theming::*;
+use crate::utils::*;
+
+/// everything that is drawn around a slide: theme, fonts, background and numbering
+pub struct Scene {
+    pub theme: Theme,
+    pub font: Font,
+    pub mono_font: Font,
+    /// None draws faux bold or italic from the regular font
+    pub bold_font: Option<Font>,
+    pub italic_font: Option<Font>,
+    pub virtual_screen_size: Vec2,
+    pub bg_image: Option<Texture2D>,
+    pub bg_scale: f32,
+    /// default for slide backgrounds that do not say fit or fill
+    pub bg_mode: BackgroundMode,
+    /// text alignment of slides without their own
+    pub align: Align,
+    pub valign: VAlign,
+    /// reflow text of slides without their own wrap directive
+    pub wrap: bool,
+    /// transition into slides without their own, only used while presenting
+    pub transition: Transition,
+    pub transition_duration: f32,
+    pub numbering: bool,
+    pub numbering_anchor: NumberingAnchor,
+    pub numbering_position: Vec2,
+    pub numbering_size: u16,
+}
+
+impl Scene {
+    /// loads fonts and background image, needs a window. Exits when they can not be loaded
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self::try_from_settings(settings).unwrap_or_else(|e| {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        })
+    }
+
+    pub fn try_from_settings(settings: &Settings) -> Result<Self, String> {
+        let theme = settings.theme.value;
+        let font: Font = try_load_font(settings.font_path.value.as_deref(), DEFAULT_FONT)?;
+        let mono_font: Font = try_load_font(settings.mono_font_path.value.as_deref(), MONO_FONT)?;
+        let bold_font = try_load_style_font(settings.bold_font_path.value.as_deref())?;
+        let italic_font = try_load_style_font(settings.italic_font_path.value.as_deref())?;
+        let virtual_screen_size = settings.virtual_resolution.value;
+        let numbering = settings.numbering.value;
+        let numbering_anchor = settings.numbering_anchor.value;
+
+        let (bg_image, bg_scale) = load_background(settings, &virtual_screen_size)?;
+
+        let numbering_position = numbering_position(numbering_anchor, &virtual_screen_size);
+        let numbering_size = numbering_size(&virtual_screen_size);
+
+        Ok(Self {
+            theme,
+            font,
+            mono_font,
+            bold_font,
+            italic_font,
+            virtual_screen_size,
+            bg_image,
+            bg_scale,
+            bg_mode: settings.bg_mode.value,
+            align: settings.text_align.value,
+            valign: settings.text_valign.value,
+            wrap: settings.wrap.value,
+            transition: settings.transition.value,
+            transition_duration: settings.transition_duration.value,
+            numbering,
+            numbering_anchor,
+            numbering_position,
+            numbering_size,
+        })
+    }
+
+    /// loads fonts and background image again, keeps everything as is on failure
+    pub fn reload_assets(&mut self, settings: &Settings) -> Result<(), String> {
+        let font = try_load_font(settings.font_path.value.as_deref(), DEFAULT_FONT)?;
+        let mono_font = try_load_font(settings.mono_font_path.value.as_deref(), MONO_FONT)?;
+        let bold_font = try_load_style_font(settings.bold_font_path.value.as_deref())?;
+        let italic_font = try_load_style_font(settings.italic_font_path.value.as_deref())?;
+        let (bg_image, bg_scale) = load_background(settings, &self.virtual_screen_size)?;
+
+        self.font = font;
+        self.mono_font = mono_font;
+        self.bold_font = bold_font;
+        self.italic_font = italic_font;
+        self.bg_image = bg_image;
+        self.bg_scale = bg_scale;
+        Ok(())
+    }
+
+    /// background and font color trade places
+    pub fn swap_theme(&mut self) {
+        self.theme = Theme {
+            background_color: self.theme.font_color,
+            font_color: self.theme.background_color,
+        };
+    }
+
+    pub fn faces(&self) -> FontFaces<'_, Font> {
+        FontFaces {
+            regular: &self.font,
+            bold: self.bold_font.as_ref(),
+            italic: self.italic_font.as_ref(),
+        }
+    }
+
+    /// alignment a slide is drawn with, its directives win over the settings
+    pub fn text_alignment(&self, slide: &Slide) -> (Align, VAlign) {
+        slide.alignment((self.align, self.valign))
+    }
+
+    /// transition into a slide and its duration, its directive wins over the settings
+    pub fn slide_transition(&self, slide: &Slide) -> (Transition, f32) {
+        (
+            slide.style.transition.unwrap_or(self.transition),
+            slide.style.transition_duration.unwrap_or(self.transition_duration),
+        )
+    }
+
+    /// draws background, slide at the given step and its number into the current camera,
+    /// directives of the slide take precedence over the scene
+    pub fn draw(&self, slides: &[Slide], index: usize, step: usize) {
+        let slide = slides.get(index);
+        let style = slide.map(|slide| &slide.style);
+        let theme = Theme {
+            background_color: style.and_then(|s| s.bg_color).unwrap_or(self.theme.background_color),
+            font_color: style.and_then(|s| s.font_color).unwrap_or(self.theme.font_color),
+        };
+
+        clear_background(theme.background_color);
+
+        match style.and_then(|s| s.background.as_ref()) {
+            None => {
+                if let Some(bg_image) = &self.bg_image {
+                    draw_img_background(bg_image, &self.bg_scale, &self.virtual_screen_size);
+                }
+            }
+            Some(SlideBackground::Hidden) => {}
+            Some(SlideBackground::Image { texture, mode }) => {
+                let scale = background_scale(texture, mode.unwrap_or(self.bg_mode), &self.virtual_screen_size);
+                draw_img_background(texture, &scale, &self.virtual_screen_size);
+            }
+        }
+
+        if let Some(slide) = slide {
+            slide.draw(
+                &self.faces(),
+                &self.mono_font,
+                &theme.palette(),
+                &self.virtual_screen_size,
+                self.text_alignment(slide),
+                step,
+            );
+            if slide.style.numbering.unwrap_or(self.numbering) {
+                draw_numbering(
+                    &index,
+                    &self.font,
+                    &self.numbering_position,
+                    &self.numbering_size,
+                    &theme,
+                    &self.numbering_anchor,
+                );
+            }
+        }
+    }
+}
+
+/// bold or italic face, None when it is not configured
+fn try_load_style_font(path: Option<&str>) -> Result<Option<Font>, String> {
+    path.map(|path| try_load_font(Some(path), &[])).transpose()
+}
+
+/// background texture and the scale that fits or fills the virtual screen with it
+fn load_background(settings: &Settings, virtual_screen_size: &Vec2) -> Result<(Option<Texture2D>, f32), String> {
+    let Some(path) = &settings.bg_image_path.value else {
+        return Ok((None, 1f32));
+    };
+    let bg_filter: bool = settings.bg_filter.value;
+    let bg_mode: BackgroundMode = settings.bg_mode.value;
+
+    let image = decode_image(path).map_err(|e| format!("failed to load background image `{}`: {}", path, e))?;
+    let texture = Texture2D::from_image(&image);
+    if bg_filter { texture.set_filter(FilterMode::Linear) }
+    else { texture.set_filter(FilterMode::Nearest) }
+
+    let bg_scale = background_scale(&texture, bg_mode, virtual_screen_size);
+    Ok((Some(texture), bg_scale))
+}
+
+/// scale that fits or fills the virtual screen with a texture
+fn background_scale(texture: &Texture2D, mode: BackgroundMode, virtual_screen_size: &Vec2) -> f32 {
+    let scale_x = virtual_screen_size.x / texture.width();
+    let scale_y = virtual_screen_size.y / texture.height();
+
+    match mode {
+        BackgroundMode::Fill => scale_x.max(scale_y),
+        BackgroundMode::Fit  => scale_x.min(scale_y)
+    }
+}
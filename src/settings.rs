@@ -0,0 +1,646 @@
+use macroquad::prelude::*;
+use std::path::{Path, PathBuf};
+
+use crate::cli::*;
+use crate::config_handle::*;
+use crate::defaults::*;
+use crate::document::*;
+use crate::export::ExportFormat;
+use crate::keys::*;
+use crate::pdf::NotesMode;
+use crate::presenter::PresenterMode;
+use crate::slide::NumberingAnchor;
+use crate::theming::*;
+use crate::utils::*;
+
+/// where an effective setting came from, later layers win
+#[derive(Clone, Debug, PartialEq)]
+pub enum Source {
+    Default,
+    GlobalConfig(PathBuf),
+    DeckConfig(PathBuf),
+    /// presentation file and the line of the entry
+    FrontMatter(PathBuf, usize),
+    Env(String),
+    Cli(String),
+}
+
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Source::Default => write!(f, "default"),
+            Source::GlobalConfig(path) => write!(f, "global config {}", path.display()),
+            Source::DeckConfig(path) => write!(f, "deck config {}", path.display()),
+            Source::FrontMatter(path, line) => write!(f, "front matter {}:{}", path.display(), line),
+            Source::Env(var) => write!(f, "environment {}", var),
+            Source::Cli(flag) => write!(f, "command line {}", flag),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Setting<T> {
+    pub value: T,
+    pub source: Source,
+}
+
+impl<T> Setting<T> {
+    fn new(value: T) -> Self {
+        Self { value, source: Source::Default }
+    }
+
+    fn set(&mut self, value: T, source: &Source) {
+        self.value = value;
+        self.source = source.clone();
+    }
+}
+
+/// everything reiha can be configured with, merged from all layers
+pub struct Settings {
+    pub theme: Setting<Theme>,
+    pub filtering: Setting<FilterMode>,
+    pub font_path: Setting<Option<String>>,
+    pub mono_font_path: Setting<Option<String>>,
+    /// None fakes bold and italic from the regular font
+    pub bold_font_path: Setting<Option<String>>,
+    pub italic_font_path: Setting<Option<String>>,
+    pub virtual_resolution: Setting<Vec2>,
+    pub numbering: Setting<bool>,
+    pub numbering_anchor: Setting<NumberingAnchor>,
+    pub bg_image_path: Setting<Option<String>>,
+    pub bg_filter: Setting<bool>,
+    pub bg_mode: Setting<BackgroundMode>,
+    pub text_align: Setting<Align>,
+    pub text_valign: Setting<VAlign>,
+    pub wrap: Setting<bool>,
+    pub transition: Setting<Transition>,
+    /// seconds
+    pub transition_duration: Setting<f32>,
+    /// talk length in seconds, None when there is no plan to pace against
+    pub duration: Setting<Option<u64>>,
+    /// seconds behind the plan for the pace to turn yellow and red
+    pub pace_warning: Setting<u64>,
+    pub pace_alarm: Setting<u64>,
+    pub progress_bar: Setting<bool>,
+    /// file for the timing of every slide, None prints it on exit
+    pub timing_report: Setting<Option<String>>,
+    /// adds the slide times of this run to the rehearsals of the presentation
+    pub rehearse: Setting<bool>,
+    pub preview: Setting<bool>,
+    pub presenter: Setting<PresenterMode>,
+    /// None means 1/30 of the virtual height
+    pub min_font_size: Setting<Option<u16>>,
+    pub deny_warnings: Setting<bool>,
+    pub export_format: Setting<ExportFormat>,
+    pub export_out: Setting<Option<String>>,
+    pub export_notes: Setting<Option<NotesMode>>,
+    /// only config.toml can rebind keys
+    pub keys: KeyMap,
+    /// layer that bound each action, actions not listed keep their default keys
+    pub key_sources: Vec<(Action, Source)>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            theme: Setting::new(DARK_THEME),
+            filtering: Setting::new(FilterMode::Nearest),
+            font_path: Setting::new(None),
+            mono_font_path: Setting::new(None),
+            bold_font_path: Setting::new(None),
+            italic_font_path: Setting::new(None),
+            virtual_resolution: Setting::new(VIRTUAL_SCREEN_SIZE),
+            numbering: Setting::new(false),
+            numbering_anchor: Setting::new(NumberingAnchor::BottomLeft),
+            bg_image_path: Setting::new(None),
+            bg_filter: Setting::new(true),
+            bg_mode: Setting::new(BackgroundMode::Fill),
+            text_align: Setting::new(Align::default()),
+            text_valign: Setting::new(VAlign::default()),
+            wrap: Setting::new(false),
+            transition: Setting::new(Transition::default()),
+            transition_duration: Setting::new(0.4),
+            duration: Setting::new(None),
+            pace_warning: Setting::new(30),
+            pace_alarm: Setting::new(120),
+            progress_bar: Setting::new(false),
+            timing_report: Setting::new(None),
+            rehearse: Setting::new(false),
+            preview: Setting::new(false),
+            presenter: Setting::new(PresenterMode::Off),
+            min_font_size: Setting::new(None),
+            deny_warnings: Setting::new(false),
+            export_format: Setting::new(ExportFormat::Png),
+            export_out: Setting::new(None),
+            export_notes: Setting::new(None),
+            keys: KeyMap::default(),
+            key_sources: Vec::new(),
+        }
+    }
+}
+
+/// options of the config command itself, like --explain, do not change settings
+fn is_setting(opt: &Opt) -> bool {
+    opt.commands != [Command::Config]
+}
+
+/// name of the environment variable for an option, --numbering-anchor is REIHA_NUMBERING_ANCHOR
+pub fn env_var(opt: &Opt) -> String {
+    format!("REIHA_{}", opt.long.trim_start_matches('-').replace('-', "_").to_uppercase())
+}
+
+impl Settings {
+    /// defaults, global config, deck config, front matter, environment and command line, in that order
+    pub fn load(input_path: Option<&str>, cli_options: &[ParsedOption]) -> Result<Self, String> {
+        Self::load_layers(input_path, cli_options, &global_config_paths(), |var| std::env::var(var).ok())
+    }
+
+    /// load with the global config files and the environment passed in, env gives the value of a variable
+    fn load_layers(
+        input_path: Option<&str>,
+        cli_options: &[ParsedOption],
+        global_paths: &[PathBuf],
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, String> {
+        let mut settings = Settings::default();
+
+        for path in global_paths {
+            settings.apply_file(path, Source::GlobalConfig(path.clone()), None)?;
+        }
+
+        if let Some(input_path) = input_path {
+            let deck_dir = Path::new(input_path).parent().unwrap_or(Path::new(""));
+            for name in DECK_CONFIG_NAMES {
+                let path = deck_dir.join(name);
+                settings.apply_file(&path, Source::DeckConfig(path.clone()), Some(deck_dir))?;
+            }
+
+            // problems with the presentation itself are reported when it is parsed
+            if let Ok(source) = std::fs::read_to_string(input_path) {
+                let (entries, _, _) = parse_front_matter(&source);
+                settings.apply_front_matter(input_path, &entries)?;
+            }
+        }
+
+        for opt in OPTIONS.iter().filter(|opt| is_setting(opt)) {
+            let var = env_var(opt);
+            let Some(value) = env(&var) else {
+                continue;
+            };
+            // the whole variable is one value, so paths may have spaces
+            settings
+                .apply(opt, &[value], &Source::Env(var.clone()), None)
+                .map_err(|e| format!("{}: {}", var, e))?;
+        }
+
+        for (opt, values) in cli_options.iter().filter(|(opt, _)| is_setting(opt)) {
+            settings.apply(opt, values, &Source::Cli(opt.long.to_string()), None)?;
+        }
+
+        Ok(settings)
+    }
+
+    fn apply_file(&mut self, path: &Path, source: Source, base_dir: Option<&Path>) -> Result<(), String> {
+        let Some(config) = read_config_file(path)? else {
+            return Ok(());
+        };
+
+        for (opt, values) in config.options {
+            self.apply(opt, &values, &source, base_dir)
+                .map_err(|e| format!("{}: {}", path.display(), e))?;
+        }
+
+        for (action, triggers) in config.keys {
+            self.keys.bind(action, triggers);
+            self.key_sources.retain(|(a, _)| *a != action);
+            self.key_sources.push((action, source.clone()));
+        }
+
+        Ok(())
+    }
+
+    fn apply_front_matter(&mut self, input_path: &str, entries: &[FrontMatterEntry]) -> Result<(), String> {
+        let deck_dir = Path::new(input_path).parent().unwrap_or(Path::new(""));
+
+        for entry in entries {
+            let source = Source::FrontMatter(PathBuf::from(input_path), entry.line);
+            front_matter_option(entry)
+                .and_then(|(long, values)| {
+                    let opt = find_option(&long).expect("front matter keys map onto known options");
+                    self.apply(opt, &values, &source, Some(deck_dir))
+                })
+                .map_err(|e| format!("{}:{}: {}", input_path, entry.line, e))?;
+        }
+
+        Ok(())
+    }
+
+    /// the only place an option value is parsed, whatever layer it comes from.
+    /// Relative paths are resolved against base_dir when given
+    pub fn apply(&mut self, opt: &Opt, values: &[String], source: &Source, base_dir: Option<&Path>) -> Result<(), String> {
+        let value = values.first().map(String::as_str).unwrap_or("");
+        let path = || match base_dir {
+            Some(dir) if Path::new(value).is_relative() => Some(dir.join(value).to_string_lossy().to_string()),
+            _ => Some(value.to_string()),
+        };
+
+        match opt.long {
+            "--theme" => self.theme.set(parse_theme(value)?, source),
+            "--linear" => {
+                let linear = parse_flag(values)?;
+                self.filtering.set(if linear { FilterMode::Linear } else { FilterMode::Nearest }, source)
+            }
+            "--font" => self.font_path.set(path(), source),
+            "--mono-font" => self.mono_font_path.set(path(), source),
+            "--bold-font" => self.bold_font_path.set(path(), source),
+            "--italic-font" => self.italic_font_path.set(path(), source),
+            "--resolution" => self.virtual_resolution.set(parse_resolution(value)?, source),
+            "--numbering" => self.numbering.set(parse_flag(values)?, source),
+            "--numbering-anchor" => self.numbering_anchor.set(parse_numbering_anchor(value)?, source),
+            "--background" => {
+                self.bg_image_path.set(path(), source);
+                for extra in values.iter().skip(1) {
+                    match extra.as_str() {
+                        "l" | "linear" => self.bg_filter.set(true, source),
+                        "n" | "nearest" => self.bg_filter.set(false, source),
+                        "fit" => self.bg_mode.set(BackgroundMode::Fit, source),
+                        "fill" => self.bg_mode.set(BackgroundMode::Fill, source),
+                        _ => return Err(format!("invalid background option '{}'", extra)),
+                    }
+                }
+            }
+            "--align" => self.text_align.set(parse_align(value)?, source),
+            "--valign" => self.text_valign.set(parse_valign(value)?, source),
+            "--wrap" => self.wrap.set(parse_flag(values)?, source),
+            "--transition" => self.transition.set(parse_transition(value)?, source),
+            "--transition-duration" => self.transition_duration.set(parse_transition_duration(value)?, source),
+            "--duration" => self.duration.set(Some(parse_time_span(value, 60)?), source),
+            "--pace-warning" => self.pace_warning.set(parse_time_span(value, 1)?, source),
+            "--pace-alarm" => self.pace_alarm.set(parse_time_span(value, 1)?, source),
+            "--progress-bar" => self.progress_bar.set(parse_flag(values)?, source),
+            "--timing-report" => self.timing_report.set(path(), source),
+            "--rehearse" => self.rehearse.set(parse_flag(values)?, source),
+            "--preview" => self.preview.set(parse_flag(values)?, source),
+            "--presenter" => self.presenter.set(parse_presenter_mode(value)?, source),
+            "--min-font-size" => {
+                let size = value
+                    .parse::<u16>()
+                    .map_err(|_| format!("--min-font-size expects a size in pixels, got '{}'", value))?;
+                self.min_font_size.set(Some(size), source)
+            }
+            "--deny-warnings" => self.deny_warnings.set(parse_flag(values)?, source),
+            "--format" => self.export_format.set(parse_export_format(value)?, source),
+            "--out" => self.export_out.set(Some(value.to_string()), source),
+            "--notes" => self.export_notes.set(Some(parse_notes_mode(value)?), source),
+            _ => return Err(format!("option '{}' can not be configured", opt.long)),
+        }
+
+        Ok(())
+    }
+
+    /// effective value of every setting as (name, value, source)
+    pub fn describe(&self) -> Vec<(String, String, &Source)> {
+        let path = |p: &Option<String>| p.clone().unwrap_or_else(|| "embedded".to_string());
+        let none = |p: &Option<String>| p.clone().unwrap_or_else(|| "none".to_string());
+        let faux = |p: &Option<String>| p.clone().unwrap_or_else(|| "faux".to_string());
+
+        let rows: Vec<(&str, String, &Source)> = vec![
+            ("theme", theme_name(&self.theme.value), &self.theme.source),
+            (
+                "filtering",
+                match self.filtering.value {
+                    FilterMode::Linear => "linear",
+                    FilterMode::Nearest => "nearest",
+                }
+                .to_string(),
+                &self.filtering.source,
+            ),
+            ("font", path(&self.font_path.value), &self.font_path.source),
+            ("mono-font", path(&self.mono_font_path.value), &self.mono_font_path.source),
+            ("bold-font", faux(&self.bold_font_path.value), &self.bold_font_path.source),
+            ("italic-font", faux(&self.italic_font_path.value), &self.italic_font_path.source),
+            (
+                "resolution",
+                format!("{}x{}", self.virtual_resolution.value.x, self.virtual_resolution.value.y),
+                &self.virtual_resolution.source,
+            ),
+            ("numbering", self.numbering.value.to_string(), &self.numbering.source),
+            (
+                "numbering-anchor",
+                numbering_anchor_name(self.numbering_anchor.value).to_string(),
+                &self.numbering_anchor.source,
+            ),
+            ("background", none(&self.bg_image_path.value), &self.bg_image_path.source),
+            (
+                "background-filter",
+                if self.bg_filter.value { "linear" } else { "nearest" }.to_string(),
+                &self.bg_filter.source,
+            ),
+            (
+                "background-mode",
+                match self.bg_mode.value {
+                    BackgroundMode::Fill => "fill",
+                    BackgroundMode::Fit => "fit",
+                }
+                .to_string(),
+                &self.bg_mode.source,
+            ),
+            ("align", align_name(self.text_align.value).to_string(), &self.text_align.source),
+            ("valign", valign_name(self.text_valign.value).to_string(), &self.text_valign.source),
+            ("wrap", self.wrap.value.to_string(), &self.wrap.source),
+            ("transition", transition_name(self.transition.value).to_string(), &self.transition.source),
+            (
+                "transition-duration",
+                format!("{}s", self.transition_duration.value),
+                &self.transition_duration.source,
+            ),
+            (
+                "duration",
+                self.duration.value.map_or_else(|| "none".to_string(), format_time),
+                &self.duration.source,
+            ),
+            ("pace-warning", format_time(self.pace_warning.value), &self.pace_warning.source),
+            ("pace-alarm", format_time(self.pace_alarm.value), &self.pace_alarm.source),
+            ("progress-bar", self.progress_bar.value.to_string(), &self.progress_bar.source),
+            ("timing-report", none(&self.timing_report.value), &self.timing_report.source),
+            ("rehearse", self.rehearse.value.to_string(), &self.rehearse.source),
+            ("preview", self.preview.value.to_string(), &self.preview.source),
+            (
+                "presenter",
+                match self.presenter.value {
+                    PresenterMode::Off => "off",
+                    PresenterMode::Split => "split",
+                    PresenterMode::Window => "window",
+                }
+                .to_string(),
+                &self.presenter.source,
+            ),
+            (
+                "min-font-size",
+                match self.min_font_size.value {
+                    Some(size) => size.to_string(),
+                    None => format!("{} (1/30 of the height)", (self.virtual_resolution.value.y / 30f32) as u16),
+                },
+                &self.min_font_size.source,
+            ),
+            ("deny-warnings", self.deny_warnings.value.to_string(), &self.deny_warnings.source),
+            (
+                "format",
+                match self.export_format.value {
+                    ExportFormat::Png => "png",
+                    ExportFormat::Pdf => "pdf",
+                    ExportFormat::Html => "html",
+                }
+                .to_string(),
+                &self.export_format.source,
+            ),
+            ("out", none(&self.export_out.value), &self.export_out.source),
+            (
+                "notes",
+                match self.export_notes.value {
+                    Some(NotesMode::Pages) => "pages",
+                    Some(NotesMode::Annotations) => "annotations",
+                    None => "none",
+                }
+                .to_string(),
+                &self.export_notes.source,
+            ),
+        ];
+
+        let mut rows: Vec<(String, String, &Source)> =
+            rows.into_iter().map(|(name, value, source)| (name.to_string(), value, source)).collect();
+        for (action, name) in ACTIONS {
+            let keys: Vec<String> = self.keys.triggers(*action).iter().map(|t| trigger_name(*t)).collect();
+            let source = self
+                .key_sources
+                .iter()
+                .find(|(a, _)| a == action)
+                .map(|(_, source)| source)
+                .unwrap_or(&Source::Default);
+            rows.push((format!("keys.{}", name), keys.join(" "), source));
+        }
+        rows
+    }
+}
+
+/// prints effective settings, with explain also the layer that set each of them
+pub fn print_settings(settings: &Settings, explain: bool) {
+    let rows = settings.describe();
+    let name_width = rows.iter().map(|(name, _, _)| name.len()).max().unwrap_or(0);
+    let value_width = rows.iter().map(|(_, value, _)| value.chars().count()).max().unwrap_or(0);
+
+    for (name, value, source) in rows {
+        if explain {
+            println!("{:name_width$}  {:value_width$}  {}", name, value, source);
+        } else {
+            println!("{:name_width$}  {}", name, value);
+        }
+    }
+}
+
+/// option and values for a front matter entry, `filtering: linear` is --linear true
+fn front_matter_option(entry: &FrontMatterEntry) -> Result<(String, Vec<String>), String> {
+    match entry.key.as_str() {
+        "filtering" => {
+            let linear = match entry.value.as_str() {
+                "linear" => "true",
+                "nearest" => "false",
+                other => return Err(format!("filtering must be linear or nearest, got '{}'", other)),
+            };
+            Ok(("--linear".to_string(), vec![linear.to_string()]))
+        }
+        "background" => {
+            // filter and mode keywords at the end, the rest is the path and may contain spaces
+            let mut path = entry.value.as_str();
+            let mut extra = Vec::new();
+            while let Some((rest, last)) = path.rsplit_once(char::is_whitespace)
+                && extra.len() < 2
+                && ["linear", "l", "nearest", "n", "fit", "fill"].contains(&last)
+            {
+                extra.insert(0, last.to_string());
+                path = rest.trim_end();
+            }
+            Ok(("--background".to_string(), [vec![path.to_string()], extra].concat()))
+        }
+        key => Ok((format!("--{}", key), vec![entry.value.clone()])),
+    }
+}
+
+/// flags take no value on the command line, config files and environment may say true or false
+fn parse_flag(values: &[String]) -> Result<bool, String> {
+    match values.first().map(String::as_str) {
+        None | Some("true" | "1" | "yes" | "on") => Ok(true),
+        Some("false" | "0" | "no" | "off") => Ok(false),
+        Some(value) => Err(format!("expected true or false, got '{}'", value)),
+    }
+}
+
+fn theme_name(theme: &Theme) -> String {
+    if *theme == DARK_THEME {
+        "dark".to_string()
+    } else if *theme == LIGHT_THEME {
+        "light".to_string()
+    } else {
+        format!("{}x{}", to_hex_color(theme.background_color), to_hex_color(theme.font_color))
+    }
+}
+
+fn numbering_anchor_name(anchor: NumberingAnchor) -> &'static str {
+    match anchor {
+        NumberingAnchor::BottomLeft => "bl",
+        NumberingAnchor::BottomCenter => "bc",
+        NumberingAnchor::BottomRight => "br",
+        NumberingAnchor::TopLeft => "tl",
+        NumberingAnchor::TopCenter => "tc",
+        NumberingAnchor::TopRight => "tr",
+    }
+}
+
+/// dark, light or <bg_hex>x<font_hex>
+pub fn parse_theme(value: &str) -> Result<Theme, String> {
+    match value {
+        "dark" => Ok(DARK_THEME),
+        "light" => Ok(LIGHT_THEME),
+        _ => {
+            let Some((bg, font)) = value.split_once('x') else {
+                return Err(format!("unknown theme '{}', expected dark, light or <bg_hex>x<font_hex>", value));
+            };
+            match (parse_hex_color(bg), parse_hex_color(font)) {
+                (Ok(bg), Ok(font)) => Ok(Theme {
+                    background_color: bg,
+                    font_color: font,
+                }),
+                _ => Err(format!("invalid hex colors in theme '{}', expected e.g. 000000xffffff", value)),
+            }
+        }
+    }
+}
+
+/// <width>x<height>, at most 3840x3840
+pub fn parse_resolution(value: &str) -> Result<Vec2, String> {
+    let size = value
+        .split_once('x')
+        .and_then(|(w, h)| Some((w.parse::<f32>().ok()?, h.parse::<f32>().ok()?)));
+
+    match size {
+        Some((w, h)) if w > 3840.0 || h > 3840.0 => {
+            Err(format!("resolution too large ({}x{}), max allowed is 3840x3840", w, h))
+        }
+        Some((w, h)) if w >= 1.0 && h >= 1.0 => Ok(vec2(w, h)),
+        _ => Err(format!("invalid resolution '{}', use <width>x<height> (e.g. 1600x1200)", value)),
+    }
+}
+
+pub fn parse_numbering_anchor(value: &str) -> Result<NumberingAnchor, String> {
+    match value {
+        "bl" => Ok(NumberingAnchor::BottomLeft),
+        "bc" => Ok(NumberingAnchor::BottomCenter),
+        "br" => Ok(NumberingAnchor::BottomRight),
+        "tl" => Ok(NumberingAnchor::TopLeft),
+        "tc" => Ok(NumberingAnchor::TopCenter),
+        "tr" => Ok(NumberingAnchor::TopRight),
+        _ => Err(format!("unknown numbering anchor '{}', expected bl, bc, br, tl, tc or tr", value)),
+    }
+}
+
+pub fn parse_export_format(value: &str) -> Result<ExportFormat, String> {
+    match value {
+        "png" => Ok(ExportFormat::Png),
+        "pdf" => Ok(ExportFormat::Pdf),
+        "html" => Ok(ExportFormat::Html),
+        _ => Err(format!("unknown export format '{}', supported: png, pdf, html", value)),
+    }
+}
+
+pub fn parse_presenter_mode(value: &str) -> Result<PresenterMode, String> {
+    match value {
+        "off" => Ok(PresenterMode::Off),
+        "split" => Ok(PresenterMode::Split),
+        "window" => Ok(PresenterMode::Window),
+        _ => Err(format!("unknown presenter view '{}', expected off, split or window", value)),
+    }
+}
+
+pub fn parse_notes_mode(value: &str) -> Result<NotesMode, String> {
+    match value {
+        "pages" => Ok(NotesMode::Pages),
+        "annotations" => Ok(NotesMode::Annotations),
+        _ => Err(format!("unknown notes mode '{}', supported: pages, annotations", value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn later_layers_win() {
+        let dir = std::env::temp_dir().join(format!("reiha-settings-{}", std::process::id()));
+        let global = dir.join("global");
+        std::fs::create_dir_all(&global).unwrap();
+        std::fs::write(global.join("config"), "--theme light\n--align center\n--pace-warning 10\n").unwrap();
+        std::fs::write(global.join("config.toml"), "[numbering]\nenabled = true\n").unwrap();
+        std::fs::write(dir.join("reiha.config"), "--align left\n--pace-alarm 90\n").unwrap();
+        std::fs::write(dir.join("reiha.toml"), "[text]\nalign = \"right\"\nwrap = false\n\n[timer]\nwarning = 30\n").unwrap();
+        let input = dir.join("talk.rh");
+        std::fs::write(&input, "---\nwrap: on\nduration: 30\n---\nhello\n").unwrap();
+
+        let global_paths = [global.join("config"), global.join("config.toml"), global.join("missing.toml")];
+        let env = |var: &str| match var {
+            "REIHA_PACE_ALARM" => Some("100".to_string()),
+            "REIHA_DURATION" => Some("20m".to_string()),
+            _ => None,
+        };
+        let cli = [(find_option("--duration").unwrap(), vec!["25m".to_string()])];
+        let input_path = input.to_str().unwrap();
+        let settings = Settings::load_layers(Some(input_path), &cli, &global_paths, env);
+        std::fs::remove_dir_all(&dir).unwrap();
+        let settings = settings.unwrap();
+
+        // the global config counts where nothing else says otherwise
+        assert!(settings.theme.value == LIGHT_THEME);
+        assert_eq!(settings.theme.source, Source::GlobalConfig(global.join("config")));
+        assert!(settings.numbering.value);
+        assert_eq!(settings.numbering.source, Source::GlobalConfig(global.join("config.toml")));
+        // reiha.toml is read after reiha.config, both after the global config
+        assert_eq!(settings.text_align.value, Align::Right);
+        assert_eq!(settings.text_align.source, Source::DeckConfig(dir.join("reiha.toml")));
+        assert_eq!(settings.pace_warning.value, 30);
+        // front matter over the deck config
+        assert!(settings.wrap.value);
+        assert_eq!(settings.wrap.source, Source::FrontMatter(input.clone(), 2));
+        // the environment over the files, the command line over everything
+        assert_eq!(settings.pace_alarm.value, 100);
+        assert_eq!(settings.pace_alarm.source, Source::Env("REIHA_PACE_ALARM".to_string()));
+        assert_eq!(settings.duration.value, Some(25 * 60));
+        assert_eq!(settings.duration.source, Source::Cli("--duration".to_string()));
+    }
+
+    #[test]
+    fn environment_values_are_not_split() {
+        let env = |var: &str| (var == "REIHA_BACKGROUND").then(|| "my slides/bg 2.png".to_string());
+        let settings = Settings::load_layers(None, &[], &[], env).unwrap();
+        assert_eq!(settings.bg_image_path.value.as_deref(), Some("my slides/bg 2.png"));
+        assert_eq!(settings.bg_image_path.source, Source::Env("REIHA_BACKGROUND".to_string()));
+    }
+
+    #[test]
+    fn environment_mistakes_name_the_variable() {
+        let env = |var: &str| (var == "REIHA_ALIGN").then(|| "sideways".to_string());
+        let error = Settings::load_layers(None, &[], &[], env).err().unwrap();
+        assert!(error.starts_with("REIHA_ALIGN: "), "{}", error);
+    }
+
+    #[test]
+    fn front_matter_keys_are_options() {
+        let option = |key: &str, value: &str| {
+            let entry = FrontMatterEntry { key: key.to_string(), value: value.to_string(), line: 1 };
+            front_matter_option(&entry).map(|(long, values)| format!("{} {}", long, values.join("|")))
+        };
+        // the path may have spaces, the keywords after it are split off
+        assert_eq!(option("background", "my bg.png fit nearest"), Ok("--background my bg.png|fit|nearest".to_string()));
+        assert_eq!(option("filtering", "linear"), Ok("--linear true".to_string()));
+        assert!(option("filtering", "blurry").is_err());
+        assert_eq!(option("theme", "light"), Ok("--theme light".to_string()));
+    }
+}
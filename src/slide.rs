@@ -1,59 +1,120 @@
 use macroquad::prelude::*;
 
+use crate::highlight::*;
+use crate::markup::*;
+use crate::style::*;
 use crate::theming::*;
 use crate::utils::*;
+use crate::wrap::*;
 
-const CFACT : f32 = 0.7;
+pub use crate::document::{Align, BackgroundMode, Focus, SlideSpec, SlideType, Transition, VAlign, step_count};
 
-pub enum SlideType {
-    Empty,
-    Text,
-    Image,
-    TextImage,
-    Code,
+pub const CFACT : f32 = 0.7;
+
+/// background a slide draws instead of the scene one
+#[derive(Clone)]
+pub enum SlideBackground {
+    Hidden,
+    Image { texture: Texture2D, mode: Option<BackgroundMode> },
+}
+
+/// how a slide differs from the rest of the deck, set by its `%` directives
+#[derive(Clone, Default)]
+pub struct SlideStyle {
+    pub bg_color: Option<Color>,
+    pub font_color: Option<Color>,
+    pub background: Option<SlideBackground>,
+    pub numbering: Option<bool>,
+    pub align: Option<Align>,
+    pub valign: Option<VAlign>,
+    pub transition: Option<Transition>,
+    pub transition_duration: Option<f32>,
 }
 
 pub struct Slide {
     pub num:  u32,
     pub slide_type: SlideType,
+    /// text without markup
     pub text: Option<String>,
+    /// styled runs of every text line
+    pub lines: Vec<Vec<Run>>,
     pub img:  Option<Texture2D>,
+    /// shown in the terminal instead of the image
+    pub image_path: Option<String>,
     pub img_scale: Option<f32>,
     pub font_size: Option<u16>,
     pub comments:  Option<String>,
+    pub style: SlideStyle,
+    pub focus: Focus,
+    /// step each line shows up at
+    pub reveal: Vec<usize>,
+    /// seconds the slide may take from its `% time` directive
+    pub time: Option<u64>,
+}
+
+#[cfg(test)]
+impl Slide {
+    /// text slide with nothing else set, for tests that do not draw
+    pub fn plain(num: u32, text: &str) -> Self {
+        Self {
+            num,
+            slide_type: SlideType::Text,
+            text: Some(text.to_string()),
+            lines: Vec::new(),
+            img: None,
+            image_path: None,
+            img_scale: None,
+            font_size: None,
+            comments: None,
+            style: SlideStyle::default(),
+            focus: Focus::default(),
+            reveal: Vec::new(),
+            time: None,
+        }
+    }
 }
 
 impl Slide {
-    pub fn new(
-        num:  u32,
-        slide_type: SlideType,
-        text: Option<String>,
+    /// text is wrapped and fitted with the given measurer, drawing takes the fonts later
+    pub fn new<M: MeasureText + ?Sized>(
+        spec: &SlideSpec,
         img:  Option<Texture2D>,
-        comments: Option<String>,
+        style: SlideStyle,
+        wrap: bool,
         virtual_screen_size: &Vec2,
-        font: &Font,
-        mono_font: &Font,
+        faces: &FontFaces<M>,
+        mono_font: &M,
     ) -> Self {
+        let (lines, reveal) = slide_lines(spec, wrap.then_some(faces), virtual_screen_size);
+        let text = spec.text().map(|_| plain_text(&lines));
+
         let mut self_values = Self {
-            num,
-            slide_type,
+            num: spec.num,
+            slide_type: spec.slide_type,
             text: None,
+            lines,
             img: None,
+            image_path: spec.image.clone(),
             img_scale: None,
             font_size: None,
-            comments,
+            comments: spec.notes.clone(),
+            style,
+            focus: spec.focus.clone(),
+            reveal,
+            time: spec.directives.time,
         };
 
+        self_values.font_size = fit_font_size(
+            self_values.slide_type,
+            &self_values.lines,
+            faces,
+            mono_font,
+            virtual_screen_size,
+        );
+
         match self_values.slide_type {
             SlideType::Empty => {}
-            SlideType::Text => {
-                self_values.font_size = Some(find_max_font_size(
-                    text.as_ref().unwrap(),
-                    Some(font),
-                    1.0,
-                    Some(1.0),
-                    virtual_screen_size,
-                ));
+            SlideType::Text | SlideType::Code => {
                 self_values.text = text;
             }
             SlideType::Image => {
@@ -80,24 +141,6 @@ impl Slide {
                 }
 
                 // Text
-                self_values.font_size = Some(find_max_font_size(
-                        text.as_ref().unwrap(),
-                        Some(font),
-                        1.0,
-                        Some(1.0),
-                        &vec2(virtual_screen_size.x, virtual_screen_size.y * (1f32 - CFACT)),
-                ));
-                self_values.text = text;
-            }
-            SlideType::Code => {
-                // text
-                self_values.font_size = Some(find_max_font_size(
-                    text.as_ref().unwrap(),
-                    Some(mono_font),
-                    1.0,
-                    Some(1.0),
-                    virtual_screen_size,
-                ));
                 self_values.text = text;
             }
         }
@@ -105,18 +148,34 @@ impl Slide {
         self_values
     }
 
-    pub fn draw(&self, font: &Font, mono_font: &Font, font_color: &Color, virtual_screen_size: &Vec2) {
+    /// alignment the slide is drawn with, its directives win over the given default
+    pub fn alignment(&self, (align, valign): (Align, VAlign)) -> (Align, VAlign) {
+        (self.style.align.unwrap_or(align), self.style.valign.unwrap_or(valign))
+    }
+
+    /// how many times next stays on the slide, plus one
+    pub fn steps(&self) -> usize {
+        step_count(&self.focus, &self.reveal)
+    }
+
+    /// whether the 0-based line is shown at the step, `+` lines wait for theirs
+    pub fn is_revealed(&self, step: usize, line: usize) -> bool {
+        self.reveal.get(line).is_none_or(|from| *from <= step)
+    }
+
+    pub fn draw(
+        &self,
+        faces: &FontFaces<Font>,
+        mono_font: &Font,
+        palette: &Palette,
+        virtual_screen_size: &Vec2,
+        alignment: (Align, VAlign),
+        step: usize,
+    ) {
         match self.slide_type {
             SlideType::Empty => {}
             SlideType::Text => {
-                draw_text_center(
-                    &self.text.clone().unwrap(),
-                    Some(font),
-                    font_color,
-                    virtual_screen_size,
-                    self.font_size.unwrap_or(16u16),
-                    None,
-                );
+                self.draw_lines(faces, palette, virtual_screen_size, alignment, step);
             }
             SlideType::Image => {
                 draw_img_scaled_and_centered(
@@ -131,70 +190,95 @@ impl Slide {
                     &self.img_scale.clone().unwrap(),
                     &vec2(virtual_screen_size.x, virtual_screen_size.y * CFACT),
                 );
-                draw_text_center(
-                    &self.text.clone().unwrap(),
-                    Some(font),
-                    font_color,
-                    &vec2(virtual_screen_size.x, virtual_screen_size.y),
-                    self.font_size.unwrap_or(16u16),
-                    Some(virtual_screen_size.y * CFACT),
-                );
+                self.draw_lines(faces, palette, virtual_screen_size, alignment, step);
             }
             SlideType::Code => {
-                draw_text_center(
-                    &self.text.clone().unwrap(),
-                    Some(mono_font),
-                    font_color,
-                    virtual_screen_size,
-                    self.font_size.unwrap_or(16u16),
-                    None,
-                );
+                self.draw_lines(&FontFaces::regular(mono_font), palette, virtual_screen_size, alignment, step);
             }
         }
     }
 
-    pub fn print(&self, total: usize) {
-        println!("[slide {}/{}]", self.num, total);
-        println!("===[ Content ]===============================================");
-
-        match self.slide_type {
-            SlideType::Empty => {
-                println!("\n\
-                    [ Empty Slide ]\
-                    ");
-            }
-            SlideType::Text | SlideType::Code => {
-                if let Some(text) = &self.text {
-                    for line in text.lines() {
-                        println!("{}", line);
-                    }
-                }
-            }
-            SlideType::Image => {
-                println!("\n\
-                    [ image ]
-                    ");
-            }
-            SlideType::TextImage => {
-                println!("\n\
-                    [ image ]
-                    ");
-                if let Some(text) = &self.text {
-                    for line in text.lines() {
-                        println!("{}", line);
-                    }
-                }
+    /// draws the text lines as they are at the step: lines still to come keep their space,
+    /// code lines out of focus are dimmed
+    fn draw_lines(
+        &self,
+        faces: &FontFaces<Font>,
+        palette: &Palette,
+        virtual_screen_size: &Vec2,
+        (align, valign): (Align, VAlign),
+        step: usize,
+    ) {
+        let font_size = self.font_size.unwrap_or(16u16);
+        let start_pos_y = (self.slide_type == SlideType::TextImage).then_some(virtual_screen_size.y * CFACT);
+        let origins = line_origins(&self.lines, faces, font_size, virtual_screen_size, start_pos_y, align, valign);
+        let dimmed = palette.dimmed();
+
+        for (i, (runs, position)) in self.lines.iter().zip(origins).enumerate() {
+            if !self.is_revealed(step, i) {
+                continue;
             }
+            let palette = if self.focus.is_focused(step, i + 1) { palette } else { &dimmed };
+            draw_runs(runs, position, faces, font_size, palette);
         }
+    }
 
-        if let Some(comments) = &self.comments {
-            println!("\n= =[ Notes ]= = = = = = = = = = = = = = = = = = = = = = = = =");
-            for line in comments.lines() {
-                println!("{}", line);
-            }
+    /// "slide 3/20", with the step on slides that have more than one
+    pub fn counter(&self, total: usize, step: usize) -> String {
+        if self.steps() > 1 {
+            format!("slide {}/{} step {}/{}", self.num, total, step + 1, self.steps())
+        } else {
+            format!("slide {}/{}", self.num, total)
+        }
+    }
+
+    /// content as terminal lines at the step. With color code is highlighted and lines out of focus
+    /// or still to come are dimmed, lines still to come are marked with + either way
+    pub fn terminal_lines(&self, step: usize, color: bool) -> Vec<String> {
+        let mut out = Vec::new();
+        match (self.slide_type, &self.image_path) {
+            (SlideType::Empty, _) => out.push("[ empty slide ]".to_string()),
+            (SlideType::Image | SlideType::TextImage, Some(path)) => out.push(format!("[ image {} ]", path)),
+            _ => {}
+        }
+
+        for (i, runs) in self.lines.iter().enumerate() {
+            let text = plain_text(std::slice::from_ref(runs));
+            let line = match (self.is_revealed(step, i), self.focus.is_focused(step, i + 1), color) {
+                (false, _, true) => format!("\x1b[2m+ {}\x1b[0m", text),
+                (false, _, false) => format!("+ {}", text),
+                (true, false, true) => format!("\x1b[2m{}\x1b[0m", text),
+                (true, true, true) if self.slide_type == SlideType::Code => ansi_line(runs),
+                (true, _, _) => text,
+            };
+            out.push(line);
         }
 
-        println!("=============================================================");
+        out
+    }
+}
+
+/// font size the text of a slide is fitted to, None for slides without text.
+/// Generic over the measurer so it gives the same answer with and without a window
+pub fn fit_font_size<M: MeasureText + ?Sized>(
+    slide_type: SlideType,
+    lines: &[Vec<Run>],
+    faces: &FontFaces<M>,
+    mono_font: &M,
+    virtual_screen_size: &Vec2,
+) -> Option<u16> {
+    let area = text_area(slide_type, virtual_screen_size);
+    match slide_type {
+        SlideType::Empty | SlideType::Image => None,
+        SlideType::Text | SlideType::TextImage => Some(find_max_font_size(lines, faces, &area)),
+        SlideType::Code => Some(find_max_font_size(lines, &FontFaces::regular(mono_font), &area)),
+    }
+}
+
+/// size of the part of the screen the text of a slide is fitted into
+pub fn text_area(slide_type: SlideType, virtual_screen_size: &Vec2) -> Vec2 {
+    match slide_type {
+        SlideType::TextImage => vec2(virtual_screen_size.x, virtual_screen_size.y * (1f32 - CFACT)),
+        _ => *virtual_screen_size,
     }
 }
 
@@ -253,60 +337,79 @@ pub fn draw_img_background(
     );
 }
 
-pub fn draw_text_center(
-    text: &str,
-    font: Option<&Font>,
-    font_color: &Color,
-    virtual_screen_size: &Vec2,
+/// baseline position of the first line of a text block, centered horizontally on the screen
+/// and placed vertically in the screen or in the area below start_pos_y
+pub fn text_origin(
+    text_size: Vec2,
     font_size: u16,
+    virtual_screen_size: &Vec2,
     start_pos_y: Option<f32>,
-) {
-    let font_scale = 1f32;
-    let font_scale_aspect = 1f32;
-    let rotation = 0f32;
-    let line_distance_factor = 1f32;
-
-    let mut screen_center = vec2(virtual_screen_size.x / 2f32, virtual_screen_size.y / 2f32);
-
-    if let Some(start_y) = start_pos_y {
-        screen_center.y = virtual_screen_size.y - (virtual_screen_size.y - start_y) / 2.0;
-    }
-
-    let mut position = screen_center;
+    valign: VAlign,
+) -> Vec2 {
+    let area_top = start_pos_y.unwrap_or(0f32);
+    let area_height = virtual_screen_size.y - area_top;
+    // fitting leaves 2% of the area free on each side
+    let margin = area_height * 0.02;
+
+    let top = match valign {
+        VAlign::Top => area_top + margin,
+        VAlign::Middle => area_top + (area_height - text_size.y) / 2f32,
+        VAlign::Bottom => area_top + area_height - margin - text_size.y,
+    };
+
+    vec2(
+        (virtual_screen_size.x - text_size.x) / 2f32,
+        top + font_size as f32 * 0.85, // 0.85 is a picked value that seems to be working
+    )
+}
 
-    // NOTE: Macroquad crate modified using this commit: https://github.com/not-fl3/macroquad/pull/884/files
-    let text_dimentions = measure_multiline_text(
-        text,
-        font,
+/// baseline position of every line of a text block.
+/// Generic over the measurer so the pdf text layer lands where the text is drawn
+pub fn line_origins<M: MeasureText + ?Sized>(
+    lines: &[Vec<Run>],
+    faces: &FontFaces<M>,
+    font_size: u16,
+    virtual_screen_size: &Vec2,
+    start_pos_y: Option<f32>,
+    align: Align,
+    valign: VAlign,
+) -> Vec<Vec2> {
+    let origin = text_origin(
+        measure_lines(lines, faces, font_size),
         font_size,
-        font_scale,
-        Some(line_distance_factor),
+        virtual_screen_size,
+        start_pos_y,
+        valign,
     );
+    let margin = virtual_screen_size.x * 0.02;
+
+    lines
+        .iter()
+        .enumerate()
+        .map(|(i, runs)| {
+            let width = || line_width(runs, faces, font_size);
+            let x = match align {
+                Align::Block => origin.x,
+                Align::Left => margin,
+                Align::Center => (virtual_screen_size.x - width()) / 2f32,
+                Align::Right => virtual_screen_size.x - margin - width(),
+            };
+            vec2(x, origin.y + i as f32 * font_size as f32)
+        })
+        .collect()
+}
 
-    let text_center = vec2(
-        position.x + (text_dimentions.width / 2f32),
-        position.y + (text_dimentions.height / 2f32) - (font_size as f32 * 0.85), // 0.85 is a picked value that seems to be working
-    );
-
-    let difference = screen_center - text_center;
-    position += difference;
-
-    draw_multiline_text_ex(
-        text,
-        position.x,
-        position.y,
-        Some(line_distance_factor),
-        TextParams {
-            font,
-            font_size,
-            font_scale,
-            font_scale_aspect,
-            rotation,
-            color: *font_color,
-        },
-    );
+/// highlighted code line for the terminal
+fn ansi_line(runs: &[Run]) -> String {
+    runs.iter()
+        .map(|run| match run.token {
+            Some(token) => format!("\x1b[{}m{}\x1b[0m", ansi_color(token), run.text),
+            None => run.text.clone(),
+        })
+        .collect()
 }
 
+#[derive(Clone, Copy)]
 pub enum NumberingAnchor{
     BottomLeft,
     BottomCenter,
@@ -316,6 +419,39 @@ pub enum NumberingAnchor{
     TopRight,
 }
 
+/// where the slide number is anchored on the virtual screen
+pub fn numbering_position(anchor: NumberingAnchor, virtual_screen_size: &Vec2) -> Vec2 {
+    let bottom = virtual_screen_size.y - virtual_screen_size.y / 300f32;
+    match anchor {
+        NumberingAnchor::BottomLeft => vec2(virtual_screen_size.x / 400f32, bottom),
+        NumberingAnchor::BottomCenter => vec2(virtual_screen_size.x / 2f32, bottom),
+        NumberingAnchor::BottomRight => vec2(virtual_screen_size.x, bottom),
+        NumberingAnchor::TopLeft => vec2(virtual_screen_size.x / 400f32, 0f32),
+        NumberingAnchor::TopCenter => vec2(virtual_screen_size.x / 2f32, 0f32),
+        NumberingAnchor::TopRight => vec2(virtual_screen_size.x, 0f32),
+    }
+}
+
+/// font size of the slide number
+pub fn numbering_size(virtual_screen_size: &Vec2) -> u16 {
+    virtual_screen_size.x as u16 / 32u16
+}
+
+/// how far the number is moved from its anchor position so that its width and height
+/// stay on the screen
+pub fn numbering_offset(anchor: &NumberingAnchor, width: f32, height: f32) -> Vec2 {
+    let anchor_x = match anchor {
+        NumberingAnchor::BottomLeft | NumberingAnchor::TopLeft => 0f32,
+        NumberingAnchor::BottomCenter | NumberingAnchor::TopCenter => width / 2f32,
+        NumberingAnchor::BottomRight | NumberingAnchor::TopRight => width,
+    };
+    let anchor_y = match anchor {
+        NumberingAnchor::TopLeft | NumberingAnchor::TopCenter | NumberingAnchor::TopRight => height,
+        _ => 0f32,
+    };
+    vec2(-anchor_x, anchor_y)
+}
+
 pub fn draw_numbering(
     current_slide: &usize,
     font: &Font,
@@ -325,21 +461,7 @@ pub fn draw_numbering(
     anchor: &NumberingAnchor,
 ) {
     let number_dim = measure_text(&(current_slide + 1).to_string(), Some(&font), *numbering_size, 1f32);
-    let anchor_x: f32;
-
-    match anchor {
-        NumberingAnchor::BottomLeft   | NumberingAnchor::TopLeft   => { anchor_x = 0f32 },
-        NumberingAnchor::BottomCenter | NumberingAnchor::TopCenter => { anchor_x = number_dim.width / 2f32 },
-        NumberingAnchor::BottomRight  | NumberingAnchor::TopRight  => { anchor_x = number_dim.width },
-    }
-
-    let anchor_y: f32;
-    match anchor {
-         NumberingAnchor::TopLeft | NumberingAnchor::TopCenter | NumberingAnchor::TopRight => anchor_y = number_dim.height,
-         _ => anchor_y = 0f32,
-    }
-
-    let numbering_position = vec2(numbering_position.x - anchor_x, numbering_position.y + anchor_y);
+    let numbering_position = *numbering_position + numbering_offset(anchor, number_dim.width, number_dim.height);
 
     // // NOTE: hi-contrast feature
     // const SHADOW_OFFSET: f32 = 1f32;
@@ -409,3 +531,52 @@ pub fn draw_numbering(
         },
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCREEN: Vec2 = vec2(1000f32, 500f32);
+
+    /// a 40px wide line over a 20px one at font size 20
+    fn lines() -> Vec<Vec<Run>> {
+        ["aaaa", "aa  "].iter().map(|line| parse_line(line).0).collect()
+    }
+
+    fn origins(align: Align, valign: VAlign) -> Vec<(f32, f32)> {
+        line_origins(&lines(), &FontFaces::regular(&Monospace), 20, &SCREEN, None, align, valign)
+            .iter()
+            .map(|origin| (origin.x, origin.y))
+            .collect()
+    }
+
+    #[test]
+    fn text_is_anchored_vertically_with_a_margin() {
+        let size = vec2(40f32, 40f32);
+        // the baseline is 0.85 of the font size below the top of the block, the margin 2% of the height
+        assert_eq!(text_origin(size, 20, &SCREEN, None, VAlign::Top), vec2(480f32, 10f32 + 17f32));
+        assert_eq!(text_origin(size, 20, &SCREEN, None, VAlign::Middle), vec2(480f32, 230f32 + 17f32));
+        assert_eq!(text_origin(size, 20, &SCREEN, None, VAlign::Bottom), vec2(480f32, 450f32 + 17f32));
+    }
+
+    #[test]
+    fn text_below_an_image_stays_in_its_area() {
+        let size = vec2(40f32, 40f32);
+        // 150px below the image, the margin is 2% of that
+        let below = |valign| text_origin(size, 20, &SCREEN, Some(350f32), valign).y;
+        assert_eq!(below(VAlign::Top), 353f32 + 17f32);
+        assert_eq!(below(VAlign::Middle), 405f32 + 17f32);
+        assert_eq!(below(VAlign::Bottom), 457f32 + 17f32);
+    }
+
+    #[test]
+    fn lines_are_aligned_horizontally() {
+        // a block is centered as a whole, its lines start at the same x
+        assert_eq!(origins(Align::Block, VAlign::Middle), [(480f32, 247f32), (480f32, 267f32)]);
+        // left and right keep 2% of the width free, trailing spaces do not count
+        assert_eq!(origins(Align::Left, VAlign::Middle), [(20f32, 247f32), (20f32, 267f32)]);
+        assert_eq!(origins(Align::Center, VAlign::Middle), [(480f32, 247f32), (490f32, 267f32)]);
+        assert_eq!(origins(Align::Right, VAlign::Middle), [(940f32, 247f32), (960f32, 267f32)]);
+        assert_eq!(origins(Align::Right, VAlign::Top), [(940f32, 27f32), (960f32, 47f32)]);
+    }
+}
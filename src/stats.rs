@@ -0,0 +1,58 @@
+use crate::diagnostic::*;
+use crate::document::*;
+use crate::utils::*;
+
+fn count_words<'a>(lines: impl Iterator<Item = &'a str>) -> usize {
+    lines.map(|line| line.split_whitespace().count()).sum()
+}
+
+/// prints slide, word and image counts, returns the process exit code
+pub fn run(input_path: &str) -> i32 {
+    let (deck, diagnostics) = read_deck(input_path);
+    report(&diagnostics, input_path);
+    let Some(deck) = deck else {
+        return 1;
+    };
+
+    let count = |slide_type| deck.slides.iter().filter(|s| s.slide_type == slide_type).count();
+
+    let slide_words = count_words(
+        deck.slides
+            .iter()
+            .filter(|s| s.text().is_some())
+            .flat_map(|s| s.lines.iter().map(String::as_str)),
+    );
+    let notes: Vec<String> = deck
+        .slides
+        .iter()
+        .filter_map(|s| s.notes.as_deref())
+        .flat_map(clean_notes)
+        .collect();
+    let notes_words = count_words(notes.iter().map(String::as_str));
+
+    let mut images: Vec<&str> = deck.slides.iter().filter_map(|s| s.image.as_deref()).collect();
+    let image_uses = images.len();
+    images.sort();
+    images.dedup();
+
+    println!("`{}`: {}", input_path, pluralize(deck.slides.len(), "slide"));
+    println!(
+        "  text: {}, image: {}, text+image: {}, code: {}, empty: {}",
+        count(SlideType::Text),
+        count(SlideType::Image),
+        count(SlideType::TextImage),
+        count(SlideType::Code),
+        count(SlideType::Empty)
+    );
+    println!("  {} on slides, {} in notes", pluralize(slide_words, "word"), pluralize(notes_words, "word"));
+    println!("  {} ({} unique)", pluralize(image_uses, "image"), images.len());
+    // a bare `|` line is not a note
+    let with_notes = deck
+        .slides
+        .iter()
+        .filter(|s| s.notes.as_deref().is_some_and(|n| clean_notes(n).iter().any(|line| !line.trim().is_empty())))
+        .count();
+    println!("  {} with notes", pluralize(with_notes, "slide"));
+
+    0
+}
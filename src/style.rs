@@ -0,0 +1,137 @@
+use macroquad::prelude::*;
+
+use crate::markup::*;
+use crate::theming::*;
+use crate::utils::*;
+
+/// slant of faux italic, x shift per pixel above the baseline
+pub const FAUX_ITALIC_SLANT: f32 = 0.2;
+
+/// a face to draw a run with and the styles it has to fake
+pub struct Face<'a, M: ?Sized> {
+    pub font: &'a M,
+    pub faux_bold: bool,
+    pub faux_italic: bool,
+}
+
+/// faces of a font, styles without a face of their own are faked from another one
+pub struct FontFaces<'a, M: ?Sized> {
+    pub regular: &'a M,
+    pub bold: Option<&'a M>,
+    pub italic: Option<&'a M>,
+}
+
+impl<'a, M: ?Sized> FontFaces<'a, M> {
+    /// a single face, bold and italic are faked
+    pub fn regular(font: &'a M) -> Self {
+        Self { regular: font, bold: None, italic: None }
+    }
+
+    pub fn face(&self, run: &Run) -> Face<'a, M> {
+        let face = |font: &'a M, faux_bold: bool, faux_italic: bool| Face { font, faux_bold, faux_italic };
+        match (run.bold, run.italic, self.bold, self.italic) {
+            (false, false, _, _) => face(self.regular, false, false),
+            (true, false, Some(bold), _) => face(bold, false, false),
+            (true, false, None, _) => face(self.regular, true, false),
+            (false, true, _, Some(italic)) => face(italic, false, false),
+            (false, true, _, None) => face(self.regular, false, true),
+            (true, true, Some(bold), _) => face(bold, false, true),
+            (true, true, None, Some(italic)) => face(italic, true, false),
+            (true, true, None, None) => face(self.regular, true, true),
+        }
+    }
+}
+
+/// how far faux bold draws the run a second time
+pub fn faux_bold_offset(font_size: u16) -> f32 {
+    (font_size as f32 / 28f32).max(1f32)
+}
+
+/// width of a run in the face it is drawn with
+pub fn run_width<M: MeasureText + ?Sized>(run: &Run, text: &str, faces: &FontFaces<M>, font_size: u16) -> f32 {
+    let face = faces.face(run);
+    let extra = if face.faux_bold { faux_bold_offset(font_size) } else { 0f32 };
+    face.font.measure_width(text, font_size) + extra
+}
+
+/// width of runs with all their whitespace
+pub fn runs_width<M: MeasureText + ?Sized>(runs: &[Run], faces: &FontFaces<M>, font_size: u16) -> f32 {
+    runs.iter().map(|run| run_width(run, &run.text, faces, font_size)).sum()
+}
+
+/// width of a line of runs, trailing whitespace is not counted
+pub fn line_width<M: MeasureText + ?Sized>(runs: &[Run], faces: &FontFaces<M>, font_size: u16) -> f32 {
+    let Some(last) = runs.iter().rposition(|run| !run.text.trim_end().is_empty()) else {
+        return 0f32;
+    };
+
+    runs[..=last]
+        .iter()
+        .enumerate()
+        .map(|(i, run)| {
+            let text = if i == last { run.text.trim_end() } else { run.text.as_str() };
+            run_width(run, text, faces, font_size)
+        })
+        .sum()
+}
+
+/// size of a block of lines, a line is as high as the font size
+pub fn measure_lines<M: MeasureText + ?Sized>(lines: &[Vec<Run>], faces: &FontFaces<M>, font_size: u16) -> Vec2 {
+    let width = lines
+        .iter()
+        .map(|runs| line_width(runs, faces, font_size))
+        .fold(0f32, f32::max);
+    vec2(width, lines.len() as f32 * font_size as f32)
+}
+
+/// color of a run, markup wins over the token color and both over the text color of the palette
+pub fn run_color(run: &Run, palette: &Palette) -> Color {
+    run.color
+        .map(Color::from_hex)
+        .or_else(|| run.token.map(|token| palette.token_color(token)))
+        .unwrap_or(palette.text)
+}
+
+/// draws a line of runs starting at the baseline position
+pub fn draw_runs(runs: &[Run], position: Vec2, faces: &FontFaces<Font>, font_size: u16, palette: &Palette) {
+    let mut x = position.x;
+
+    for run in runs {
+        let face = faces.face(run);
+        let width = run_width(run, &run.text, faces, font_size);
+
+        if run.highlight {
+            // 0.85 matches the baseline offset of text_origin, so the mark covers the line
+            draw_rectangle(x, position.y - font_size as f32 * 0.85, width, font_size as f32, HIGHLIGHT_COLOR);
+        }
+
+        let params = TextParams {
+            font: Some(face.font),
+            font_size,
+            color: run_color(run, palette),
+            ..Default::default()
+        };
+
+        if face.faux_italic {
+            // shear around the baseline, x moves right the higher a point is
+            let shear = Mat4::from_cols(
+                vec4(1f32, 0f32, 0f32, 0f32),
+                vec4(-FAUX_ITALIC_SLANT, 1f32, 0f32, 0f32),
+                vec4(0f32, 0f32, 1f32, 0f32),
+                vec4(FAUX_ITALIC_SLANT * position.y, 0f32, 0f32, 1f32),
+            );
+            unsafe { get_internal_gl() }.quad_gl.push_model_matrix(shear);
+        }
+
+        draw_text_ex(&run.text, x, position.y, params.clone());
+        if face.faux_bold {
+            draw_text_ex(&run.text, x + faux_bold_offset(font_size), position.y, params);
+        }
+
+        if face.faux_italic {
+            unsafe { get_internal_gl() }.quad_gl.pop_model_matrix();
+        }
+
+        x += width;
+    }
+}
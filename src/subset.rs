@@ -0,0 +1,213 @@
+use std::collections::BTreeSet;
+
+/// a big endian u16 in font data, None past the end
+fn read_u16(data: &[u8], at: usize) -> Option<u16> {
+    data.get(at..at + 2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+}
+
+fn read_u32(data: &[u8], at: usize) -> Option<u32> {
+    data.get(at..at + 4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+/// sum of the data as big endian u32 words, how sfnt tables are checked
+fn checksum(data: &[u8]) -> u32 {
+    data.chunks(4)
+        .map(|chunk| {
+            let mut word = [0u8; 4];
+            word[..chunk.len()].copy_from_slice(chunk);
+            u32::from_be_bytes(word)
+        })
+        .fold(0u32, u32::wrapping_add)
+}
+
+/// glyphs a composite glyph is built from, none for a simple one
+fn components(glyph: &[u8]) -> Option<Vec<u16>> {
+    const ARG_1_AND_2_ARE_WORDS: u16 = 0x0001;
+    const WE_HAVE_A_SCALE: u16 = 0x0008;
+    const MORE_COMPONENTS: u16 = 0x0020;
+    const WE_HAVE_AN_X_AND_Y_SCALE: u16 = 0x0040;
+    const WE_HAVE_A_TWO_BY_TWO: u16 = 0x0080;
+
+    if glyph.is_empty() || read_u16(glyph, 0)? as i16 >= 0 {
+        return Some(Vec::new());
+    }
+
+    let mut components = Vec::new();
+    let mut at = 10;
+    loop {
+        let flags = read_u16(glyph, at)?;
+        components.push(read_u16(glyph, at + 2)?);
+
+        at += 4 + if flags & ARG_1_AND_2_ARE_WORDS != 0 { 4 } else { 2 };
+        at += match flags {
+            f if f & WE_HAVE_A_SCALE != 0 => 2,
+            f if f & WE_HAVE_AN_X_AND_Y_SCALE != 0 => 4,
+            f if f & WE_HAVE_A_TWO_BY_TWO != 0 => 8,
+            _ => 0,
+        };
+        if flags & MORE_COMPONENTS == 0 {
+            return Some(components);
+        }
+    }
+}
+
+/// a TrueType font with the outlines of all but the given glyphs taken out.
+/// Glyph ids stay as they are, so text encoded against the full font shows the same.
+/// None for fonts this can not do, e.g. collections or CFF outlines, those are embedded whole
+pub fn subset_font(data: &[u8], glyphs: &BTreeSet<u16>) -> Option<Vec<u8>> {
+    let version = read_u32(data, 0)?;
+    if version != 0x0001_0000 && &data[..4] != b"true" {
+        return None;
+    }
+
+    let num_tables = read_u16(data, 4)? as usize;
+    let mut tables: Vec<([u8; 4], Vec<u8>)> = Vec::with_capacity(num_tables);
+    for i in 0..num_tables {
+        let entry = 12 + i * 16;
+        let tag: [u8; 4] = data.get(entry..entry + 4)?.try_into().ok()?;
+        let offset = read_u32(data, entry + 8)? as usize;
+        let length = read_u32(data, entry + 12)? as usize;
+        tables.push((tag, data.get(offset..offset + length)?.to_vec()));
+    }
+    let table = |tag: &[u8; 4]| tables.iter().find(|(t, _)| t == tag).map(|(_, data)| data.as_slice());
+
+    let long_offsets = read_u16(table(b"head")?, 50)? == 1;
+    let num_glyphs = read_u16(table(b"maxp")?, 4)? as usize;
+    let glyf = table(b"glyf")?;
+    let loca = table(b"loca")?;
+    let glyph = |id: usize| -> Option<&[u8]> {
+        let (start, end) = if long_offsets {
+            (read_u32(loca, id * 4)? as usize, read_u32(loca, id * 4 + 4)? as usize)
+        } else {
+            (read_u16(loca, id * 2)? as usize * 2, read_u16(loca, id * 2 + 2)? as usize * 2)
+        };
+        glyf.get(start..end)
+    };
+
+    // .notdef is always kept, composite glyphs need the glyphs they are built from
+    let mut keep: BTreeSet<u16> = glyphs.iter().copied().filter(|id| (*id as usize) < num_glyphs).collect();
+    keep.insert(0);
+    let mut pending: Vec<u16> = keep.iter().copied().collect();
+    while let Some(id) = pending.pop() {
+        for component in components(glyph(id as usize)?)? {
+            if (component as usize) < num_glyphs && keep.insert(component) {
+                pending.push(component);
+            }
+        }
+    }
+
+    let mut new_glyf = Vec::new();
+    let mut offsets = Vec::with_capacity(num_glyphs + 1);
+    for id in 0..num_glyphs {
+        offsets.push(new_glyf.len());
+        if keep.contains(&(id as u16)) {
+            new_glyf.extend_from_slice(glyph(id)?);
+            new_glyf.resize(new_glyf.len().next_multiple_of(4), 0);
+        }
+    }
+    offsets.push(new_glyf.len());
+
+    // the new glyf is never longer than the old one, so short offsets still fit
+    let new_loca: Vec<u8> = if long_offsets {
+        offsets.iter().flat_map(|offset| (*offset as u32).to_be_bytes()).collect()
+    } else {
+        offsets.iter().flat_map(|offset| ((*offset / 2) as u16).to_be_bytes()).collect()
+    };
+
+    for (tag, data) in &mut tables {
+        match &*tag {
+            b"glyf" => *data = std::mem::take(&mut new_glyf),
+            b"loca" => *data = new_loca.clone(),
+            // checkSumAdjustment, set again once the whole font is written
+            b"head" => data.get_mut(8..12)?.fill(0),
+            _ => {}
+        }
+    }
+
+    Some(write_font(version, &tables))
+}
+
+/// sfnt file from its tables, tables are written in the order given
+fn write_font(version: u32, tables: &[([u8; 4], Vec<u8>)]) -> Vec<u8> {
+    let num_tables = tables.len() as u16;
+    let entry_selector = num_tables.max(1).ilog2() as u16;
+    let search_range = (1u16 << entry_selector) * 16;
+
+    let mut font = Vec::new();
+    font.extend_from_slice(&version.to_be_bytes());
+    for value in [num_tables, search_range, entry_selector, num_tables * 16 - search_range] {
+        font.extend_from_slice(&value.to_be_bytes());
+    }
+
+    let mut offset = 12 + tables.len() * 16;
+    for (tag, data) in tables {
+        font.extend_from_slice(tag);
+        font.extend_from_slice(&checksum(data).to_be_bytes());
+        font.extend_from_slice(&(offset as u32).to_be_bytes());
+        font.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        offset += data.len().next_multiple_of(4);
+    }
+
+    let mut head_at = None;
+    for (tag, data) in tables {
+        if tag == b"head" {
+            head_at = Some(font.len());
+        }
+        font.extend_from_slice(data);
+        font.resize(font.len().next_multiple_of(4), 0);
+    }
+
+    if let Some(head_at) = head_at {
+        let adjustment = 0xB1B0_AFBAu32.wrapping_sub(checksum(&font));
+        font[head_at + 8..head_at + 12].copy_from_slice(&adjustment.to_be_bytes());
+    }
+
+    font
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::defaults::*;
+
+    #[test]
+    fn subset_keeps_the_used_glyphs_only() {
+        let full = fontdue::Font::from_bytes(&MONO_FONT[..], fontdue::FontSettings::default()).unwrap();
+        let used: BTreeSet<u16> = "Hi".chars().map(|c| full.lookup_glyph_index(c)).collect();
+
+        let bytes = subset_font(MONO_FONT, &used).unwrap();
+        assert!(bytes.len() < MONO_FONT.len() / 2);
+
+        let subset = fontdue::Font::from_bytes(bytes.as_slice(), fontdue::FontSettings::default()).unwrap();
+        for c in "Hi".chars() {
+            assert_eq!(subset.lookup_glyph_index(c), full.lookup_glyph_index(c));
+            assert_eq!(subset.rasterize(c, 20f32), full.rasterize(c, 20f32));
+        }
+        // everything else is still mapped and as wide, just without an outline
+        let (metrics, bitmap) = subset.rasterize('x', 20f32);
+        assert_eq!(metrics.advance_width, full.metrics('x', 20f32).advance_width);
+        assert!(bitmap.iter().all(|coverage| *coverage == 0));
+    }
+
+    #[test]
+    fn subset_checksums_add_up() {
+        let bytes = subset_font(MONO_FONT, &BTreeSet::from([1, 2, 3])).unwrap();
+        assert_eq!(checksum(&bytes), 0xB1B0_AFBA);
+    }
+
+    #[test]
+    fn composite_glyphs_keep_their_parts() {
+        // numberOfContours -1, bounding box, then two components, the first with word arguments
+        let mut glyph = vec![0xFF, 0xFF, 0, 0, 0, 0, 0, 0, 0, 0];
+        glyph.extend_from_slice(&[0x00, 0x21, 0x00, 0x07, 0, 0, 0, 0]);
+        glyph.extend_from_slice(&[0x00, 0x08, 0x00, 0x09, 0, 0, 0x40, 0x00]);
+        assert_eq!(components(&glyph), Some(vec![7, 9]));
+        assert_eq!(components(&[0x00, 0x01, 0, 0]), Some(Vec::new()));
+    }
+
+    #[test]
+    fn other_fonts_are_left_alone() {
+        assert_eq!(subset_font(b"OTTO\0\0\0\0\0\0\0\0", &BTreeSet::new()), None);
+        assert_eq!(subset_font(b"ttcf", &BTreeSet::new()), None);
+    }
+}
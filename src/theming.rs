@@ -1,5 +1,8 @@
 use macroquad::prelude::*;
 
+use crate::highlight::Token;
+
+#[derive(Clone, Copy, PartialEq)]
 pub struct Theme {
     pub background_color: Color,
     pub font_color: Color,
@@ -34,3 +37,80 @@ pub const LIGHT_THEME: Theme = Theme {
         a: 1f32,
     },
 };
+
+/// behind `==highlighted==` text, translucent so it works on dark and light themes
+pub const HIGHLIGHT_COLOR: Color = Color {
+    r: 1f32,
+    g: 0.85f32,
+    b: 0f32,
+    a: 0.35f32,
+};
+
+/// colors slide text is drawn with, highlighted code tokens have their own
+#[derive(Clone, Copy, PartialEq)]
+pub struct Palette {
+    pub text: Color,
+    pub keyword: Color,
+    pub type_name: Color,
+    pub string: Color,
+    pub number: Color,
+    pub comment: Color,
+    pub special: Color,
+}
+
+impl Theme {
+    /// palette of the theme, code colors are picked by how light the background is
+    pub fn palette(&self) -> Palette {
+        let bg = self.background_color;
+        let luminance = 0.2126 * bg.r + 0.7152 * bg.g + 0.0722 * bg.b;
+
+        if luminance < 0.5 {
+            Palette {
+                text: self.font_color,
+                keyword: Color::from_hex(0xff7b72),
+                type_name: Color::from_hex(0xffa657),
+                string: Color::from_hex(0xa5d6ff),
+                number: Color::from_hex(0x79c0ff),
+                comment: Color::from_hex(0x8b949e),
+                special: Color::from_hex(0xd2a8ff),
+            }
+        } else {
+            Palette {
+                text: self.font_color,
+                keyword: Color::from_hex(0xcf222e),
+                type_name: Color::from_hex(0x953800),
+                string: Color::from_hex(0x0a3069),
+                number: Color::from_hex(0x0550ae),
+                comment: Color::from_hex(0x6e7781),
+                special: Color::from_hex(0x8250df),
+            }
+        }
+    }
+}
+
+impl Palette {
+    /// the same colors faded towards the background, for lines out of focus
+    pub fn dimmed(&self) -> Palette {
+        let fade = |color: Color| Color { a: color.a * 0.3, ..color };
+        Palette {
+            text: fade(self.text),
+            keyword: fade(self.keyword),
+            type_name: fade(self.type_name),
+            string: fade(self.string),
+            number: fade(self.number),
+            comment: fade(self.comment),
+            special: fade(self.special),
+        }
+    }
+
+    pub fn token_color(&self, token: Token) -> Color {
+        match token {
+            Token::Keyword => self.keyword,
+            Token::Type => self.type_name,
+            Token::String => self.string,
+            Token::Number => self.number,
+            Token::Comment => self.comment,
+            Token::Special => self.special,
+        }
+    }
+}
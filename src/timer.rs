@@ -0,0 +1,267 @@
+use std::time::{Duration, Instant};
+
+use crate::rehearsal::*;
+use crate::slide::*;
+use crate::utils::*;
+use crate::watch::*;
+
+/// clock of the presentation that can be paused and reset, it keeps the time spent on every slide
+pub struct Timer {
+    /// time counted up to the last pause
+    counted: Duration,
+    /// when the clock was last started, None while it is paused
+    running: Option<Instant>,
+    /// slide the clock is counting for and the elapsed time when it came up
+    slide: usize,
+    slide_started: Duration,
+    /// time spent on every slide, a slide shown twice adds up
+    dwell: Vec<Duration>,
+}
+
+impl Timer {
+    pub fn new(slide_count: usize) -> Self {
+        Self {
+            counted: Duration::ZERO,
+            running: Some(Instant::now()),
+            slide: 0,
+            slide_started: Duration::ZERO,
+            dwell: vec![Duration::ZERO; slide_count],
+        }
+    }
+
+    /// time the clock ran, pauses not included
+    pub fn elapsed(&self) -> Duration {
+        self.counted + self.running.map_or(Duration::ZERO, |since| since.elapsed())
+    }
+
+    /// seconds on the current slide
+    pub fn on_slide(&self) -> u64 {
+        self.elapsed().saturating_sub(self.slide_started).as_secs()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.running.is_none()
+    }
+
+    pub fn toggle_pause(&mut self) {
+        match self.running.take() {
+            Some(since) => self.counted += since.elapsed(),
+            None => self.running = Some(Instant::now()),
+        }
+    }
+
+    /// back to zero with the slide times forgotten, a paused clock stays paused
+    pub fn reset(&mut self) {
+        self.counted = Duration::ZERO;
+        self.running = self.running.map(|_| Instant::now());
+        self.slide_started = Duration::ZERO;
+        self.dwell.iter_mut().for_each(|dwell| *dwell = Duration::ZERO);
+    }
+
+    /// the presentation moved to another slide
+    pub fn enter(&mut self, slide: usize) {
+        self.lap();
+        self.slide = slide;
+    }
+
+    /// the slides were reloaded, `slide` is where the presentation stays.
+    /// Times move with slides that moved, slides that are new or changed start from zero
+    pub fn reload(&mut self, old: &[Slide], new: &[Slide], slide: usize) {
+        self.lap();
+        let mut taken = vec![false; old.len()];
+        let dwell = new
+            .iter()
+            .enumerate()
+            .map(|(j, new_slide)| {
+                let found = old
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, old_slide)| !taken[*i] && same_slide(old_slide, new_slide))
+                    .min_by_key(|(i, _)| i.abs_diff(j))
+                    .map(|(i, _)| i);
+                found.map_or(Duration::ZERO, |i| {
+                    taken[i] = true;
+                    self.dwell.get(i).copied().unwrap_or_default()
+                })
+            })
+            .collect();
+        self.dwell = dwell;
+        self.slide = slide;
+    }
+
+    /// adds the time since the current slide came up to its dwell time
+    fn lap(&mut self) {
+        let elapsed = self.elapsed();
+        if let Some(dwell) = self.dwell.get_mut(self.slide) {
+            *dwell += elapsed.saturating_sub(self.slide_started);
+        }
+        self.slide_started = elapsed;
+    }
+
+    /// seconds spent on every slide so far
+    pub fn dwell(&mut self) -> Vec<u64> {
+        self.lap();
+        self.dwell.iter().map(Duration::as_secs).collect()
+    }
+
+    /// how long every slide took, slides never shown get a dash.
+    /// With rehearsals the times of the last one and the average are next to it
+    pub fn report(&mut self, slides: &[Slide], input_path: &str, comparisons: &[Rehearsed]) -> String {
+        let dwell = self.dwell();
+        let time = |seconds: Option<u64>| match seconds {
+            Some(seconds) if seconds > 0 => format_time(seconds),
+            _ => "-".to_string(),
+        };
+        let rehearsed = comparisons.iter().any(|comparison| comparison.average.is_some());
+
+        let mut report = format!("timing of {}\n", input_path);
+        if rehearsed {
+            report.push_str(&format!("{:>5}  {:>7}  {:>7}  {:>7}\n", "slide", "time", "last", "avg"));
+        }
+        for (i, slide) in slides.iter().enumerate() {
+            // first line of the slide with everything revealed, so it is recognized
+            let title = slide
+                .terminal_lines(slide.steps().saturating_sub(1), false)
+                .into_iter()
+                .find(|line| !line.trim().is_empty())
+                .unwrap_or_default();
+            let seconds = dwell.get(i).copied();
+            match comparisons.get(i).filter(|_| rehearsed) {
+                Some(comparison) => report.push_str(&format!(
+                    "{:>5}  {:>7}  {:>7}  {:>7}  {}\n",
+                    i + 1,
+                    time(seconds),
+                    time(comparison.last),
+                    time(comparison.average),
+                    title.trim()
+                )),
+                None => report.push_str(&format!("{:>5}  {:>7}  {}\n", i + 1, time(seconds), title.trim())),
+            }
+        }
+        report.push_str(&format!("{:>5}  {:>7}\n", "total", format_time(self.elapsed().as_secs())));
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::markup::parse_line;
+
+    /// a paused timer, its clock is moved by hand
+    fn stopped(slide_count: usize) -> Timer {
+        let mut timer = Timer::new(slide_count);
+        timer.toggle_pause();
+        timer.counted = Duration::ZERO;
+        timer
+    }
+
+    fn at(timer: &mut Timer, seconds: u64) {
+        timer.counted = Duration::from_secs(seconds);
+    }
+
+    /// two slides with a blank line above their title
+    fn slides() -> [Slide; 2] {
+        ["Hello", "World"].map(|title| Slide {
+            lines: vec![parse_line("").0, parse_line(title).0],
+            ..Slide::plain(1, title)
+        })
+    }
+
+    #[test]
+    fn dwell_piles_up_over_slide_changes() {
+        let mut timer = stopped(3);
+        at(&mut timer, 10);
+        timer.enter(1);
+        at(&mut timer, 25);
+        assert_eq!(timer.on_slide(), 15);
+        timer.enter(2);
+        at(&mut timer, 27);
+        assert_eq!(timer.dwell(), [10, 15, 2]);
+
+        // asking for the times does not count anything twice
+        assert_eq!(timer.dwell(), [10, 15, 2]);
+        assert_eq!(timer.on_slide(), 0);
+    }
+
+    #[test]
+    fn a_slide_shown_twice_adds_up() {
+        let mut timer = stopped(2);
+        at(&mut timer, 10);
+        timer.enter(1);
+        at(&mut timer, 14);
+        timer.enter(0);
+        at(&mut timer, 19);
+        timer.enter(1);
+        at(&mut timer, 20);
+        assert_eq!(timer.dwell(), [15, 5]);
+    }
+
+    #[test]
+    fn reset_keeps_the_clock_paused_or_running() {
+        let mut timer = stopped(2);
+        at(&mut timer, 30);
+        timer.enter(1);
+        timer.reset();
+        assert!(timer.is_paused());
+        assert_eq!(timer.elapsed(), Duration::ZERO);
+        assert_eq!(timer.dwell(), [0, 0]);
+
+        let mut timer = Timer::new(2);
+        timer.reset();
+        assert!(!timer.is_paused());
+    }
+
+    #[test]
+    fn report_lists_every_slide() {
+        let slides = slides();
+        let mut timer = stopped(2);
+        at(&mut timer, 65);
+        timer.enter(1);
+
+        assert_eq!(
+            timer.report(&slides, "talk.rh", &[]),
+            "timing of talk.rh\n    1     1:05  Hello\n    2        -  World\ntotal     1:05\n"
+        );
+    }
+
+    #[test]
+    fn report_has_rehearsal_columns_once_there_are_rehearsals() {
+        let slides = slides();
+        let mut timer = stopped(2);
+        at(&mut timer, 65);
+        timer.enter(1);
+        at(&mut timer, 70);
+        let comparisons = [
+            Rehearsed { last: Some(50), average: Some(55), last_start: Some(0) },
+            Rehearsed { last: None, average: None, last_start: None },
+        ];
+
+        assert_eq!(
+            timer.report(&slides, "talk.rh", &comparisons),
+            "timing of talk.rh\n\
+            slide     time     last      avg\n\
+            \x20   1     1:05     0:50     0:55  Hello\n\
+            \x20   2     0:05        -        -  World\n\
+            total     1:10\n"
+        );
+        // rehearsals without any time of these slides leave the columns out
+        let none = [Rehearsed::default(); 2];
+        assert!(timer.report(&slides, "talk.rh", &none).starts_with("timing of talk.rh\n    1     1:05  Hello\n"));
+    }
+
+    #[test]
+    fn reload_keeps_times_with_their_slides() {
+        let old = [Slide::plain(1, "a"), Slide::plain(2, "b"), Slide::plain(3, "c")];
+        let new = [Slide::plain(1, "c"), Slide::plain(2, "a"), Slide::plain(3, "b changed"), Slide::plain(4, "d")];
+
+        let mut timer = Timer::new(old.len());
+        timer.toggle_pause();
+        timer.counted = Duration::ZERO;
+        timer.dwell = [10, 20, 30].map(Duration::from_secs).to_vec();
+
+        timer.reload(&old, &new, 1);
+        assert_eq!(timer.dwell(), [30, 10, 0, 0]);
+        assert_eq!(timer.slide, 1);
+    }
+}
@@ -0,0 +1,170 @@
+use macroquad::prelude::*;
+use macroquad_canvas::Canvas2D;
+
+use crate::scene::*;
+use crate::slide::*;
+
+/// slide change that is still running
+#[derive(Debug, PartialEq)]
+struct Running {
+    transition: Transition,
+    duration: f32,
+    elapsed: f32,
+    /// slide and step that are being replaced
+    from: (usize, usize),
+}
+
+impl Running {
+    /// None when there is nothing to show, without an effect or without time for it
+    fn new(transition: Transition, duration: f32, from: (usize, usize)) -> Option<Self> {
+        match transition {
+            Transition::None => None,
+            _ if duration <= 0f32 => None,
+            _ => Some(Self { transition, duration, elapsed: 0f32, from }),
+        }
+    }
+
+    /// moves on by dt seconds, returns the eased progress
+    fn advance(&mut self, dt: f32) -> f32 {
+        self.elapsed += dt;
+        ease((self.elapsed / self.duration).min(1f32))
+    }
+
+    fn is_done(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+}
+
+/// smoothstep, eases in and out
+fn ease(progress: f32) -> f32 {
+    progress * progress * (3f32 - 2f32 * progress)
+}
+
+/// slide a layer of a transition shows
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Layer {
+    From,
+    To,
+}
+
+/// what a transition draws at eased progress t, bottom first:
+/// the slide, its x offset in screen widths and its alpha
+fn layers(transition: Transition, t: f32) -> Vec<(Layer, f32, f32)> {
+    match transition {
+        Transition::None => vec![(Layer::To, 0f32, 1f32)],
+        Transition::Fade => vec![(Layer::From, 0f32, 1f32), (Layer::To, 0f32, t)],
+        Transition::SlideLeft => vec![(Layer::From, -t, 1f32), (Layer::To, 1f32 - t, 1f32)],
+        Transition::SlideRight => vec![(Layer::From, t, 1f32), (Layer::To, t - 1f32, 1f32)],
+        // over the background, the old slide fades out in the first half and the new one in after it
+        Transition::FadeThroughBackground if t < 0.5 => vec![(Layer::From, 0f32, 1f32 - t * 2f32)],
+        Transition::FadeThroughBackground => vec![(Layer::To, 0f32, t * 2f32 - 1f32)],
+    }
+}
+
+/// draws the outgoing and incoming slide into canvases of their own and blends them.
+/// Exports draw slides with Scene::draw directly, so they never have transitions
+pub struct Transitions {
+    from_canvas: Canvas2D,
+    to_canvas: Canvas2D,
+    running: Option<Running>,
+}
+
+impl Transitions {
+    pub fn new(virtual_screen_size: Vec2) -> Self {
+        Self {
+            from_canvas: Canvas2D::new(virtual_screen_size.x, virtual_screen_size.y),
+            to_canvas: Canvas2D::new(virtual_screen_size.x, virtual_screen_size.y),
+            running: None,
+        }
+    }
+
+    /// starts the transition into slide `to`, a running one is dropped
+    pub fn start(&mut self, scene: &Scene, slides: &[Slide], from: (usize, usize), to: usize) {
+        self.running = slides.get(to).and_then(|slide| {
+            let (transition, duration) = scene.slide_transition(slide);
+            Running::new(transition, duration, from)
+        });
+    }
+
+    pub fn stop(&mut self) {
+        self.running = None;
+    }
+
+    /// draws the current slide into the camera of `target`, blended with the one before it while
+    /// a transition runs. The camera is left at the default one
+    pub fn draw(&mut self, scene: &Scene, slides: &[Slide], current: (usize, usize), target: &Canvas2D) {
+        let Some(running) = &mut self.running else {
+            set_camera(&target.camera);
+            scene.draw(slides, current.0, current.1);
+            set_default_camera();
+            return;
+        };
+
+        let t = running.advance(get_frame_time());
+        let transition = running.transition;
+        let from = running.from;
+        if running.is_done() {
+            self.running = None;
+        }
+
+        set_camera(&self.from_canvas.camera);
+        scene.draw(slides, from.0, from.1);
+        set_camera(&self.to_canvas.camera);
+        scene.draw(slides, current.0, current.1);
+
+        set_camera(&target.camera);
+        let size = scene.virtual_screen_size;
+        if transition == Transition::FadeThroughBackground {
+            clear_background(scene.theme.background_color);
+        }
+        for (layer, x, alpha) in layers(transition, t) {
+            let canvas = match layer {
+                Layer::From => &self.from_canvas,
+                Layer::To => &self.to_canvas,
+            };
+            draw_texture_ex(
+                canvas.get_texture(),
+                x * size.x,
+                0f32,
+                Color { a: alpha, ..WHITE },
+                DrawTextureParams { dest_size: Some(size), ..Default::default() },
+            );
+        }
+        set_default_camera();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn easing_starts_and_ends_slowly() {
+        assert_eq!(ease(0f32), 0f32);
+        assert_eq!(ease(0.5), 0.5);
+        assert_eq!(ease(1f32), 1f32);
+        assert!(ease(0.1) < 0.1 && ease(0.9) > 0.9);
+    }
+
+    #[test]
+    fn runs_for_its_duration() {
+        assert_eq!(Running::new(Transition::None, 0.4, (0, 0)), None);
+        assert_eq!(Running::new(Transition::Fade, 0f32, (0, 0)), None);
+
+        let mut running = Running::new(Transition::Fade, 0.4, (2, 1)).unwrap();
+        assert_eq!(running.advance(0.2), 0.5);
+        assert!(!running.is_done());
+        // a long frame does not overshoot
+        assert_eq!(running.advance(0.5), 1f32);
+        assert!(running.is_done());
+    }
+
+    #[test]
+    fn layers_meet_in_the_middle() {
+        assert_eq!(layers(Transition::SlideLeft, 0.25), [(Layer::From, -0.25, 1f32), (Layer::To, 0.75, 1f32)]);
+        assert_eq!(layers(Transition::SlideRight, 1f32), [(Layer::From, 1f32, 1f32), (Layer::To, 0f32, 1f32)]);
+        assert_eq!(layers(Transition::Fade, 0.3), [(Layer::From, 0f32, 1f32), (Layer::To, 0f32, 0.3)]);
+        assert_eq!(layers(Transition::FadeThroughBackground, 0.25), [(Layer::From, 0f32, 0.5)]);
+        assert_eq!(layers(Transition::FadeThroughBackground, 0.75), [(Layer::To, 0f32, 0.5)]);
+    }
+}
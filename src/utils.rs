@@ -1,8 +1,12 @@
 use macroquad::prelude::*;
-use regex::Regex;
-use std::process::Command;
+use std::cell::RefCell;
+use std::collections::HashMap;
 
+use crate::diagnostic::*;
+use crate::document::*;
+use crate::markup::*;
 use crate::slide::*;
+use crate::style::*;
 
 /// println that exists only in debug mod
 #[macro_export]
@@ -10,280 +14,401 @@ macro_rules! debug_println {
     ($($arg:tt)*) => (if ::std::cfg!(debug_assertions) { ::std::println!($($arg)*); })
 }
 
-pub enum BackgroundMode {
-    Fill,
-    Fit
-}
-
-pub async fn parse(path: &str, virtual_screen_size: &Vec2, font: &Font, mono_font: &Font) -> Vec<Slide> {
-    let content = std::fs::read_to_string(path).expect("Failed to read file");
-
-    let mut fixed = String::new();
-    let mut in_code = false;
-
-    for line in content.lines() {
-        let trimmed = line.trim_start();
+/// reads presentation file and lays it out into renderable slides.
+/// Slides are only usable when none of the returned diagnostics is an error
+pub fn parse(
+    path: &str,
+    virtual_screen_size: &Vec2,
+    faces: &FontFaces<Font>,
+    mono_font: &Font,
+    wrap: bool,
+) -> (Vec<Slide>, Vec<Diagnostic>) {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) => return (Vec::new(), vec![Diagnostic::file_error(format!("failed to read `{}`: {}", path, e))]),
+    };
 
-        if trimmed.starts_with("```") {
-            in_code = !in_code;
-            fixed.push_str(line);
-            fixed.push('\n');
-            continue;
-        }
+    let (deck, mut diagnostics) = parse_deck(&content);
+    if has_errors(&diagnostics) {
+        return (Vec::new(), diagnostics);
+    }
 
-        if in_code {
-            // replace truly empty lines inside code blocks to avoid splitting
-            if trimmed.is_empty() {
-                fixed.push_str("<!--EMPTY-CODE-LINE-->\n");
-            } else {
-                fixed.push_str(line);
-                fixed.push('\n');
-            }
-        } else {
-            fixed.push_str(line);
-            fixed.push('\n');
+    match layout(&deck, virtual_screen_size, faces, mono_font, wrap) {
+        Ok(slides) => (slides, diagnostics),
+        Err(mut errors) => {
+            diagnostics.append(&mut errors);
+            (Vec::new(), diagnostics)
         }
     }
+}
 
+/// reads and parses a presentation without laying it out, None if it can not be used
+pub fn read_deck(path: &str) -> (Option<Deck>, Vec<Diagnostic>) {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) => return (None, vec![Diagnostic::file_error(format!("failed to read `{}`: {}", path, e))]),
+    };
 
-    let mut slides = Vec::new();
-    let re = Regex::new(r"\n\s*\n+").unwrap();
-    let mut paragraphs = re.split(&fixed);
-    // let mut paragraphs = re.split(&content);
-
-    let mut slide_num = 1;
+    let (deck, diagnostics) = parse_deck(&content);
+    if has_errors(&diagnostics) { (None, diagnostics) } else { (Some(deck), diagnostics) }
+}
 
-    while let Some(paragraph) = paragraphs.next() {
-        let paragraph = paragraph.replace("<!--EMPTY-CODE-LINE-->", "");
-        let lines: Vec<&str> = paragraph.lines().collect();
+/// turns parsed slides into renderable ones: loads textures, wraps text if asked to and fits fonts
+pub fn layout(
+    deck: &Deck,
+    virtual_screen_size: &Vec2,
+    faces: &FontFaces<Font>,
+    mono_font: &Font,
+    wrap: bool,
+) -> Result<Vec<Slide>, Vec<Diagnostic>> {
+    let mut slides = Vec::new();
+    let mut errors = Vec::new();
+    // slide backgrounds by path, section dividers tend to share one
+    let mut backgrounds: Vec<(String, Texture2D)> = Vec::new();
+    let cached_faces = CachedFaces::new(faces);
+    let cached_mono_font = MeasureCache::new(mono_font);
+
+    for spec in &deck.slides {
+        let texture = match &spec.image {
+            Some(img_path) => match decode_image(img_path) {
+                Ok(image) => Some(Texture2D::from_image(&image)),
+                Err(e) => {
+                    errors.push(image_diagnostic(deck, spec, img_path, &e));
+                    continue;
+                }
+            },
+            None => None,
+        };
+
+        let style = match slide_style(deck, spec, &mut backgrounds) {
+            Ok(style) => style,
+            Err(e) => {
+                errors.push(e);
+                continue;
+            }
+        };
 
-        if lines.iter().all(|line| line.trim().is_empty()) {
-            continue;
-        }
+        let wrap = spec.directives.wrap.unwrap_or(wrap);
+        slides.push(Slide::new(
+            spec,
+            texture,
+            style,
+            wrap,
+            virtual_screen_size,
+            &cached_faces.faces(),
+            &cached_mono_font,
+        ));
+    }
 
-        // Empty slide
-        if lines[0].starts_with('\\') {
-            let comments = lines
-                .iter()
-                .skip(1)
-                .filter(|l| l.trim_start().starts_with('|'))
-                .map(|l| *l)
-                .collect::<Vec<&str>>()
-                .join("\n");
-
-            slides.push(Slide::new(
-                slide_num,
-                SlideType::Empty,
-                None,
-                None,
-                Some(comments),
-                virtual_screen_size,
-                font,
-                mono_font,
-            ));
-            slide_num += 1;
-            continue;
-        }
+    if errors.is_empty() { Ok(slides) } else { Err(errors) }
+}
 
-        // Image slide
-        if lines[0].starts_with('@') {
-            let img_path = lines[0][1..].trim();
-            let texture = load_texture(img_path).await.expect("Failed to load image");
-
-            let mut text_lines = Vec::new();
-            let mut comment_lines = Vec::new();
-
-            for line in lines.iter().skip(1) {
-                let l = line.trim_start();
-                if l.starts_with('|') {
-                    comment_lines.push(*line);
-                } else if l.starts_with('~') {
-                    text_lines.push("");
-                } else if !l.is_empty() {
-                    text_lines.push(*line);
+/// resolves the directives of a slide, background images are loaded once per path
+fn slide_style(deck: &Deck, spec: &SlideSpec, backgrounds: &mut Vec<(String, Texture2D)>) -> Result<SlideStyle, Diagnostic> {
+    let directives = &spec.directives;
+
+    let background = match &directives.background {
+        None => None,
+        Some(BackgroundOverride::Hidden) => Some(SlideBackground::Hidden),
+        Some(BackgroundOverride::Image { path, mode, line }) => {
+            let texture = match backgrounds.iter().find(|(p, _)| p == path) {
+                Some((_, texture)) => texture.clone(),
+                None => {
+                    let image = decode_image(path).map_err(|e| image_diagnostic_at(deck, *line, spec.num, path, &e))?;
+                    let texture = Texture2D::from_image(&image);
+                    backgrounds.push((path.clone(), texture.clone()));
+                    texture
                 }
-            }
-
-            let slide_type:SlideType;
-            if text_lines.is_empty() {
-                slide_type = SlideType::Image;
-            } else {
-                slide_type = SlideType::TextImage;
             };
-
-            slides.push(Slide::new(
-                    slide_num,
-                    slide_type,
-                    if text_lines.is_empty() { None } else { Some(text_lines.join("\n")) },
-                    Some(texture),
-                    if comment_lines.is_empty() { None } else { Some(comment_lines.join("\n")) },
-                    virtual_screen_size,
-                    font,
-                    mono_font,
-            ));
-
-            slide_num += 1;
-            continue;
+            Some(SlideBackground::Image { texture, mode: *mode })
         }
+    };
 
+    Ok(SlideStyle { background, ..directive_style(directives) })
+}
 
-        // Text slides
-        let mut text_lines = Vec::new();
-        let mut comment_lines = Vec::new();
+/// style of a slide from its directives, without the background image that needs loading
+pub fn directive_style(directives: &Directives) -> SlideStyle {
+    SlideStyle {
+        bg_color: directives.bg_color.as_deref().and_then(|hex| parse_hex_color(hex).ok()),
+        font_color: directives.font_color.as_deref().and_then(|hex| parse_hex_color(hex).ok()),
+        background: None,
+        numbering: directives.numbering,
+        align: directives.align,
+        valign: directives.valign,
+        transition: directives.transition,
+        transition_duration: directives.transition_duration,
+    }
+}
 
-        // Code
-        if lines[0].starts_with("```") {
-            let mut code_block_ended = false;
-            for line in lines.iter().skip(1) {
-                let line = line.trim_end();
-                if line.starts_with("```") { code_block_ended = true; }
-                if !code_block_ended { text_lines.push(line); }
-                else { if line.starts_with('|') { comment_lines.push(line); }}
-            }
+/// points at the `@` line of a slide whose image could not be loaded
+pub fn image_diagnostic(deck: &Deck, spec: &SlideSpec, img_path: &str, reason: &str) -> Diagnostic {
+    image_diagnostic_at(deck, spec.span.start_line, spec.num, img_path, reason)
+}
+
+/// points at the image path on the given line, e.g. of a `% background:` directive
+pub fn image_diagnostic_at(deck: &Deck, line_no: usize, slide_num: u32, img_path: &str, reason: &str) -> Diagnostic {
+    let line = deck.source_line(line_no);
+    let column = line.find(img_path).map(|i| line[..i].chars().count() + 1).unwrap_or(1);
+
+    Diagnostic::error(
+        format!("failed to load image `{}`: {}", img_path, reason),
+        line_no,
+        column,
+        img_path.chars().count(),
+        line,
+    )
+    .in_slide(slide_num)
+    .with_help("image paths are relative to the directory reiha is started from")
+}
 
-            slides.push(Slide::new(
-                    slide_num,
-                    SlideType::Code,
-                    Some(text_lines.join("\n")),
-                    None,
-                    if comment_lines.is_empty() {
-                        None
-                    } else {
-                        Some(comment_lines.join("\n"))
-                    },
-                    virtual_screen_size,
-                    font,
-                    mono_font,
-            ));
-
-            slide_num += 1;
-            continue;
+/// loads font from path if given, embedded fallback otherwise
+pub fn try_load_font(path: Option<&str>, fallback: &[u8]) -> Result<Font, String> {
+    match path {
+        Some(path) => {
+            let data = std::fs::read(path).map_err(|e| format!("Failed to read font file `{}`: {}", path, e))?;
+            load_ttf_font_from_bytes(&data).map_err(|e| format!("Failed to load font `{}`: {:?}", path, e))
         }
+        None => Ok(load_ttf_font_from_bytes(fallback).unwrap()),
+    }
+}
 
+/// reads and decodes an image without touching the GPU
+pub fn decode_image(path: &str) -> Result<Image, String> {
+    let bytes = std::fs::read(path).map_err(|e| e.to_string())?;
 
-        // Text
-        for line in lines {
-            if line.trim_start().starts_with('|') {
-                comment_lines.push(line);
-            } else if line.trim_start().starts_with('~') {
-                text_lines.push("");
-            } else {
-                text_lines.push(line);
-            }
-        }
+    match Image::from_file_with_format(&bytes, None) {
+        Ok(image) => Ok(image),
+        Err(macroquad::Error::ImageError(e)) => Err(e.to_string()),
+        Err(e) => Err(format!("{:?}", e)),
+    }
+}
 
-        // if no text: it's source comment that is not going to be rendered anywhere
-        if text_lines.is_empty() && !comment_lines.is_empty() {
-            continue;
-        }
+/// anything that can tell how wide a line of text is,
+/// lets font fitting run without a window
+pub trait MeasureText {
+    /// width of a single line, trailing spaces included
+    fn measure_width(&self, text: &str, font_size: u16) -> f32;
+}
 
-        slides.push(Slide::new(
-            slide_num,
-            SlideType::Text,
-            Some(text_lines.join("\n")),
-            None,
-            if comment_lines.is_empty() {
-                None
-            } else {
-                Some(comment_lines.join("\n"))
-            },
-            virtual_screen_size,
-            font,
-            mono_font,
-        ));
+impl MeasureText for Font {
+    fn measure_width(&self, text: &str, font_size: u16) -> f32 {
+        measure_text(text, Some(self), font_size, 1f32).width
+    }
+}
 
-        slide_num += 1;
+/// mirrors measure_text using glyph metrics only, no GPU atlas involved
+impl MeasureText for fontdue::Font {
+    fn measure_width(&self, text: &str, font_size: u16) -> f32 {
+        text.chars().map(|character| self.metrics(character, font_size as f32).advance_width).sum()
     }
+}
+
+/// every character half as wide as the font size, for tests that fit text without a font
+#[cfg(test)]
+pub struct Monospace;
 
-    slides
+#[cfg(test)]
+impl MeasureText for Monospace {
+    fn measure_width(&self, text: &str, font_size: u16) -> f32 {
+        text.chars().count() as f32 * font_size as f32 / 2f32
+    }
 }
 
-pub fn find_max_font_size(
-    text: &str,
-    font: Option<&Font>,
-    font_scale: f32,
-    line_distance_factor: Option<f32>,
+/// bounds of the font size search, the page script searches the same range
+pub const MIN_FONT_SIZE: u16 = 4;
+pub const MAX_FONT_SIZE: u16 = 1024;
+
+/// largest font size the lines fit into 96% of the area with, styled runs are measured with their own face.
+/// Text only grows with the font size, so a binary search finds it,
+/// smaller than MIN_FONT_SIZE is never returned even if it does not fit
+pub fn find_max_font_size<M: MeasureText + ?Sized>(
+    lines: &[Vec<Run>],
+    faces: &FontFaces<M>,
     virtual_screen_size: &Vec2,
 ) -> u16 {
-    let screen_w = virtual_screen_size.x;
-    let screen_h = virtual_screen_size.y;
-    let target_width = screen_w * 0.96;
-    let target_height = screen_h * 0.96;
-
-    // Determine step size based on text length
-    let text_len = text.chars().count();
-    let mut step: u16 = match text_len {
-        0..=3 => 96,
-        4..=7 => 64,
-        8..=15 => 48,
-        _ => 32,
+    let target_width = virtual_screen_size.x * 0.96;
+    let target_height = virtual_screen_size.y * 0.96;
+    let fits = |font_size: u16| {
+        let dim = measure_lines(lines, faces, font_size);
+        dim.x <= target_width && dim.y <= target_height
     };
 
-    let mut depth = 4;
+    // the height alone already caps the size, only the widths are searched
+    let by_height = (target_height / lines.len().max(1) as f32) as u16;
+    let mut low = MIN_FONT_SIZE;
+    let mut high = by_height.clamp(MIN_FONT_SIZE, MAX_FONT_SIZE);
 
-    let mut font_size: u16 = 4u16;
+    while low < high {
+        let mid = low + (high - low).div_ceil(2);
+        if fits(mid) {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+
+    low
+}
 
-    loop {
-        let dim = measure_multiline_text(text, font, font_size, font_scale, line_distance_factor);
+/// remembers the widths a font measured. Fitting measures every line at several sizes
+/// and slides repeat lines and words, so a deck is mostly measured once
+pub struct MeasureCache<'a, M: ?Sized> {
+    font: &'a M,
+    /// widths by font size, then text
+    widths: RefCell<HashMap<u16, HashMap<String, f32>>>,
+}
 
-        if dim.width > target_width || dim.height > target_height {
-            font_size -= step;
-            step = step / 2;
-            depth -= 1;
-            debug_println!("decreased to {}", font_size);
-            if depth <= 0 {
-                break;
+impl<'a, M: MeasureText + ?Sized> MeasureCache<'a, M> {
+    pub fn new(font: &'a M) -> Self {
+        Self { font, widths: RefCell::new(HashMap::new()) }
+    }
+}
+
+impl<M: MeasureText + ?Sized> MeasureText for MeasureCache<'_, M> {
+    fn measure_width(&self, text: &str, font_size: u16) -> f32 {
+        let mut widths = self.widths.borrow_mut();
+        let widths = widths.entry(font_size).or_default();
+        match widths.get(text) {
+            Some(width) => *width,
+            None => {
+                let width = self.font.measure_width(text, font_size);
+                widths.insert(text.to_string(), width);
+                width
             }
         }
+    }
+}
 
-        font_size += step;
-
-        debug_println!("{}", font_size);
+/// a measure cache for every face of a font
+pub struct CachedFaces<'a, M: ?Sized> {
+    regular: MeasureCache<'a, M>,
+    bold: Option<MeasureCache<'a, M>>,
+    italic: Option<MeasureCache<'a, M>>,
+}
 
-        if font_size >= 1024 {
-            break;
+impl<'a, M: MeasureText + ?Sized> CachedFaces<'a, M> {
+    pub fn new(faces: &FontFaces<'a, M>) -> Self {
+        Self {
+            regular: MeasureCache::new(faces.regular),
+            bold: faces.bold.map(MeasureCache::new),
+            italic: faces.italic.map(MeasureCache::new),
         }
     }
 
-    println!(".");
-
-    font_size
+    pub fn faces(&self) -> FontFaces<'_, MeasureCache<'a, M>> {
+        FontFaces {
+            regular: &self.regular,
+            bold: self.bold.as_ref(),
+            italic: self.italic.as_ref(),
+        }
+    }
 }
 
 /// parses hex color to Color
 pub fn parse_hex_color(s: &str) -> Result<Color, ()> {
-    let s = s.trim_start_matches('#');
-    if s.len() != 6 {
-        return Err(());
-    }
-    if let Ok(rgb) = u32::from_str_radix(s, 16) {
-        let r = ((rgb >> 16) & 0xFF) as f32 / 255.0;
-        let g = ((rgb >> 8) & 0xFF) as f32 / 255.0;
-        let b = (rgb & 0xFF) as f32 / 255.0;
-        Ok(Color { r, g, b, a: 1.0 })
+    parse_hex_rgb(s).map(Color::from_hex).ok_or(())
+}
+
+/// Color to hex without the leading #, inverse of parse_hex_color
+pub fn to_hex_color(color: Color) -> String {
+    format!(
+        "{:02x}{:02x}{:02x}",
+        (color.r * 255f32).round() as u8,
+        (color.g * 255f32).round() as u8,
+        (color.b * 255f32).round() as u8
+    )
+}
+
+/// minutes and seconds like 12:05, with hours in front from an hour on
+pub fn format_time(secs: u64) -> String {
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
     } else {
-        Err(())
+        format!("{}:{:02}", secs / 60, secs % 60)
     }
 }
 
-pub fn print_time(elapsed_secs: Option<u64>) {
-    if let Some(secs) = elapsed_secs {
-        let minutes = secs / 60;
-        let seconds = secs % 60;
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    /// Monospace that counts how often it measured
+    struct Counting(Cell<usize>);
 
-        println!("[time {:01}:{:02}]", minutes, seconds);
+    impl MeasureText for Counting {
+        fn measure_width(&self, text: &str, font_size: u16) -> f32 {
+            self.0.set(self.0.get() + 1);
+            Monospace.measure_width(text, font_size)
+        }
     }
-}
 
-pub fn clear_screen() {
-    if cfg!(target_os = "windows") {
-        Command::new("cmd")
-            .args(["/C", "cls"])
-            .status()
-            .expect("Failed to clear screen");
-    } else {
-        Command::new("clear")
-            .status()
-            .expect("Failed to clear screen");
+    /// every character as wide as the font size
+    struct Wide;
+
+    impl MeasureText for Wide {
+        fn measure_width(&self, text: &str, font_size: u16) -> f32 {
+            text.chars().count() as f32 * font_size as f32
+        }
+    }
+
+    fn lines(text: &str) -> Vec<Vec<Run>> {
+        text.lines().map(|line| parse_line(line).0).collect()
+    }
+
+    fn fit(text: &str, width: f32, height: f32) -> u16 {
+        find_max_font_size(&lines(text), &FontFaces::regular(&Monospace), &vec2(width, height))
+    }
+
+    #[test]
+    fn fits_the_width_and_the_height() {
+        // 960px for 4 characters of half the size
+        assert_eq!(fit("abcd", 1000f32, 1000f32), 480);
+        assert_eq!(fit("abcdefghij\nab", 1000f32, 1000f32), 192);
+        assert_eq!(fit("a\nb\nc\nd", 1000f32, 1000f32), 240);
+        // trailing spaces do not count
+        assert_eq!(fit("abcd    ", 1000f32, 1000f32), 480);
+    }
+
+    #[test]
+    fn stays_within_the_bounds() {
+        assert_eq!(fit("a", 5000f32, 5000f32), MAX_FONT_SIZE);
+        assert_eq!(fit(&"a".repeat(1000), 100f32, 100f32), MIN_FONT_SIZE);
+    }
+
+    #[test]
+    fn finds_what_trying_every_size_finds() {
+        let area = vec2(1280f32, 720f32);
+        for text in ["a", "hello world", "one\ntwo three\nfour five six seven", "*bold* and _italic_ text"] {
+            let lines = lines(text);
+            let faces = FontFaces::regular(&Monospace);
+            let fits = |size: u16| {
+                let dim = measure_lines(&lines, &faces, size);
+                dim.x <= area.x * 0.96 && dim.y <= area.y * 0.96
+            };
+            let expected = (MIN_FONT_SIZE..=MAX_FONT_SIZE).rev().find(|size| fits(*size)).unwrap();
+            assert_eq!(find_max_font_size(&lines, &faces, &area), expected, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn measures_a_few_sizes_only() {
+        let counting = Counting(Cell::new(0));
+        find_max_font_size(&lines("a line of text"), &FontFaces::regular(&counting), &vec2(1920f32, 1080f32));
+        // a binary search over at most 1024 sizes
+        assert!(counting.0.get() <= 11, "{} measurements", counting.0.get());
+    }
+
+    #[test]
+    fn styled_runs_use_their_face() {
+        let regular: &dyn MeasureText = &Monospace;
+        let bold: &dyn MeasureText = &Wide;
+        let faces = FontFaces { regular, bold: Some(bold), italic: None };
+        // "ab " is 1.5 and the bold "cd" 2 font sizes wide
+        assert_eq!(find_max_font_size(&lines("ab *cd*"), &faces, &vec2(1000f32, 1000f32)), 274);
+        // faux bold is the regular face drawn twice, a little wider
+        assert_eq!(find_max_font_size(&lines("ab *cd*"), &FontFaces::regular(regular), &vec2(1000f32, 1000f32)), 378);
     }
 }
@@ -0,0 +1,205 @@
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use crate::document::*;
+use crate::settings::*;
+use crate::slide::*;
+
+/// seconds between two looks at the watched files
+const POLL_INTERVAL: f32 = 0.5;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Reload {
+    /// presentation or one of its images changed
+    Slides,
+    /// a font or the background image changed, slides have to be fitted again too
+    Everything,
+}
+
+/// polls modification times of the presentation and everything it loads
+pub struct Watcher {
+    sources: Vec<(PathBuf, Option<SystemTime>)>,
+    assets: Vec<(PathBuf, Option<SystemTime>)>,
+    timer: f32,
+}
+
+fn modified(path: &PathBuf) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn stamp(paths: Vec<PathBuf>) -> Vec<(PathBuf, Option<SystemTime>)> {
+    paths.into_iter().map(|path| {
+        let time = modified(&path);
+        (path, time)
+    }).collect()
+}
+
+/// true if any file got modified, created or removed since the last look
+fn refresh(files: &mut [(PathBuf, Option<SystemTime>)]) -> bool {
+    let mut changed = false;
+    for (path, time) in files.iter_mut() {
+        let now = modified(path);
+        if now != *time {
+            *time = now;
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// presentation file followed by every `@image` and slide background it references
+fn source_paths(input_path: &str) -> Vec<PathBuf> {
+    let mut paths = vec![PathBuf::from(input_path)];
+
+    if let Ok(content) = std::fs::read_to_string(input_path) {
+        let (deck, _) = parse_deck(&content);
+        for spec in deck.slides {
+            let background = match spec.directives.background {
+                Some(BackgroundOverride::Image { path, .. }) => Some(path),
+                _ => None,
+            };
+            for image in spec.image.filter(|i| !i.is_empty()).into_iter().chain(background) {
+                let image = PathBuf::from(image);
+                if !paths.contains(&image) {
+                    paths.push(image);
+                }
+            }
+        }
+    }
+
+    paths
+}
+
+fn asset_paths(settings: &Settings) -> Vec<PathBuf> {
+    [
+        &settings.font_path.value,
+        &settings.mono_font_path.value,
+        &settings.bold_font_path.value,
+        &settings.italic_font_path.value,
+        &settings.bg_image_path.value,
+    ]
+        .into_iter()
+        .flatten()
+        .map(PathBuf::from)
+        .collect()
+}
+
+impl Watcher {
+    pub fn new(input_path: &str, settings: &Settings) -> Self {
+        Self {
+            sources: stamp(source_paths(input_path)),
+            assets: stamp(asset_paths(settings)),
+            timer: POLL_INTERVAL,
+        }
+    }
+
+    /// call once per frame, tells what has to be reloaded
+    pub fn poll(&mut self, input_path: &str, frame_time: f32) -> Option<Reload> {
+        self.timer -= frame_time;
+        if self.timer > 0f32 {
+            return None;
+        }
+        self.timer = POLL_INTERVAL;
+
+        let assets_changed = refresh(&mut self.assets);
+        let sources_changed = refresh(&mut self.sources);
+
+        if sources_changed {
+            // the presentation may reference other images now
+            let paths = source_paths(input_path);
+            if paths.iter().ne(self.sources.iter().map(|(path, _)| path)) {
+                self.sources = stamp(paths);
+            }
+        }
+
+        match (assets_changed, sources_changed) {
+            (true, _) => Some(Reload::Everything),
+            (false, true) => Some(Reload::Slides),
+            (false, false) => None,
+        }
+    }
+}
+
+/// `key: value` pairs of the front matter, line numbers left out so moving it around changes nothing
+pub fn front_matter_settings(input_path: &str) -> Vec<(String, String)> {
+    let Ok(content) = std::fs::read_to_string(input_path) else {
+        return Vec::new();
+    };
+    let (entries, _, _) = parse_front_matter(&content);
+    entries.into_iter().map(|entry| (entry.key, entry.value)).collect()
+}
+
+/// the slides show the same thing, that is how a slide is recognized after a reload
+pub fn same_slide(a: &Slide, b: &Slide) -> bool {
+    a.slide_type == b.slide_type && a.text == b.text && a.image_path == b.image_path && a.comments == b.comments
+}
+
+/// index in the reloaded slides that shows what was on screen before:
+/// nearest slide with the same content, or the same index if nothing matches
+pub fn follow_slide(old: &[Slide], new: &[Slide], index: usize) -> usize {
+    if new.is_empty() {
+        return 0;
+    }
+
+    let nearest_match = old.get(index).and_then(|current| {
+        new.iter()
+            .enumerate()
+            .filter(|(_, slide)| same_slide(slide, current))
+            .min_by_key(|(i, _)| i.abs_diff(index))
+            .map(|(i, _)| i)
+    });
+
+    nearest_match.unwrap_or(index.min(new.len() - 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deck(texts: &[&str]) -> Vec<Slide> {
+        texts.iter().enumerate().map(|(i, text)| Slide::plain(i as u32 + 1, text)).collect()
+    }
+
+    #[test]
+    fn slides_are_the_same_when_they_show_the_same() {
+        let slide = Slide::plain(1, "hello");
+        assert!(same_slide(&slide, &Slide::plain(7, "hello")));
+        assert!(!same_slide(&slide, &Slide::plain(1, "hello!")));
+        assert!(!same_slide(&slide, &Slide { comments: Some("note".to_string()), ..Slide::plain(1, "hello") }));
+        assert!(!same_slide(&slide, &Slide { slide_type: SlideType::Code, ..Slide::plain(1, "hello") }));
+    }
+
+    #[test]
+    fn current_slide_is_followed_where_it_moved() {
+        let old = deck(&["a", "b", "c", "d"]);
+
+        // a slide added before it moves it down, one removed before it moves it up
+        assert_eq!(follow_slide(&old, &deck(&["new", "a", "b", "c", "d"]), 2), 3);
+        assert_eq!(follow_slide(&old, &deck(&["b", "c", "d"]), 2), 1);
+        // the nearest copy wins when it shows up more than once
+        assert_eq!(follow_slide(&old, &deck(&["c", "x", "x", "c", "x"]), 2), 3);
+        assert_eq!(follow_slide(&old, &deck(&["a", "c", "x", "x", "x", "c"]), 2), 1);
+        // and the earlier one on a tie
+        assert_eq!(follow_slide(&old, &deck(&["c", "x", "x", "x", "c"]), 2), 0);
+    }
+
+    #[test]
+    fn changed_or_deleted_slides_keep_the_position() {
+        let old = deck(&["a", "b", "c", "d"]);
+
+        assert_eq!(follow_slide(&old, &deck(&["a", "b", "c edited", "d"]), 2), 2);
+        assert_eq!(follow_slide(&old, &deck(&["a", "b", "d"]), 2), 2);
+        // a deck shorter than where the presenter was ends on its last slide
+        assert_eq!(follow_slide(&old, &deck(&["x", "y"]), 3), 1);
+        assert_eq!(follow_slide(&old, &deck(&["x"]), 2), 0);
+        // out of range before the reload too
+        assert_eq!(follow_slide(&old, &deck(&["x", "y"]), 9), 1);
+    }
+
+    #[test]
+    fn empty_decks_start_at_the_first_slide() {
+        assert_eq!(follow_slide(&deck(&["a", "b"]), &[], 1), 0);
+        assert_eq!(follow_slide(&[], &deck(&["a", "b"]), 1), 1);
+        assert_eq!(follow_slide(&[], &[], 0), 0);
+    }
+}
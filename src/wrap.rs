@@ -0,0 +1,248 @@
+use macroquad::prelude::*;
+
+use crate::markup::*;
+use crate::slide::*;
+use crate::style::*;
+use crate::utils::*;
+
+/// size lines are measured at while looking for the best width, widths scale with it
+const REFERENCE_SIZE: u16 = 100;
+
+/// how many widths between the widest word and the widest line are tried
+const CANDIDATES: usize = 48;
+
+/// a word and the whitespace in front of it, both keep the styles they had
+struct Atom {
+    space: Vec<Run>,
+    word: Vec<Run>,
+    space_width: f32,
+    word_width: f32,
+}
+
+/// a line split into its indentation and words
+struct Words {
+    indent: Vec<Run>,
+    indent_width: f32,
+    atoms: Vec<Atom>,
+}
+
+/// runs of a slice of characters, each character knows the run it came from
+fn to_runs(chars: &[(char, usize)], runs: &[Run]) -> Vec<Run> {
+    let mut out: Vec<(usize, Run)> = Vec::new();
+    for (c, index) in chars {
+        match out.last_mut() {
+            Some((last, run)) if last == index => run.text.push(*c),
+            _ => out.push((*index, Run { text: c.to_string(), ..runs[*index].clone() })),
+        }
+    }
+    out.into_iter().map(|(_, run)| run).collect()
+}
+
+fn split_words<M: MeasureText + ?Sized>(runs: &[Run], faces: &FontFaces<M>) -> Words {
+    let chars: Vec<(char, usize)> = runs
+        .iter()
+        .enumerate()
+        .flat_map(|(i, run)| run.text.chars().map(move |c| (c, i)))
+        .collect();
+    let width = |part: &[Run]| runs_width(part, faces, REFERENCE_SIZE);
+
+    let indent_len = chars.iter().take_while(|(c, _)| c.is_whitespace()).count();
+    let indent = to_runs(&chars[..indent_len], runs);
+    let mut atoms = Vec::new();
+
+    let mut i = indent_len;
+    while i < chars.len() {
+        let space_end = i + chars[i..].iter().take_while(|(c, _)| c.is_whitespace()).count();
+        let word_end = space_end + chars[space_end..].iter().take_while(|(c, _)| !c.is_whitespace()).count();
+        if word_end == space_end {
+            break; // trailing whitespace
+        }
+        let space = to_runs(&chars[i..space_end], runs);
+        let word = to_runs(&chars[space_end..word_end], runs);
+        atoms.push(Atom { space_width: width(&space), word_width: width(&word), space, word });
+        i = word_end;
+    }
+
+    Words { indent_width: width(&indent), indent, atoms }
+}
+
+/// greedy wrap of one line, returns the atom ranges of the lines it becomes
+fn break_line(words: &Words, max_width: f32) -> Vec<(usize, usize)> {
+    let mut breaks = Vec::new();
+    let mut start = 0;
+    let mut width = words.indent_width;
+
+    for (i, atom) in words.atoms.iter().enumerate() {
+        if i > start && width + atom.space_width + atom.word_width > max_width {
+            breaks.push((start, i));
+            start = i;
+            width = words.indent_width + atom.word_width;
+        } else if i == start {
+            width = words.indent_width + atom.word_width;
+        } else {
+            width += atom.space_width + atom.word_width;
+        }
+    }
+    breaks.push((start, words.atoms.len()));
+    breaks
+}
+
+/// widest line and line count of the whole slide wrapped at max_width
+fn measure_wrap(lines: &[Words], max_width: f32) -> (f32, usize) {
+    let mut widest = 0f32;
+    let mut count = 0;
+
+    for words in lines {
+        for (start, end) in break_line(words, max_width) {
+            let width = words.indent_width
+                + words.atoms[start..end]
+                    .iter()
+                    .enumerate()
+                    .map(|(i, atom)| if i == 0 { atom.word_width } else { atom.space_width + atom.word_width })
+                    .sum::<f32>();
+            widest = widest.max(width);
+            count += 1;
+        }
+    }
+
+    (widest, count)
+}
+
+/// reflows the lines of a text slide to the width that allows the largest font,
+/// the indentation of a line is repeated on the lines it wraps into.
+/// Returns the new lines with the index of the line each one came from
+pub fn wrap_lines<M: MeasureText + ?Sized>(lines: &[Vec<Run>], faces: &FontFaces<M>, area: &Vec2) -> Vec<(usize, Vec<Run>)> {
+    let words: Vec<Words> = lines.iter().map(|runs| split_words(runs, faces)).collect();
+
+    // same 96% of the area find_max_font_size fits into
+    let font_size = |(widest, count): (f32, usize)| {
+        let by_width = if widest > 0f32 { area.x * 0.96 * REFERENCE_SIZE as f32 / widest } else { f32::MAX };
+        by_width.min(area.y * 0.96 / count.max(1) as f32)
+    };
+
+    let narrowest = words
+        .iter()
+        .flat_map(|w| w.atoms.iter().map(move |atom| w.indent_width + atom.word_width))
+        .fold(0f32, f32::max);
+    let widest = measure_wrap(&words, f32::MAX).0;
+
+    // from wide to narrow, so a tie keeps fewer lines
+    let mut best = (widest, font_size((widest, lines.len())));
+    for i in 1..=CANDIDATES {
+        let max_width = widest - (widest - narrowest) * i as f32 / CANDIDATES as f32;
+        let size = font_size(measure_wrap(&words, max_width));
+        if size > best.1 {
+            best = (max_width, size);
+        }
+    }
+
+    words
+        .iter()
+        .enumerate()
+        .flat_map(|(index, words)| {
+            break_line(words, best.0).into_iter().map(move |(start, end)| {
+                let mut runs = words.indent.clone();
+                for (i, atom) in words.atoms[start..end].iter().enumerate() {
+                    if i > 0 {
+                        runs.extend(atom.space.iter().cloned());
+                    }
+                    runs.extend(atom.word.iter().cloned());
+                }
+                (index, runs)
+            })
+        })
+        .collect()
+}
+
+/// text lines of a slide as they are laid out, with the step each one shows up at.
+/// Text is wrapped when faces to measure it with are given, code never is
+pub fn slide_lines<M: MeasureText + ?Sized>(
+    spec: &SlideSpec,
+    wrap: Option<&FontFaces<M>>,
+    virtual_screen_size: &Vec2,
+) -> (Vec<Vec<Run>>, Vec<usize>) {
+    let lines = slide_runs(spec.slide_type, spec.language, &spec.text().unwrap_or_default());
+
+    match wrap {
+        Some(faces) if matches!(spec.slide_type, SlideType::Text | SlideType::TextImage) => {
+            let wrapped = wrap_lines(&lines, faces, &text_area(spec.slide_type, virtual_screen_size));
+            let reveal = wrapped.iter().map(|(index, _)| spec.reveal.get(*index).copied().unwrap_or(0)).collect();
+            (wrapped.into_iter().map(|(_, runs)| runs).collect(), reveal)
+        }
+        _ => (lines, spec.reveal.clone()),
+    }
+}
+
+/// breaks plain text between words so each line fits into max_width, a word longer than that gets a line of its own.
+/// Notes in the presenter window and on PDF pages are wrapped with it
+pub fn wrap_words<M: MeasureText + ?Sized>(line: &str, font: &M, font_size: u16, max_width: f32) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+
+    for word in line.split_whitespace() {
+        let candidate = if current.is_empty() { word.to_string() } else { format!("{} {}", current, word) };
+        if !current.is_empty() && font.measure_width(&candidate, font_size) > max_width {
+            lines.push(std::mem::replace(&mut current, word.to_string()));
+        } else {
+            current = candidate;
+        }
+    }
+    lines.push(current);
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::parse_deck;
+
+    fn texts(lines: &[Vec<Run>]) -> Vec<String> {
+        lines.iter().map(|runs| plain_text(std::slice::from_ref(runs))).collect()
+    }
+
+    #[test]
+    fn wraps_to_the_width_with_the_largest_font() {
+        let lines = [parse_line("  aaaa *bbbb* cccc dddd").0, parse_line("x").0];
+        let wrapped = wrap_lines(&lines, &FontFaces::regular(&Monospace), &vec2(1600f32, 600f32));
+
+        // unwrapped the 21 characters fit 146px, two lines of 11 fit 192px and four only 115px
+        let (indexes, runs): (Vec<usize>, Vec<Vec<Run>>) = wrapped.into_iter().unzip();
+        assert_eq!(indexes, [0, 0, 1]);
+        assert_eq!(texts(&runs), ["  aaaa bbbb", "  cccc dddd", "x"]);
+        assert!(runs[0].iter().any(|run| run.bold && run.text == "bbbb"));
+    }
+
+    #[test]
+    fn a_word_is_never_split() {
+        let lines = [parse_line("abcdefghijklmnopqrstuvwxyz a").0];
+        let wrapped = wrap_lines(&lines, &FontFaces::regular(&Monospace), &vec2(100f32, 1000f32));
+        let runs: Vec<Vec<Run>> = wrapped.into_iter().map(|(_, runs)| runs).collect();
+        assert_eq!(texts(&runs), ["abcdefghijklmnopqrstuvwxyz", "a"]);
+    }
+
+    #[test]
+    fn wrapped_lines_keep_their_step_and_code_is_left_alone() {
+        let (deck, _) = parse_deck("first\n+ aaaa bbbb cccc dddd\n\n```\naaaa bbbb cccc dddd eeee ffff\n```\n");
+        let faces = FontFaces::regular(&Monospace);
+        let size = vec2(1600f32, 600f32);
+
+        let (lines, reveal) = slide_lines(&deck.slides[0], Some(&faces), &size);
+        // both ways of making it three lines fit 192px, the wider one is kept
+        assert_eq!(texts(&lines), ["first", "aaaa bbbb cccc", "dddd"]);
+        assert_eq!(reveal, [0, 1, 1]);
+
+        let (lines, _) = slide_lines(&deck.slides[1], Some(&faces), &size);
+        assert_eq!(texts(&lines), ["aaaa bbbb cccc dddd eeee ffff"]);
+    }
+
+    #[test]
+    fn words_wrap_to_the_width() {
+        // five pixels a character at size 10
+        assert_eq!(wrap_words("aaa bbb ccc", &Monospace, 10, 36f32), ["aaa bbb", "ccc"]);
+        assert_eq!(wrap_words("aaa bbb ccc", &Monospace, 10, 60f32), ["aaa bbb ccc"]);
+        assert_eq!(wrap_words("a abcdefghij b", &Monospace, 10, 20f32), ["a", "abcdefghij", "b"]);
+        // runs of spaces are one break
+        assert_eq!(wrap_words("  aaa   bbb ", &Monospace, 10, 60f32), ["aaa bbb"]);
+        assert_eq!(wrap_words("", &Monospace, 10, 20f32), [""]);
+    }
+}